@@ -0,0 +1,470 @@
+// -----------------------------------------------------------------------------------------
+// Scattering functions (diffuse, GGX microfacet reflection and transmission)
+// -----------------------------------------------------------------------------------------
+
+use super::material::Material;
+use super::material::Principled;
+use super::random::Pcg32;
+use super::ray::RayHitResult;
+use super::spectrum;
+use super::spectrum::Dispersion;
+use super::vector::Vec3;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// -----------------------------------------------------------------------------------------
+
+const TWO_PI: f32 = std::f32::consts::PI * 2.0;
+const MIN_ALPHA: f32 = 0.0001;
+
+// -----------------------------------------------------------------------------------------
+// Metal Type (measured complex refractive indices at roughly 650, 550 and 450nm)
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Metal {
+    Aluminium,
+    Chromium,
+    Copper,
+    Gold,
+    Iron,
+    Platinum,
+    Silver,
+}
+
+// -----------------------------------------------------------------------------------------
+// Conductor Type (a named metal, or explicit eta and k per channel)
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[serde(untagged)]
+pub enum Conductor {
+    Named(Metal),
+    Measured { eta: Vec3, k: Vec3 },
+}
+
+// -----------------------------------------------------------------------------------------
+// Conductor Members
+impl Conductor {
+    pub fn ior(&self) -> (Vec3, Vec3) {
+        match self {
+            Conductor::Named(metal) => match metal {
+                Metal::Aluminium => (
+                    Vec3::new(1.65740, 0.88036, 0.52123),
+                    Vec3::new(9.22390, 6.26950, 4.83700),
+                ),
+                Metal::Chromium => (
+                    Vec3::new(3.10710, 3.18120, 2.32300),
+                    Vec3::new(3.33140, 3.32910, 3.13500),
+                ),
+                Metal::Copper => (
+                    Vec3::new(0.27105, 0.67693, 1.31640),
+                    Vec3::new(3.60920, 2.62480, 2.29210),
+                ),
+                Metal::Gold => (
+                    Vec3::new(0.18299, 0.42108, 1.37340),
+                    Vec3::new(3.42420, 2.34590, 1.77040),
+                ),
+                Metal::Iron => (
+                    Vec3::new(2.91140, 2.94970, 2.58450),
+                    Vec3::new(3.08930, 2.93180, 2.76700),
+                ),
+                Metal::Platinum => (
+                    Vec3::new(2.37570, 2.08470, 1.84530),
+                    Vec3::new(4.26550, 3.71530, 3.13650),
+                ),
+                Metal::Silver => (
+                    Vec3::new(0.15943, 0.14512, 0.13547),
+                    Vec3::new(3.92910, 3.19000, 2.38080),
+                ),
+            },
+            Conductor::Measured { eta, k } => (*eta, *k),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// BsdfSample Type (weight is bsdf * cosine / pdf)
+#[derive(Debug, Copy, Clone)]
+pub struct BsdfSample {
+    pub direction: Vec3,
+    pub weight: Vec3,
+}
+
+// -----------------------------------------------------------------------------------------
+// Bsdf Type (material inputs evaluated at a hit, in the hit's shading frame)
+#[derive(Debug, Copy, Clone)]
+pub struct Bsdf {
+    tangent: Vec3,
+    bitangent: Vec3,
+    normal: Vec3,
+    base_colour: Vec3,
+    roughness: f32,
+    alpha_x: f32,
+    alpha_y: f32,
+    metalness: f32,
+    conductor: Option<(Vec3, Vec3)>,
+    specular: Vec3,
+    sheen: Vec3,
+    clearcoat: f32,
+    clearcoat_alpha: f32,
+    transmission: f32,
+    ior: f32,
+    dispersion: Option<Dispersion>,
+    subsurface: f32,
+    disney_diffuse: bool,
+    reflected: f32,
+}
+
+// -----------------------------------------------------------------------------------------
+// Bsdf Constructor
+impl Bsdf {
+    pub fn new(material: &Material, hit: &RayHitResult) -> Bsdf {
+        if let Some(principled) = &material.principled {
+            return Bsdf::principled(principled, material, hit);
+        }
+        let (tangent, bitangent) = hit.tangent_frame();
+        let roughness = material.roughness.sample(hit).x.clamp(0.0, 1.0);
+        let (alpha_x, alpha_y) = anisotropic_alpha(roughness, material.anisotropy);
+
+        // Lambertian diffuse, optional specular coat (0.5 is 4% reflectance) and metal
+        Bsdf {
+            tangent,
+            bitangent,
+            normal: hit.shading_normal,
+            base_colour: material.diffuse.sample(hit),
+            roughness,
+            alpha_x,
+            alpha_y,
+            metalness: material.metalness.sample(hit).x.clamp(0.0, 1.0),
+            conductor: material.conductor.map(|conductor| conductor.ior()),
+            specular: Vec3::ONE * (0.08 * material.specular.sample(hit).x.clamp(0.0, 1.0)),
+            sheen: Vec3::BLACK,
+            clearcoat: 0.0,
+            clearcoat_alpha: MIN_ALPHA,
+            transmission: 0.0,
+            ior: 1.5,
+            dispersion: None,
+            subsurface: 0.0,
+            disney_diffuse: false,
+            reflected: 1.0 - material.absorbed,
+        }
+    }
+
+    fn principled(principled: &Principled, material: &Material, hit: &RayHitResult) -> Bsdf {
+        let (tangent, bitangent) = hit.tangent_frame();
+        let roughness = principled.roughness.sample(hit).x.clamp(0.0, 1.0);
+        let (alpha_x, alpha_y) = anisotropic_alpha(roughness, principled.anisotropy);
+        let clearcoat_roughness = principled.clearcoat_roughness.clamp(0.0, 1.0);
+
+        // Tints are the base colour normalised by luminance (Burley 2012)
+        let base_colour = principled.base_colour.sample(hit);
+        let luminance = (0.3 * base_colour.x) + (0.6 * base_colour.y) + (0.1 * base_colour.z);
+        let tint = if luminance > 0.0 {
+            base_colour / luminance
+        } else {
+            Vec3::ONE
+        };
+        let specular = principled.specular.sample(hit).x.clamp(0.0, 1.0);
+        let specular_tint = Vec3::lerp(Vec3::ONE, tint, principled.specular_tint);
+        let sheen_tint = Vec3::lerp(Vec3::ONE, tint, principled.sheen_tint);
+
+        Bsdf {
+            tangent,
+            bitangent,
+            normal: hit.shading_normal,
+            base_colour,
+            roughness,
+            alpha_x,
+            alpha_y,
+            metalness: principled.metallic.sample(hit).x.clamp(0.0, 1.0),
+            conductor: material.conductor.map(|conductor| conductor.ior()),
+            specular: specular_tint * (0.08 * specular),
+            sheen: sheen_tint * principled.sheen,
+            clearcoat: principled.clearcoat.clamp(0.0, 1.0),
+            clearcoat_alpha: (clearcoat_roughness * clearcoat_roughness).max(MIN_ALPHA),
+            transmission: principled.transmission.clamp(0.0, 1.0),
+            ior: match principled.dispersion {
+                Some(dispersion) => dispersion.ior(spectrum::LAMBDA_D),
+                None => principled.ior,
+            },
+            dispersion: principled.dispersion,
+            subsurface: principled.subsurface.clamp(0.0, 1.0),
+            disney_diffuse: true,
+            reflected: 1.0 - material.absorbed,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Bsdf Members
+impl Bsdf {
+    pub fn is_dispersive(&self) -> bool {
+        self.dispersion.is_some() && self.transmission > 0.0
+    }
+
+    pub fn at_wavelength(mut self, lambda: f32) -> Bsdf {
+        if let Some(dispersion) = self.dispersion {
+            self.ior = dispersion.ior(lambda);
+        }
+        self
+    }
+
+    pub fn sample(&self, outgoing: Vec3, rng: &mut Pcg32) -> Option<BsdfSample> {
+        // Move into shading space, flipping the frame when seen from behind
+        let (mut bitangent, mut normal) = (self.bitangent, self.normal);
+        let inside = Vec3::dot(outgoing, normal) < 0.0;
+        if inside {
+            bitangent = -bitangent;
+            normal = -normal;
+        }
+        let to_local = |v: Vec3| {
+            Vec3::new(
+                Vec3::dot(v, self.tangent),
+                Vec3::dot(v, bitangent),
+                Vec3::dot(v, normal),
+            )
+        };
+        let wo = to_local(outgoing);
+
+        // Pick a lobe, layer by layer, selection probabilities cancel with the blend weights
+        let (wi, weight) = if rng.gen::<f32>() < self.clearcoat_probability(wo) {
+            // Clearcoat (colourless, on top of everything else)
+            let alpha = self.clearcoat_alpha;
+            let wi = sample_reflection(wo, alpha, alpha, rng)?;
+            (wi, Vec3::ONE * masking_weight(wo, wi, alpha, alpha))
+        } else if rng.gen::<f32>() < self.metalness {
+            // Conductor (tinted by base colour when no measured ior is given)
+            let microfacet = sample_ggx_vndf(wo, self.alpha_x, self.alpha_y, rng.gen(), rng.gen());
+            let wi = Vec3::reflect(-wo, microfacet);
+            if wi.z <= 0.0 {
+                return None;
+            }
+            let cos_theta = Vec3::dot(wo, microfacet);
+            let fresnel = match self.conductor {
+                Some((eta, k)) => fresnel_conductor(cos_theta, eta, k),
+                None => fresnel_schlick(cos_theta, self.base_colour),
+            };
+            (wi, fresnel * masking_weight(wo, wi, self.alpha_x, self.alpha_y))
+        } else if rng.gen::<f32>() < self.transmission {
+            // Rough dielectric interface
+            self.sample_transmission(wo, inside, rng)?
+        } else {
+            // Dielectric specular over diffuse base
+            let fresnel = fresnel_schlick(wo.z, self.specular);
+            let probability = (fresnel.x + fresnel.y + fresnel.z) / 3.0;
+            if probability > 0.0 && rng.gen::<f32>() < probability {
+                let wi = sample_reflection(wo, self.alpha_x, self.alpha_y, rng)?;
+                let masking = masking_weight(wo, wi, self.alpha_x, self.alpha_y);
+                (wi, fresnel * (masking / probability))
+            } else {
+                let wi = sample_cosine_hemisphere(rng);
+                (wi, self.diffuse_weight(wo, wi))
+            }
+        };
+
+        // Back to world space
+        let direction = (self.tangent * wi.x) + (bitangent * wi.y) + (normal * wi.z);
+        Some(BsdfSample {
+            direction,
+            weight: weight * self.reflected,
+        })
+    }
+
+    fn clearcoat_probability(&self, wo: Vec3) -> f32 {
+        if self.clearcoat > 0.0 {
+            self.clearcoat * fresnel_schlick(wo.z, Vec3::ONE * 0.04).x
+        } else {
+            0.0
+        }
+    }
+
+    fn sample_transmission(&self, wo: Vec3, inside: bool, rng: &mut Pcg32) -> Option<(Vec3, Vec3)> {
+        // Reflect or refract about a visible microfacet normal, chosen by Fresnel
+        let eta = if inside { 1.0 / self.ior } else { self.ior };
+        let microfacet = sample_ggx_vndf(wo, self.alpha_x, self.alpha_y, rng.gen(), rng.gen());
+        let cos_theta = Vec3::dot(wo, microfacet);
+        let fresnel = fresnel_dielectric(cos_theta, eta);
+        let (wi, tint) = if rng.gen::<f32>() < fresnel {
+            let wi = Vec3::reflect(-wo, microfacet);
+            if wi.z <= 0.0 {
+                return None;
+            }
+            (wi, Vec3::ONE)
+        } else {
+            // Tint by sqrt so entering and leaving together give the base colour
+            let wi = refract(wo, microfacet, eta)?;
+            if wi.z >= 0.0 {
+                return None;
+            }
+            let colour = self.base_colour;
+            (wi, Vec3::new(colour.x.sqrt(), colour.y.sqrt(), colour.z.sqrt()))
+        };
+        Some((wi, tint * masking_weight(wo, wi, self.alpha_x, self.alpha_y)))
+    }
+
+    fn diffuse_weight(&self, wo: Vec3, wi: Vec3) -> Vec3 {
+        if !self.disney_diffuse {
+            return self.base_colour;
+        }
+
+        // Retro-reflection and subsurface flattening (Burley 2012), weights are bsdf * pi
+        let half = Vec3::normalize(wo + wi);
+        let cos_d = Vec3::dot(wi, half);
+        let (fl, fv) = (schlick_weight(wi.z), schlick_weight(wo.z));
+        let fd90 = 0.5 + (2.0 * self.roughness * cos_d * cos_d);
+        let fd = (1.0 + ((fd90 - 1.0) * fl)) * (1.0 + ((fd90 - 1.0) * fv));
+        let fss90 = self.roughness * cos_d * cos_d;
+        let fss = (1.0 + ((fss90 - 1.0) * fl)) * (1.0 + ((fss90 - 1.0) * fv));
+        let ss = 1.25 * ((fss * ((1.0 / (wi.z + wo.z).max(1e-4)) - 0.5)) + 0.5);
+        let diffuse = fd + ((ss - fd) * self.subsurface);
+        let sheen = self.sheen * (schlick_weight(cos_d) * std::f32::consts::PI);
+        (self.base_colour * diffuse) + sheen
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn anisotropic_alpha(roughness: f32, anisotropy: f32) -> (f32, f32) {
+    // Anisotropy stretches roughness along the tangent (Burley 2012)
+    let alpha = roughness * roughness;
+    let aspect = (1.0 - (0.9 * anisotropy.clamp(0.0, 1.0))).sqrt();
+    ((alpha / aspect).max(MIN_ALPHA), (alpha * aspect).max(MIN_ALPHA))
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn sample_reflection(wo: Vec3, alpha_x: f32, alpha_y: f32, rng: &mut Pcg32) -> Option<Vec3> {
+    // Reflect about a visible microfacet normal
+    let microfacet = sample_ggx_vndf(wo, alpha_x, alpha_y, rng.gen(), rng.gen());
+    let wi = Vec3::reflect(-wo, microfacet);
+    if wi.z <= 0.0 {
+        None
+    } else {
+        Some(wi)
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn masking_weight(wo: Vec3, wi: Vec3, alpha_x: f32, alpha_y: f32) -> f32 {
+    // Height correlated Smith G2 / G1, what remains of D * G2 / pdf under vndf sampling
+    let lambda_o = smith_lambda(wo, alpha_x, alpha_y);
+    let lambda_i = smith_lambda(wi, alpha_x, alpha_y);
+    (1.0 + lambda_o) / (1.0 + lambda_o + lambda_i)
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn refract(wo: Vec3, normal: Vec3, eta: f32) -> Option<Vec3> {
+    // Eta is the ratio of indices (transmitted over incident), none on total internal reflection
+    let cos_i = Vec3::dot(wo, normal);
+    let sin2_t = (1.0 - (cos_i * cos_i)).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(Vec3::normalize((-wo / eta) + (normal * ((cos_i / eta) - cos_t))))
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn sample_ggx_vndf(wo: Vec3, alpha_x: f32, alpha_y: f32, u1: f32, u2: f32) -> Vec3 {
+    // Visible normal sampling (Heitz 2018), stretch view into the hemisphere configuration
+    let vh = Vec3::normalize(Vec3::new(alpha_x * wo.x, alpha_y * wo.y, wo.z));
+
+    // Orthonormal basis around the view
+    let length_squared = (vh.x * vh.x) + (vh.y * vh.y);
+    let t1 = if length_squared > 0.0 {
+        Vec3::new(-vh.y, vh.x, 0.0) / length_squared.sqrt()
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let t2 = Vec3::cross(vh, t1);
+
+    // Sample projected area of the visible hemisphere
+    let r = u1.sqrt();
+    let phi = TWO_PI * u2;
+    let p1 = r * phi.cos();
+    let s = 0.5 * (1.0 + vh.z);
+    let p2 = ((1.0 - s) * (1.0 - (p1 * p1)).sqrt()) + (s * r * phi.sin());
+    let nh = (t1 * p1) + (t2 * p2) + (vh * (1.0 - (p1 * p1) - (p2 * p2)).max(0.0).sqrt());
+
+    // Unstretch
+    Vec3::normalize(Vec3::new(alpha_x * nh.x, alpha_y * nh.y, nh.z.max(1e-6)))
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn smith_lambda(w: Vec3, alpha_x: f32, alpha_y: f32) -> f32 {
+    let cos2 = w.z * w.z;
+    if cos2 <= 0.0 {
+        return 0.0;
+    }
+    let tan2 = ((alpha_x * alpha_x * w.x * w.x) + (alpha_y * alpha_y * w.y * w.y)) / cos2;
+    ((1.0 + tan2).sqrt() - 1.0) * 0.5
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn sample_cosine_hemisphere(rng: &mut Pcg32) -> Vec3 {
+    let r = rng.gen::<f32>().sqrt();
+    let phi = TWO_PI * rng.gen::<f32>();
+    let z = (1.0 - (r * r)).max(0.0).sqrt();
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn schlick_weight(cos_theta: f32) -> f32 {
+    (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5)
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn fresnel_schlick(cos_theta: f32, f0: Vec3) -> Vec3 {
+    f0 + ((Vec3::ONE - f0) * schlick_weight(cos_theta))
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn fresnel_dielectric(cos_theta: f32, eta: f32) -> f32 {
+    // Exact unpolarised reflectance, eta is the ratio of indices (transmitted over incident)
+    let cos_i = cos_theta.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - (cos_i * cos_i)) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0; // total internal reflection
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let rs = (cos_i - (eta * cos_t)) / (cos_i + (eta * cos_t));
+    let rp = ((eta * cos_i) - cos_t) / ((eta * cos_i) + cos_t);
+    0.5 * ((rs * rs) + (rp * rp))
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn fresnel_conductor(cos_theta: f32, eta: Vec3, k: Vec3) -> Vec3 {
+    Vec3::new(
+        fresnel_conductor_channel(cos_theta, eta.x, k.x),
+        fresnel_conductor_channel(cos_theta, eta.y, k.y),
+        fresnel_conductor_channel(cos_theta, eta.z, k.z),
+    )
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn fresnel_conductor_channel(cos_theta: f32, eta: f32, k: f32) -> f32 {
+    // Exact unpolarised reflectance for a complex index of refraction
+    let cos2 = cos_theta.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let (eta2, k2) = (eta * eta, k * k);
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = ((t0 * t0) + (4.0 * eta2 * k2)).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_theta * a;
+    let rs = (t1 - t2) / (t1 + t2);
+    let t3 = (cos2 * a2_plus_b2) + (sin2 * sin2);
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+    0.5 * (rp + rs)
+}
+
+// -----------------------------------------------------------------------------------------
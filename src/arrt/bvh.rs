@@ -0,0 +1,130 @@
+// -----------------------------------------------------------------------------------------
+
+use super::geometry::Aabb;
+use super::intersect;
+use super::ray::Ray;
+use super::ray::RayHitResult;
+use super::vector::Vec3;
+
+// -----------------------------------------------------------------------------------------
+
+const MAX_LEAF_SIZE: usize = 2;
+
+// -----------------------------------------------------------------------------------------
+// BvhNode Type (leaf when count > 0, otherwise children at first and first + 1)
+#[derive(Debug, Copy, Clone)]
+struct BvhNode {
+    bounds: Aabb,
+    first: usize,
+    count: usize,
+}
+
+// -----------------------------------------------------------------------------------------
+// Bvh Type
+#[derive(Debug, Default, Clone)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+}
+
+// -----------------------------------------------------------------------------------------
+// Bvh Constructor
+impl Bvh {
+    pub fn build(items: &[(usize, Aabb)]) -> Bvh {
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(items.len() * 2),
+            indices: Vec::with_capacity(items.len()),
+        };
+        if !items.is_empty() {
+            let mut items = items.to_vec();
+            bvh.nodes.push(BvhNode {
+                bounds: Aabb::EMPTY,
+                first: 0,
+                count: 0,
+            });
+            bvh.build_node(0, &mut items);
+        }
+        bvh
+    }
+
+    fn build_node(&mut self, node_index: usize, items: &mut [(usize, Aabb)]) {
+        // Calculate node bounds
+        let mut bounds = Aabb::EMPTY;
+        for (_, item_bounds) in items.iter() {
+            bounds.merge(item_bounds);
+        }
+        self.nodes[node_index].bounds = bounds;
+
+        // Create leaf?
+        if items.len() <= MAX_LEAF_SIZE {
+            self.nodes[node_index].first = self.indices.len();
+            self.nodes[node_index].count = items.len();
+            self.indices.extend(items.iter().map(|(index, _)| *index));
+            return;
+        }
+
+        // Split at median centroid along largest axis of the centroid bounds
+        let mut centroid_bounds = Aabb::EMPTY;
+        for (_, item_bounds) in items.iter() {
+            centroid_bounds.grow(item_bounds.centroid());
+        }
+        let axis = centroid_bounds.largest_axis();
+        items.sort_by(|(_, a), (_, b)| {
+            let a = Vec3::axis(a.centroid(), axis);
+            let b = Vec3::axis(b.centroid(), axis);
+            a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let (left, right) = items.split_at_mut(items.len() / 2);
+
+        // Recurse
+        let first_child = self.nodes.len();
+        let empty = BvhNode {
+            bounds: Aabb::EMPTY,
+            first: 0,
+            count: 0,
+        };
+        self.nodes.push(empty);
+        self.nodes.push(empty);
+        self.nodes[node_index].first = first_child;
+        self.build_node(first_child, left);
+        self.build_node(first_child + 1, right);
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Bvh Members
+impl Bvh {
+    pub fn intersect<F>(&self, ray: &Ray, mut intersect_item: F) -> RayHitResult
+    where
+        F: FnMut(usize) -> RayHitResult,
+    {
+        let mut closest = RayHitResult::MAX_HIT;
+        if self.nodes.is_empty() {
+            return closest;
+        }
+
+        // Walk tree, skipping nodes which start beyond the closest hit so far
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            match intersect::ray_aabb(ray, &node.bounds) {
+                Some((near, _)) if near < closest.distance => {}
+                _ => continue,
+            }
+            if node.count > 0 {
+                for &index in &self.indices[node.first..(node.first + node.count)] {
+                    let result = intersect_item(index);
+                    if result.hit && result.distance < closest.distance {
+                        closest = result;
+                    }
+                }
+            } else {
+                stack.push(node.first);
+                stack.push(node.first + 1);
+            }
+        }
+        closest
+    }
+}
+
+// -----------------------------------------------------------------------------------------
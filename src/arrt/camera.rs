@@ -1,14 +1,18 @@
 // -----------------------------------------------------------------------------------------
 
+use super::random::Pcg32;
 use super::ray::Ray;
+use super::ray::RayDifferentials;
 use super::vector::Vec3;
 
 // -----------------------------------------------------------------------------------------
 
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 
 // -----------------------------------------------------------------------------------------
 // Camera
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Camera {
     pub position: Vec3,
     pub lookat: Vec3,
@@ -17,7 +21,6 @@ pub struct Camera {
     pub right: Vec3,
     pub up: Vec3,
     pub forward: Vec3,
-    pub cached_rays: Vec<Ray>,
 }
 
 impl Camera {
@@ -36,7 +39,6 @@ impl Camera {
             right,
             up,
             forward,
-            cached_rays: Vec::new(),
         }
     }
 
@@ -44,13 +46,7 @@ impl Camera {
         (((y * image_width) + x) as usize) * samples_per_pixel
     }
 
-    pub fn update_cached_rays(
-        &mut self,
-        image_width: u32,
-        image_height: u32,
-        samples_per_pixel: usize,
-        rng: &mut StdRng,
-    ) {
+    pub fn frustum(&self, image_width: u32, image_height: u32) -> (Vec3, f32) {
         // Calculate aspect
         let aspect = image_width as f32 / image_height as f32;
 
@@ -66,46 +62,25 @@ impl Camera {
 
         // Calculate pixel size
         let pixel_size = near_width / image_width as f32;
+        (near_origin, pixel_size)
+    }
 
-        // Calculate cached ray count
-        let cached_ray_count = image_width as usize * image_height as usize * samples_per_pixel;
-        self.cached_rays.resize(cached_ray_count, Ray::FORWARD);
-
-        // Cache off rays
-        for pixel_y in 0..image_height {
-            let pixel_y_f = pixel_y as f32;
-            for pixel_x in 0..image_width {
-                let pixel_x_f = pixel_x as f32;
-                let pixel_index =
-                    Camera::get_pixel_index(pixel_x, pixel_y, image_width, samples_per_pixel);
-
-                // Store centroid ray
-                let ray = self.get_ray(pixel_x_f, pixel_y_f, near_origin, pixel_size);
-                self.cached_rays[pixel_index] = ray;
-
-                // Generate random sampling offsets
-                let additional_samples: usize = samples_per_pixel - 1;
-                let mut sample_offsets_x = vec![0.0; additional_samples];
-                let mut sample_offsets_y = vec![0.0; additional_samples];
-                for sample_index in 0..additional_samples {
-                    sample_offsets_x[sample_index] = rng.gen();
-                    sample_offsets_y[sample_index] = rng.gen();
-                }
-
-                // Store additional sample rays
-                for sample_index in 0..additional_samples {
-                    let offset_x = (sample_offsets_x[sample_index] - 0.5) * 0.99;
-                    let offset_y = (sample_offsets_y[sample_index] - 0.5) * 0.99;
-                    let ray = self.get_ray(
-                        pixel_x_f + offset_x,
-                        pixel_y_f + offset_y,
-                        near_origin,
-                        pixel_size,
-                    );
-                    self.cached_rays[pixel_index + sample_index + 1] = ray;
-                }
-            }
+    pub fn get_sample_ray(
+        &self,
+        pixel_x: u32,
+        pixel_y: u32,
+        sample_index: usize,
+        rng: &mut Pcg32,
+        near_origin: Vec3,
+        pixel_size: f32,
+    ) -> Ray {
+        // First sample is the pixel centroid, the rest are jittered (from the pixel's stream)
+        let (mut pixel_x_f, mut pixel_y_f) = (pixel_x as f32, pixel_y as f32);
+        if sample_index > 0 {
+            pixel_x_f += (rng.gen::<f32>() - 0.5) * 0.99;
+            pixel_y_f += (rng.gen::<f32>() - 0.5) * 0.99;
         }
+        self.get_ray(pixel_x_f, pixel_y_f, near_origin, pixel_size)
     }
 
     pub fn get_ray(&self, pixel_x: f32, pixel_y: f32, near_origin: Vec3, pixel_size: f32) -> Ray {
@@ -114,11 +89,20 @@ impl Camera {
         let vertical_offset = (pixel_y * pixel_size) + centroid_offset;
         let near_position =
             near_origin + (self.right * horizontal_offset) + (self.up * vertical_offset);
-        let direction = Vec3::normalize(near_position - self.position);
+        let to_near = near_position - self.position;
+        let distance = Vec3::length(to_near);
+        let direction = to_near / distance;
+
+        // Differentiate the normalised direction with respect to one pixel step
+        let differential = |step: Vec3| (step - (direction * Vec3::dot(direction, step))) / distance;
 
         Ray {
             origin: self.position,
             direction,
+            differentials: Some(RayDifferentials {
+                dx: differential(self.right * pixel_size),
+                dy: differential(self.up * pixel_size),
+            }),
         }
     }
 }
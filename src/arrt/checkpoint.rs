@@ -0,0 +1,218 @@
+// -----------------------------------------------------------------------------------------
+// Render checkpoints (accumulation and sampler state), so long renders can be resumed
+// -----------------------------------------------------------------------------------------
+
+use super::random::Pcg32;
+use super::vector::Vec3;
+use std::fs;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+// -----------------------------------------------------------------------------------------
+
+const MAGIC: &[u8; 8] = b"ARRTCKPT";
+const VERSION: u32 = 1;
+
+// FNV-1a (stable between runs and builds, unlike the standard library hasher)
+const FNV_OFFSET: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+// -----------------------------------------------------------------------------------------
+// Fingerprint Type (what a checkpoint was rendered from, resuming needs all of it to match)
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Fingerprint {
+    pub scene: u64,
+    pub preset: u64,
+    pub settings: u64,
+}
+
+// -----------------------------------------------------------------------------------------
+// Fingerprint Members
+impl Fingerprint {
+    pub fn check(&self, checkpoint: &Fingerprint, file: &str) -> Result<(), String> {
+        let changed = if self.scene != checkpoint.scene {
+            "scene or materials"
+        } else if self.preset != checkpoint.preset {
+            "quality preset"
+        } else if self.settings != checkpoint.settings {
+            "camera, seed or render options"
+        } else {
+            return Ok(());
+        };
+        Err(format!(
+            "ERROR: Cannot resume from checkpoint '{}' ({} changed)",
+            file, changed
+        ))
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Checkpoint Type (written between passes, samples of a pass are never half stored)
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub fingerprint: Fingerprint,
+    pub pass_index: usize,
+    pub time_s: f64,
+    pub colour_sums: Vec<Vec3>,
+    pub sample_counts: Vec<usize>,
+    pub luminance_sums: Vec<f32>,
+    pub luminance_square_sums: Vec<f32>,
+    pub converged: Vec<bool>,
+    pub pixel_rngs: Vec<Pcg32>,
+}
+
+// -----------------------------------------------------------------------------------------
+// Checkpoint Constructor
+impl Checkpoint {
+    pub fn load_from_file(file: &str) -> Result<Checkpoint, String> {
+        let invalid = || format!("ERROR: Invalid checkpoint file: '{}'", file);
+        let handle = fs::File::open(file).map_err(|error| {
+            format!(
+                "ERROR: Could not load checkpoint file: '{}' ({})",
+                file, error
+            )
+        })?;
+        let mut reader = Reader {
+            reader: BufReader::new(handle),
+        };
+
+        // Header
+        let mut magic = [0u8; 8];
+        reader.bytes(&mut magic).map_err(|_| invalid())?;
+        if &magic != MAGIC || reader.u32().map_err(|_| invalid())? != VERSION {
+            return Err(invalid());
+        }
+        let mut read = || -> std::io::Result<Checkpoint> {
+            let fingerprint = Fingerprint {
+                scene: reader.u64()?,
+                preset: reader.u64()?,
+                settings: reader.u64()?,
+            };
+            let pass_index = reader.u64()? as usize;
+            let time_s = reader.f64()?;
+            let pixel_count = reader.u64()? as usize;
+
+            // Pixels
+            let mut checkpoint = Checkpoint {
+                fingerprint,
+                pass_index,
+                time_s,
+                colour_sums: Vec::with_capacity(pixel_count),
+                sample_counts: Vec::with_capacity(pixel_count),
+                luminance_sums: Vec::with_capacity(pixel_count),
+                luminance_square_sums: Vec::with_capacity(pixel_count),
+                converged: Vec::with_capacity(pixel_count),
+                pixel_rngs: Vec::with_capacity(pixel_count),
+            };
+            for _ in 0..pixel_count {
+                let (x, y, z) = (reader.f32()?, reader.f32()?, reader.f32()?);
+                checkpoint.colour_sums.push(Vec3::new(x, y, z));
+                checkpoint.sample_counts.push(reader.u64()? as usize);
+                checkpoint.luminance_sums.push(reader.f32()?);
+                checkpoint.luminance_square_sums.push(reader.f32()?);
+                checkpoint.converged.push(reader.u8()? != 0);
+                let (state, increment) = (reader.u64()?, reader.u64()?);
+                checkpoint
+                    .pixel_rngs
+                    .push(Pcg32::from_state(state, increment));
+            }
+            Ok(checkpoint)
+        };
+        read().map_err(|_| invalid())
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Checkpoint Members
+impl Checkpoint {
+    pub fn save(&self, file: &str) -> Result<(), String> {
+        // Written next to the file first, so a crash part way through keeps the last checkpoint
+        let temp_file = format!("{}.tmp", file);
+        let write = || -> std::io::Result<()> {
+            let mut writer = BufWriter::new(fs::File::create(&temp_file)?);
+            writer.write_all(MAGIC)?;
+            writer.write_all(&VERSION.to_le_bytes())?;
+            writer.write_all(&self.fingerprint.scene.to_le_bytes())?;
+            writer.write_all(&self.fingerprint.preset.to_le_bytes())?;
+            writer.write_all(&self.fingerprint.settings.to_le_bytes())?;
+            writer.write_all(&(self.pass_index as u64).to_le_bytes())?;
+            writer.write_all(&self.time_s.to_le_bytes())?;
+            writer.write_all(&(self.colour_sums.len() as u64).to_le_bytes())?;
+            for pixel_index in 0..self.colour_sums.len() {
+                let colour_sum = self.colour_sums[pixel_index];
+                writer.write_all(&colour_sum.x.to_le_bytes())?;
+                writer.write_all(&colour_sum.y.to_le_bytes())?;
+                writer.write_all(&colour_sum.z.to_le_bytes())?;
+                writer.write_all(&(self.sample_counts[pixel_index] as u64).to_le_bytes())?;
+                writer.write_all(&self.luminance_sums[pixel_index].to_le_bytes())?;
+                writer.write_all(&self.luminance_square_sums[pixel_index].to_le_bytes())?;
+                writer.write_all(&[self.converged[pixel_index] as u8])?;
+                let (state, increment) = self.pixel_rngs[pixel_index].state();
+                writer.write_all(&state.to_le_bytes())?;
+                writer.write_all(&increment.to_le_bytes())?;
+            }
+            writer.flush()?;
+            fs::rename(&temp_file, file)
+        };
+        write().map_err(|error| {
+            format!(
+                "ERROR: Could not save checkpoint file: '{}' ({})",
+                file, error
+            )
+        })
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Reader Type (little endian values)
+struct Reader<R: Read> {
+    reader: R,
+}
+
+// -----------------------------------------------------------------------------------------
+// Reader Members
+impl<R: Read> Reader<R> {
+    fn bytes(&mut self, bytes: &mut [u8]) -> std::io::Result<()> {
+        self.reader.read_exact(bytes)
+    }
+
+    fn u8(&mut self) -> std::io::Result<u8> {
+        let mut bytes = [0u8; 1];
+        self.bytes(&mut bytes)?;
+        Ok(bytes[0])
+    }
+
+    fn u32(&mut self) -> std::io::Result<u32> {
+        let mut bytes = [0u8; 4];
+        self.bytes(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> std::io::Result<u64> {
+        let mut bytes = [0u8; 8];
+        self.bytes(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn f32(&mut self) -> std::io::Result<f32> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    fn f64(&mut self) -> std::io::Result<f64> {
+        Ok(f64::from_bits(self.u64()?))
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn hash(parts: &[&[u8]]) -> u64 {
+    // Each part is preceded by its length, so moving bytes between parts changes the hash
+    let mut hash = FNV_OFFSET;
+    for part in parts {
+        for byte in (part.len() as u64).to_le_bytes().iter().chain(part.iter()) {
+            hash = (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME);
+        }
+    }
+    hash
+}
+
+// -----------------------------------------------------------------------------------------
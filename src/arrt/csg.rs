@@ -0,0 +1,152 @@
+// -----------------------------------------------------------------------------------------
+
+use super::geometry::Aabb;
+use super::geometry::Cuboid;
+use super::geometry::Cylinder;
+use super::geometry::MaterialRef;
+use super::geometry::Sphere;
+use super::intersect;
+use super::ray::Ray;
+use super::ray::RayHitResult;
+use super::ray::RayInterval;
+use serde::{Deserialize, Serialize};
+
+// -----------------------------------------------------------------------------------------
+// CsgNode Type (tagged by "type" in scene files)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CsgNode {
+    // Solids
+    Sphere(Sphere),
+    Box(Cuboid),
+    Cylinder(Cylinder),
+
+    // Booleans
+    Union { a: Box<CsgNode>, b: Box<CsgNode> },
+    Intersection { a: Box<CsgNode>, b: Box<CsgNode> },
+    Difference { a: Box<CsgNode>, b: Box<CsgNode> },
+}
+
+// -----------------------------------------------------------------------------------------
+// CsgNode Members
+impl CsgNode {
+    pub fn intervals(&self, ray: &Ray) -> Vec<RayInterval> {
+        match self {
+            CsgNode::Sphere(sphere) => intersect::ray_sphere_interval(ray, sphere)
+                .into_iter()
+                .collect(),
+            CsgNode::Box(cuboid) => intersect::ray_box_interval(ray, cuboid)
+                .into_iter()
+                .collect(),
+            CsgNode::Cylinder(cylinder) => intersect::ray_cylinder_interval(ray, cylinder)
+                .into_iter()
+                .collect(),
+            CsgNode::Union { a, b } => {
+                combine(&a.intervals(ray), &b.intervals(ray), |in_a, in_b| in_a || in_b, false)
+            }
+            CsgNode::Intersection { a, b } => {
+                combine(&a.intervals(ray), &b.intervals(ray), |in_a, in_b| in_a && in_b, false)
+            }
+            CsgNode::Difference { a, b } => {
+                combine(&a.intervals(ray), &b.intervals(ray), |in_a, in_b| in_a && !in_b, true)
+            }
+        }
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn materials_mut(&mut self) -> Vec<&mut MaterialRef> {
+        match self {
+            CsgNode::Sphere(sphere) => vec![&mut sphere.material],
+            CsgNode::Box(cuboid) => vec![&mut cuboid.material],
+            CsgNode::Cylinder(cylinder) => vec![&mut cylinder.material],
+            CsgNode::Union { a, b }
+            | CsgNode::Intersection { a, b }
+            | CsgNode::Difference { a, b } => {
+                let mut materials = a.materials_mut();
+                materials.extend(b.materials_mut());
+                materials
+            }
+        }
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn bounds(&self) -> Aabb {
+        match self {
+            CsgNode::Sphere(sphere) => sphere.bounds(),
+            CsgNode::Box(cuboid) => cuboid.bounds(),
+            CsgNode::Cylinder(cylinder) => cylinder.bounds(),
+            CsgNode::Union { a, b } => {
+                let mut bounds = a.bounds();
+                bounds.merge(&b.bounds());
+                bounds
+            }
+            CsgNode::Intersection { a, .. } | CsgNode::Difference { a, .. } => a.bounds(),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// CsgObject Type (a boolean tree of solids placed in the scene)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CsgObject {
+    pub shape: CsgNode,
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn combine<F>(a: &[RayInterval], b: &[RayInterval], inside: F, flip_b: bool) -> Vec<RayInterval>
+where
+    F: Fn(bool, bool) -> bool,
+{
+    // Gather every boundary crossing from both operands, sorted along the ray
+    let mut crossings: Vec<(RayHitResult, bool)> = Vec::with_capacity((a.len() + b.len()) * 2);
+    for interval in a {
+        crossings.push((interval.enter, true));
+        crossings.push((interval.exit, true));
+    }
+    for interval in b {
+        crossings.push((interval.enter, false));
+        crossings.push((interval.exit, false));
+    }
+    crossings.sort_by(|(x, _), (y, _)| {
+        x.distance
+            .partial_cmp(&y.distance)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    // Sweep, emitting a boundary whenever the combined inside state changes
+    let mut result = Vec::new();
+    let (mut in_a, mut in_b, mut was_inside) = (false, false, false);
+    let mut enter = RayHitResult::NO_HIT;
+    for (crossing, from_a) in crossings {
+        if from_a {
+            in_a = !in_a;
+        } else {
+            in_b = !in_b;
+        }
+        let is_inside = inside(in_a, in_b);
+        if is_inside == was_inside {
+            continue;
+        }
+
+        // Surfaces carved out by the subtracted operand face the other way (dpdv too, so the
+        // tangent frame keeps its handedness)
+        let mut boundary = crossing;
+        if flip_b && !from_a {
+            boundary.normal = -boundary.normal;
+            boundary.shading_normal = -boundary.shading_normal;
+            boundary.dpdv = -boundary.dpdv;
+        }
+        if is_inside {
+            enter = boundary;
+        } else {
+            result.push(RayInterval::new(enter, boundary));
+        }
+        was_inside = is_inside;
+    }
+    result
+}
+
+// -----------------------------------------------------------------------------------------
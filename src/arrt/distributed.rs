@@ -0,0 +1,506 @@
+// -----------------------------------------------------------------------------------------
+// Distributed rendering, a coordinator hands tiles to workers connected over TCP
+// -----------------------------------------------------------------------------------------
+
+use super::camera::Camera;
+use super::framebuffer::Framebuffer;
+use super::material::MaterialBank;
+use super::mipmap::MipMap;
+use super::quality::QualityPreset;
+use super::renderer::{Region, RegionSums, Renderer};
+use super::scene::Scene;
+use super::texture::ImageCache;
+use super::vector::Vec3;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// -----------------------------------------------------------------------------------------
+
+const PROTOCOL_VERSION: u32 = 3;
+const TILE_SIZE: u32 = 128;
+const POLL_INTERVAL_MS: u64 = 10;
+const CONNECT_ATTEMPTS: u32 = 100;
+
+// Largest frame a peer may send (a scene, a texture image or a tile result)
+const MAX_FRAME_SIZE: u64 = 64 * 1024 * 1024;
+
+// Workers report while rendering a tile, one which is silent for longer loses its tile
+const HEARTBEAT_INTERVAL_S: u64 = 5;
+const WORKER_TIMEOUT_S: u64 = 30;
+
+// -----------------------------------------------------------------------------------------
+// Message Type (JSON frames, images and tile results follow as binary frames)
+#[derive(Serialize, Deserialize, Debug)]
+enum Message {
+    Setup(Box<Setup>),
+    Tile(Region),
+    Working,
+    Result(Region),
+    Done,
+}
+
+// -----------------------------------------------------------------------------------------
+// Event Type (what happened during a distributed render, reported by the coordinator)
+#[derive(Debug)]
+pub enum Event {
+    TileCompleted { completed: usize, tile_count: usize },
+    // Disconnected or stopped responding, its tile goes back on the queue
+    WorkerDropped { address: SocketAddr, error: String },
+    AcceptFailed(String),
+}
+
+// -----------------------------------------------------------------------------------------
+// Setup Type (everything a worker needs to render tiles of the coordinator's frame)
+#[derive(Serialize, Deserialize, Debug)]
+struct Setup {
+    version: u32,
+    quality: QualityPreset,
+    camera: Camera,
+    seed: u64,
+    spectral: bool,
+    debug_normals: bool,
+    debug_heatmap: bool,
+    debug_samples: bool,
+    scene: String,
+    materials: String,
+    materials_directory: PathBuf,
+    images: Vec<PathBuf>,
+}
+
+// -----------------------------------------------------------------------------------------
+// Frame Type (sums merged from finished tiles)
+struct Frame {
+    width: u32,
+    colour_sums: Vec<Vec3>,
+    sample_counts: Vec<usize>,
+}
+
+// -----------------------------------------------------------------------------------------
+// Frame Members
+impl Frame {
+    fn merge(&mut self, sums: &RegionSums) {
+        let region = sums.region;
+        for pixel_y in 0..region.height {
+            for pixel_x in 0..region.width {
+                let source = ((pixel_y * region.width) + pixel_x) as usize;
+                let target = (((region.y + pixel_y) * self.width) + region.x + pixel_x) as usize;
+                self.colour_sums[target] = sums.colour_sums[source];
+                self.sample_counts[target] = sums.sample_counts[source];
+            }
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Coordinator Type (tiles waiting for a worker, tiles of dropped workers are put back)
+struct Coordinator {
+    pending: Mutex<VecDeque<Region>>,
+    frame: Mutex<Frame>,
+    completed: AtomicUsize,
+    tile_count: usize,
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn coordinate<F>(
+    renderer: &Renderer,
+    scene: &Scene,
+    listener: TcpListener,
+    mut on_event: F,
+) -> Result<Framebuffer, String>
+where
+    F: FnMut(Event),
+{
+    // Tiles are rendered with all their samples, so time limits (and checkpoints) don't apply
+    if renderer.quality.time_limit_s.is_some() {
+        return Err(String::from(
+            "ERROR: Time limits aren't supported by distributed renders",
+        ));
+    }
+
+    // Setup sent to every worker, with the images its materials use
+    let materials = &renderer.materials;
+    let setup = Setup {
+        version: PROTOCOL_VERSION,
+        quality: renderer.quality.clone(),
+        camera: renderer.camera.clone(),
+        seed: renderer.seed,
+        spectral: renderer.spectral,
+        debug_normals: renderer.debug_normals,
+        debug_heatmap: renderer.debug_heatmap,
+        debug_samples: renderer.debug_samples,
+        scene: scene.description(),
+        materials: materials.description(),
+        materials_directory: materials.directory().to_path_buf(),
+        images: materials.images().to_vec(),
+    };
+    let mut setup_frames = vec![serde_json::to_vec(&Message::Setup(Box::new(setup))).unwrap()];
+    if setup_frames[0].len() as u64 > MAX_FRAME_SIZE {
+        return Err(String::from("ERROR: Scene is too large to send to workers"));
+    }
+    for image in materials.images() {
+        let bytes = fs::read(image).map_err(|error| {
+            format!(
+                "ERROR: Could not load image file: '{}' ({})",
+                image.display(),
+                error
+            )
+        })?;
+        if bytes.len() as u64 > MAX_FRAME_SIZE {
+            return Err(format!(
+                "ERROR: Image file is too large to send to workers: '{}'",
+                image.display()
+            ));
+        }
+        setup_frames.push(bytes);
+    }
+
+    // Split the frame into tiles
+    let (width, height) = (renderer.quality.image_width, renderer.quality.image_height);
+    let mut pending = VecDeque::new();
+    for y in (0..height).step_by(TILE_SIZE as usize) {
+        for x in (0..width).step_by(TILE_SIZE as usize) {
+            pending.push_back(Region {
+                x,
+                y,
+                width: TILE_SIZE.min(width - x),
+                height: TILE_SIZE.min(height - y),
+            });
+        }
+    }
+    let pixel_count = (width * height) as usize;
+    let coordinator = Coordinator {
+        tile_count: pending.len(),
+        pending: Mutex::new(pending),
+        frame: Mutex::new(Frame {
+            width,
+            colour_sums: vec![Vec3::BLACK; pixel_count],
+            sample_counts: vec![0; pixel_count],
+        }),
+        completed: AtomicUsize::new(0),
+    };
+
+    // Accept workers until every tile is done, each is served on its own thread (which passes
+    // its events back to this one)
+    listener
+        .set_nonblocking(true)
+        .map_err(|error| format!("ERROR: Could not listen for workers ({})", error))?;
+    let (event_sender, events) = mpsc::channel();
+    thread::scope(|scope| {
+        let mut reported = 0;
+        while reported < coordinator.tile_count {
+            match listener.accept() {
+                Ok((stream, address)) => {
+                    let (coordinator, setup_frames) = (&coordinator, &setup_frames);
+                    let event_sender = event_sender.clone();
+                    scope.spawn(move || {
+                        if let Err(error) = serve_worker(stream, coordinator, setup_frames) {
+                            let error = error.to_string();
+                            let _ = event_sender.send(Event::WorkerDropped { address, error });
+                        }
+                    });
+                }
+                Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+                }
+                Err(error) => on_event(Event::AcceptFailed(error.to_string())),
+            }
+            events.try_iter().for_each(&mut on_event);
+            let completed = coordinator.completed.load(Ordering::Relaxed);
+            if completed != reported {
+                reported = completed;
+                on_event(Event::TileCompleted {
+                    completed,
+                    tile_count: coordinator.tile_count,
+                });
+            }
+        }
+    });
+    events.try_iter().for_each(&mut on_event);
+
+    // Resolve merged sums
+    let frame = coordinator.frame.into_inner().unwrap();
+    Ok(renderer.resolve_sums(&frame.colour_sums, &frame.sample_counts))
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn serve_worker(
+    stream: TcpStream,
+    coordinator: &Coordinator,
+    setup_frames: &[Vec<u8>],
+) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(WORKER_TIMEOUT_S)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(WORKER_TIMEOUT_S)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    for frame in setup_frames {
+        write_frame(&mut writer, frame)?;
+    }
+    writer.flush()?;
+    loop {
+        // Wait for a tile (one may come back from a dropped worker until all are done)
+        let tile = loop {
+            if let Some(tile) = coordinator.pending.lock().unwrap().pop_front() {
+                break tile;
+            }
+            if coordinator.completed.load(Ordering::Relaxed) == coordinator.tile_count {
+                write_message(&mut writer, &Message::Done)?;
+                return writer.flush();
+            }
+            thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+        };
+
+        // Render it remotely, putting it back if the worker drops (or stops responding)
+        let mut render_tile = || -> io::Result<RegionSums> {
+            write_message(&mut writer, &Message::Tile(tile))?;
+            writer.flush()?;
+            loop {
+                match read_message(&mut reader)? {
+                    Message::Working => continue,
+                    Message::Result(region) if region == tile => {
+                        return read_sums(&mut reader, region)
+                    }
+                    _ => return Err(invalid_data("unexpected message")),
+                }
+            }
+        };
+        match render_tile().map_err(timed_out) {
+            Ok(sums) => {
+                coordinator.frame.lock().unwrap().merge(&sums);
+                coordinator.completed.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(error) => {
+                coordinator.pending.lock().unwrap().push_front(tile);
+                return Err(error);
+            }
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn work(address: &str, thread_count: usize) -> Result<(), String> {
+    // Retry for a while, the coordinator may still be starting
+    let lost = |error: io::Error| {
+        format!(
+            "ERROR: Lost connection to coordinator '{}' ({})",
+            address, error
+        )
+    };
+    let mut attempt = 0;
+    let stream = loop {
+        match TcpStream::connect(address) {
+            Ok(stream) => break stream,
+            Err(error) => {
+                attempt += 1;
+                if attempt >= CONNECT_ATTEMPTS {
+                    return Err(format!(
+                        "ERROR: Could not connect to coordinator '{}' ({})",
+                        address, error
+                    ));
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    };
+    let mut reader = BufReader::new(stream.try_clone().map_err(lost)?);
+    let mut writer = BufWriter::new(stream);
+
+    // Setup, images are decoded from the received files rather than loaded
+    let setup = match read_message(&mut reader).map_err(lost)? {
+        Message::Setup(setup) if setup.version == PROTOCOL_VERSION => setup,
+        Message::Setup(_) => {
+            return Err(String::from(
+                "ERROR: Coordinator is a different version of arrt",
+            ))
+        }
+        _ => return Err(lost(invalid_data("unexpected message"))),
+    };
+    let mut image_cache = ImageCache::new();
+    for image in &setup.images {
+        let bytes = read_frame(&mut reader).map_err(lost)?;
+        let mipmap = MipMap::load_from_memory(&bytes).map_err(|error| {
+            format!(
+                "ERROR: Could not load image file: '{}' ({})",
+                image.display(),
+                error
+            )
+        })?;
+        image_cache.insert(image.clone(), Arc::new(mipmap));
+    }
+    let materials = MaterialBank::from_description(
+        &setup.materials,
+        &setup.materials_directory,
+        &mut image_cache,
+    )?;
+    let scene = Scene::from_description(address, &setup.scene, &materials)?;
+    let mut renderer = Renderer::new(setup.quality, materials, setup.camera);
+    renderer.seed = setup.seed;
+    renderer.spectral = setup.spectral;
+    renderer.debug_normals = setup.debug_normals;
+    renderer.debug_heatmap = setup.debug_heatmap;
+    renderer.debug_samples = setup.debug_samples;
+    renderer.thread_count = thread_count;
+
+    // Render tiles until the coordinator is done
+    loop {
+        match read_message(&mut reader).map_err(lost)? {
+            Message::Tile(region) => {
+                let sums = render_tile(&renderer, &scene, region, &mut writer).map_err(lost)?;
+                write_message(&mut writer, &Message::Result(region)).map_err(lost)?;
+                write_sums(&mut writer, &sums).map_err(lost)?;
+                writer.flush().map_err(lost)?;
+            }
+            Message::Done => return Ok(()),
+            _ => return Err(lost(invalid_data("unexpected message"))),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn render_tile<W: Write>(
+    renderer: &Renderer,
+    scene: &Scene,
+    region: Region,
+    writer: &mut W,
+) -> io::Result<RegionSums> {
+    // Rendered on another thread, telling the coordinator this worker is still alive meanwhile
+    thread::scope(|scope| {
+        let (sums_sender, sums) = mpsc::channel();
+        scope.spawn(move || sums_sender.send(renderer.render_region(scene, region)));
+        loop {
+            match sums.recv_timeout(Duration::from_secs(HEARTBEAT_INTERVAL_S)) {
+                Ok(sums) => return Ok(sums),
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    write_message(writer, &Message::Working)?;
+                    writer.flush()?;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    return Err(invalid_data("tile render failed"))
+                }
+            }
+        }
+    })
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn write_frame<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    // Length prefixed
+    writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn read_frame<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let length = read_u64(reader)?;
+    if length > MAX_FRAME_SIZE {
+        return Err(invalid_data("frame too large"));
+    }
+    let mut bytes = vec![0u8; length as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn write_message<W: Write>(writer: &mut W, message: &Message) -> io::Result<()> {
+    write_frame(writer, &serde_json::to_vec(message).unwrap())
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn read_message<R: Read>(reader: &mut R) -> io::Result<Message> {
+    serde_json::from_slice(&read_frame(reader)?).map_err(|error| invalid_data(&error.to_string()))
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn write_sums<W: Write>(writer: &mut W, sums: &RegionSums) -> io::Result<()> {
+    // Colour sum (three floats) and sample count of each pixel, as stored
+    let mut bytes = Vec::with_capacity(sums.colour_sums.len() * 20);
+    for (colour_sum, sample_count) in sums.colour_sums.iter().zip(sums.sample_counts.iter()) {
+        bytes.extend_from_slice(&colour_sum.x.to_le_bytes());
+        bytes.extend_from_slice(&colour_sum.y.to_le_bytes());
+        bytes.extend_from_slice(&colour_sum.z.to_le_bytes());
+        bytes.extend_from_slice(&(*sample_count as u64).to_le_bytes());
+    }
+    write_frame(writer, &bytes)
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn read_sums<R: Read>(reader: &mut R, region: Region) -> io::Result<RegionSums> {
+    let bytes = read_frame(reader)?;
+    let pixel_count = (region.width * region.height) as usize;
+    if bytes.len() != pixel_count * 20 {
+        return Err(invalid_data("tile size mismatch"));
+    }
+    let mut sums = RegionSums {
+        region,
+        colour_sums: Vec::with_capacity(pixel_count),
+        sample_counts: Vec::with_capacity(pixel_count),
+    };
+    let mut data = bytes.as_slice();
+    for _ in 0..pixel_count {
+        let (x, y, z) = (
+            read_f32(&mut data)?,
+            read_f32(&mut data)?,
+            read_f32(&mut data)?,
+        );
+        sums.colour_sums.push(Vec3::new(x, y, z));
+        sums.sample_counts.push(read_u64(&mut data)? as usize);
+    }
+    Ok(sums)
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn read_f32<R: Read>(reader: &mut R) -> io::Result<f32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(f32::from_le_bytes(bytes))
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn invalid_data(error: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn timed_out(error: io::Error) -> io::Error {
+    // Read timeouts are would block on some platforms
+    match error.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("no response for {}s", WORKER_TIMEOUT_S),
+        ),
+        _ => error,
+    }
+}
+
+// -----------------------------------------------------------------------------------------
@@ -0,0 +1,73 @@
+// -----------------------------------------------------------------------------------------
+// Float images (top row first), for comparing renders and writing them to disk
+// -----------------------------------------------------------------------------------------
+
+use super::vector::Vec3;
+use image::{ImageOutputFormat, Rgb, RgbImage};
+use std::io::Cursor;
+
+// -----------------------------------------------------------------------------------------
+// Framebuffer Type
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Vec3>,
+}
+
+// -----------------------------------------------------------------------------------------
+// Framebuffer Constructor
+impl Framebuffer {
+    pub fn new(width: u32, height: u32, pixels: Vec<Vec3>) -> Framebuffer {
+        assert_eq!(pixels.len(), (width * height) as usize);
+        Framebuffer {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn load_from_file(file: &str) -> Result<Framebuffer, String> {
+        // Decode to float, 8 bit files map to [0, 1] (HDR files keep their range)
+        let image = image::open(file)
+            .map_err(|error| format!("ERROR: Could not load image file: '{}' ({})", file, error))?;
+        let image = image.to_rgb32f();
+        let pixels = image
+            .pixels()
+            .map(|pixel| Vec3::new(pixel[0], pixel[1], pixel[2]))
+            .collect();
+        Ok(Framebuffer::new(image.width(), image.height(), pixels))
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Framebuffer Members
+impl Framebuffer {
+    pub fn to_rgb_image(&self) -> RgbImage {
+        let mut image = RgbImage::new(self.width, self.height);
+        let mut pixel = [0u8; 4];
+        for (colour, output) in self.pixels.iter().zip(image.pixels_mut()) {
+            Vec3::copy_to_pixel(*colour, &mut pixel);
+            *output = Rgb([pixel[0], pixel[1], pixel[2]]);
+        }
+        image
+    }
+
+    pub fn save(&self, file: &str) -> Result<(), String> {
+        // Format comes from the file extension
+        self.to_rgb_image()
+            .save(file)
+            .map_err(|error| format!("ERROR: Could not save image file: '{}' ({})", file, error))
+    }
+
+    pub fn encode_png(&self) -> Result<Vec<u8>, String> {
+        // In memory (for example to send over a connection)
+        let mut bytes = Cursor::new(Vec::new());
+        self.to_rgb_image()
+            .write_to(&mut bytes, ImageOutputFormat::Png)
+            .map_err(|error| format!("ERROR: Could not encode image ({})", error))?;
+        Ok(bytes.into_inner())
+    }
+}
+
+// -----------------------------------------------------------------------------------------
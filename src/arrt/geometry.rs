@@ -1,22 +1,298 @@
 // -----------------------------------------------------------------------------------------
 
-use super::misc::StringLiteral;
+use super::csg::CsgObject;
+use super::material::MaterialId;
+use super::sdf::SdfObject;
 use super::vector::Vec3;
+use serde::{Deserialize, Serialize};
+
+// -----------------------------------------------------------------------------------------
+// MaterialRef Type (named in scene files, bound to a material bank id at scene load)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(from = "String", into = "String")]
+pub struct MaterialRef {
+    pub name: String,
+    pub id: MaterialId,
+}
+
+impl From<String> for MaterialRef {
+    fn from(name: String) -> MaterialRef {
+        MaterialRef { name, id: 0 }
+    }
+}
+
+impl From<MaterialRef> for String {
+    fn from(material: MaterialRef) -> String {
+        material.name
+    }
+}
 
 // -----------------------------------------------------------------------------------------
 // Sphere Type
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Sphere {
     pub centre: Vec3,
     pub radius: f32,
-    pub material: StringLiteral,
+    pub material: MaterialRef,
+}
+
+impl Sphere {
+    pub fn bounds(&self) -> Aabb {
+        Aabb::around(self.centre, Vec3::ONE * self.radius)
+    }
 }
 
 // -----------------------------------------------------------------------------------------
 // Plane Type
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Plane {
     pub position: Vec3,
     pub normal: Vec3,
-    pub diffuse: Vec3,
+    pub material: MaterialRef,
+}
+
+// -----------------------------------------------------------------------------------------
+// Cuboid Type (axis-aligned box)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Cuboid {
+    pub min: Vec3,
+    pub max: Vec3,
+    pub material: MaterialRef,
+}
+
+impl Cuboid {
+    pub fn bounds(&self) -> Aabb {
+        Aabb::new(self.min, self.max)
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Disk Type
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Disk {
+    pub centre: Vec3,
+    pub normal: Vec3,
+    pub radius: f32,
+    pub material: MaterialRef,
+}
+
+// -----------------------------------------------------------------------------------------
+// Quad Type (parallelogram spanned by two edges from a corner)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Quad {
+    pub corner: Vec3,
+    pub edge_u: Vec3,
+    pub edge_v: Vec3,
+    pub material: MaterialRef,
+}
+
+// -----------------------------------------------------------------------------------------
+// Cylinder Type (capped, extends from base along axis by height)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Cylinder {
+    pub base: Vec3,
+    pub axis: Vec3,
+    pub radius: f32,
+    pub height: f32,
+    pub material: MaterialRef,
+}
+
+impl Cylinder {
+    pub fn bounds(&self) -> Aabb {
+        let axis = Vec3::normalize(self.axis);
+        let top = self.base + (axis * self.height);
+        let mut bounds = Aabb::from_disk(self.base, axis, self.radius);
+        bounds.merge(&Aabb::from_disk(top, axis, self.radius));
+        bounds
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Cone Type (capped at base, apex at base + axis * height)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Cone {
+    pub base: Vec3,
+    pub axis: Vec3,
+    pub radius: f32,
+    pub height: f32,
+    pub material: MaterialRef,
+}
+
+// -----------------------------------------------------------------------------------------
+// Torus Type (ring of major_radius around axis, tube of minor_radius)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Torus {
+    pub centre: Vec3,
+    pub axis: Vec3,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+    pub material: MaterialRef,
+}
+
+// -----------------------------------------------------------------------------------------
+// Primitive Type (tagged by "type" in scene files)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Primitive {
+    Sphere(Sphere),
+    Plane(Plane),
+    Box(Cuboid),
+    Disk(Disk),
+    Quad(Quad),
+    Cylinder(Cylinder),
+    Cone(Cone),
+    Torus(Torus),
+    Sdf(SdfObject),
+    Csg(CsgObject),
+}
+
+// -----------------------------------------------------------------------------------------
+// Primitive Members
+impl Primitive {
+    pub fn origin(&self) -> Vec3 {
+        // Reference point for object space texturing
+        match self {
+            Primitive::Sphere(sphere) => sphere.centre,
+            Primitive::Plane(plane) => plane.position,
+            Primitive::Box(cuboid) => cuboid.min,
+            Primitive::Disk(disk) => disk.centre,
+            Primitive::Quad(quad) => quad.corner,
+            Primitive::Cylinder(cylinder) => cylinder.base,
+            Primitive::Cone(cone) => cone.base,
+            Primitive::Torus(torus) => torus.centre,
+            Primitive::Sdf(_) | Primitive::Csg(_) => match self.bounds() {
+                Some(bounds) => bounds.centroid(),
+                None => Vec3::ZERO,
+            },
+        }
+    }
+
+    pub fn materials_mut(&mut self) -> Vec<&mut MaterialRef> {
+        match self {
+            Primitive::Sphere(sphere) => vec![&mut sphere.material],
+            Primitive::Plane(plane) => vec![&mut plane.material],
+            Primitive::Box(cuboid) => vec![&mut cuboid.material],
+            Primitive::Disk(disk) => vec![&mut disk.material],
+            Primitive::Quad(quad) => vec![&mut quad.material],
+            Primitive::Cylinder(cylinder) => vec![&mut cylinder.material],
+            Primitive::Cone(cone) => vec![&mut cone.material],
+            Primitive::Torus(torus) => vec![&mut torus.material],
+            Primitive::Sdf(sdf) => vec![&mut sdf.material],
+            Primitive::Csg(csg) => csg.shape.materials_mut(),
+        }
+    }
+
+    pub fn bounds(&self) -> Option<Aabb> {
+        match self {
+            Primitive::Sphere(sphere) => Some(sphere.bounds()),
+            Primitive::Plane(_) => None,
+            Primitive::Box(cuboid) => Some(cuboid.bounds()),
+            Primitive::Disk(disk) => Some(Aabb::from_disk(disk.centre, disk.normal, disk.radius)),
+            Primitive::Quad(quad) => {
+                let far = quad.corner + quad.edge_u + quad.edge_v;
+                let mut bounds = Aabb::new(quad.corner, quad.corner);
+                bounds.grow(quad.corner + quad.edge_u);
+                bounds.grow(quad.corner + quad.edge_v);
+                bounds.grow(far);
+                Some(bounds)
+            }
+            Primitive::Cylinder(cylinder) => Some(cylinder.bounds()),
+            Primitive::Cone(cone) => {
+                let axis = Vec3::normalize(cone.axis);
+                let apex = cone.base + (axis * cone.height);
+                let mut bounds = Aabb::from_disk(cone.base, axis, cone.radius);
+                bounds.grow(apex);
+                Some(bounds)
+            }
+            Primitive::Torus(torus) => {
+                let axis = Vec3::normalize(torus.axis);
+                let radius = torus.major_radius + torus.minor_radius;
+                let thickness = axis * torus.minor_radius;
+                let mut bounds = Aabb::from_disk(torus.centre + thickness, axis, radius);
+                bounds.merge(&Aabb::from_disk(torus.centre - thickness, axis, radius));
+                Some(bounds)
+            }
+            Primitive::Sdf(sdf) => sdf.shape.bounds(),
+            Primitive::Csg(csg) => Some(csg.shape.bounds()),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Aabb Type
+#[derive(Debug, Copy, Clone)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+// -----------------------------------------------------------------------------------------
+// Aabb Constructor
+impl Aabb {
+    pub const EMPTY: Aabb = Aabb {
+        min: Vec3 {
+            x: f32::MAX,
+            y: f32::MAX,
+            z: f32::MAX,
+        },
+        max: Vec3 {
+            x: f32::MIN,
+            y: f32::MIN,
+            z: f32::MIN,
+        },
+    };
+
+    pub fn new(min: Vec3, max: Vec3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    pub fn around(centre: Vec3, half_size: Vec3) -> Aabb {
+        Aabb::new(centre - half_size, centre + half_size)
+    }
+
+    pub fn from_disk(centre: Vec3, normal: Vec3, radius: f32) -> Aabb {
+        // Per-axis extent of a disk is radius * sin(angle between axis and normal)
+        let n = Vec3::normalize(normal);
+        let extent = Vec3::new(
+            (1.0 - (n.x * n.x)).max(0.0).sqrt(),
+            (1.0 - (n.y * n.y)).max(0.0).sqrt(),
+            (1.0 - (n.z * n.z)).max(0.0).sqrt(),
+        ) * radius;
+        Aabb::new(centre - extent, centre + extent)
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Aabb Members
+impl Aabb {
+    pub fn grow(&mut self, point: Vec3) {
+        self.min = Vec3::min(self.min, point);
+        self.max = Vec3::max(self.max, point);
+    }
+
+    pub fn merge(&mut self, other: &Aabb) {
+        self.min = Vec3::min(self.min, other.min);
+        self.max = Vec3::max(self.max, other.max);
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_size(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn largest_axis(&self) -> usize {
+        let size = self.max - self.min;
+        if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        }
+    }
 }
 
 // -----------------------------------------------------------------------------------------
@@ -1,25 +1,58 @@
 // -----------------------------------------------------------------------------------------
 
+use super::csg::CsgObject;
+use super::geometry::Aabb;
+use super::geometry::Cone;
+use super::geometry::Cuboid;
+use super::geometry::Cylinder;
+use super::geometry::Disk;
 use super::geometry::Plane;
+use super::geometry::Primitive;
+use super::geometry::Quad;
 use super::geometry::Sphere;
+use super::geometry::Torus;
+use super::material::MaterialId;
+use super::polynomial;
 use super::ray::Ray;
 use super::ray::RayHitResult;
+use super::ray::RayInterval;
+use super::sdf::SdfObject;
+use super::vector::Vec2;
 use super::vector::Vec3;
 
 // -----------------------------------------------------------------------------------------
 
+const TOLLERANCE: f32 = 0.001;
+const TWO_PI: f32 = std::f32::consts::PI * 2.0;
+
+// -----------------------------------------------------------------------------------------
+
+pub fn ray_primitive(ray: &Ray, primitive: &Primitive) -> RayHitResult {
+    match primitive {
+        Primitive::Sphere(sphere) => ray_sphere(ray, sphere),
+        Primitive::Plane(plane) => ray_plane(ray, plane),
+        Primitive::Box(cuboid) => ray_box(ray, cuboid),
+        Primitive::Disk(disk) => ray_disk(ray, disk),
+        Primitive::Quad(quad) => ray_quad(ray, quad),
+        Primitive::Cylinder(cylinder) => ray_cylinder(ray, cylinder),
+        Primitive::Cone(cone) => ray_cone(ray, cone),
+        Primitive::Torus(torus) => ray_torus(ray, torus),
+        Primitive::Sdf(sdf) => ray_sdf(ray, sdf),
+        Primitive::Csg(csg) => ray_csg(ray, csg),
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
 pub fn ray_plane(ray: &Ray, plane: &Plane) -> RayHitResult {
-    const TOLLERANCE: f32 = 0.001;
-    let denom = Vec3::dot(plane.normal, ray.direction);
+    let normal = Vec3::normalize(plane.normal);
+    let denom = Vec3::dot(normal, ray.direction);
     if denom.abs() > TOLLERANCE {
         let plane_to_ray = plane.position - ray.origin;
-        let t = Vec3::dot(plane_to_ray, plane.normal) / denom;
+        let t = Vec3::dot(plane_to_ray, normal) / denom;
         let hit_pos = ray.get_point(t);
-        //let row = (hit_pos.z * 0.25).round().abs() % 2.0;
-        //let col = (hit_pos.x * 0.25).round().abs() % 2.0;
-        //let pick = (row + col) % 2.0;
-        //let diffuse = plane.diffuse; // Vec3::lerp(plane.diffuse, plane.diffuse * 0.5, pick);
-        RayHitResult::new(t > TOLLERANCE, t, hit_pos, plane.normal, "mirror")
+        let uv = planar_uv(hit_pos - plane.position, normal);
+        RayHitResult::new(t > TOLLERANCE, t, hit_pos, normal, uv, plane.material.id)
     } else {
         RayHitResult::NO_HIT
     }
@@ -28,39 +61,510 @@ pub fn ray_plane(ray: &Ray, plane: &Plane) -> RayHitResult {
 // -----------------------------------------------------------------------------------------
 
 pub fn ray_sphere(ray: &Ray, sphere: &Sphere) -> RayHitResult {
+    match ray_sphere_interval(ray, sphere) {
+        Some(interval) => first_boundary(&interval),
+        None => RayHitResult::NO_HIT,
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn ray_sphere_interval(ray: &Ray, sphere: &Sphere) -> Option<RayInterval> {
     let m: Vec3 = ray.origin - sphere.centre;
     let b: f32 = Vec3::dot(m, ray.direction);
     let c: f32 = Vec3::dot(m, m) - (sphere.radius * sphere.radius);
 
     // Exit if r’s origin outside s (c > 0) and r pointing away from s (b > 0)
     if c > 0.0 && b > 0.0 {
-        return RayHitResult::NO_HIT;
+        return None;
     }
     let discr: f32 = b * b - c;
 
     // A negative discriminant corresponds to ray missing sphere
     if discr < 0.0 {
+        return None;
+    }
+
+    // Ray now found to intersect sphere, compute both t values of intersection
+    let sqrt_discr = discr.sqrt();
+    let hit = |t: f32| {
+        let hit_position = ray.get_point(t);
+        let normal = Vec3::normalize(hit_position - sphere.centre);
+        let uv = spherical_uv(normal);
+        let (dpdu, dpdv) = spherical_partials(normal, sphere.radius);
+        RayHitResult::new(true, t, hit_position, normal, uv, sphere.material.id)
+            .with_partials(dpdu, dpdv)
+    };
+    Some(RayInterval::new(hit(-b - sqrt_discr), hit(-b + sqrt_discr)))
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn ray_aabb(ray: &Ray, aabb: &Aabb) -> Option<(f32, f32)> {
+    // Slab test, returns entry and exit distances
+    let inv_direction = Vec3::ONE / ray.direction;
+    let t0 = (aabb.min - ray.origin) * inv_direction;
+    let t1 = (aabb.max - ray.origin) * inv_direction;
+    let t_min = Vec3::min(t0, t1);
+    let t_max = Vec3::max(t0, t1);
+    let near = t_min.x.max(t_min.y).max(t_min.z);
+    let far = t_max.x.min(t_max.y).min(t_max.z);
+    if near <= far && far > 0.0 {
+        Some((near, far))
+    } else {
+        None
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn ray_box(ray: &Ray, cuboid: &Cuboid) -> RayHitResult {
+    match ray_box_interval(ray, cuboid) {
+        Some(interval) => first_boundary(&interval),
+        None => RayHitResult::NO_HIT,
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn ray_box_interval(ray: &Ray, cuboid: &Cuboid) -> Option<RayInterval> {
+    let (near, far) = ray_aabb(ray, &cuboid.bounds())?;
+    Some(RayInterval::new(
+        box_hit(ray, cuboid, near),
+        box_hit(ray, cuboid, far),
+    ))
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn ray_disk(ray: &Ray, disk: &Disk) -> RayHitResult {
+    let normal = Vec3::normalize(disk.normal);
+    ray_disk_internal(ray, disk.centre, normal, disk.radius, disk.material.id)
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn ray_quad(ray: &Ray, quad: &Quad) -> RayHitResult {
+    let n = Vec3::cross(quad.edge_u, quad.edge_v);
+    let normal = Vec3::normalize(n);
+    let t = match plane_distance(ray, quad.corner, normal) {
+        Some(t) => t,
+        None => return RayHitResult::NO_HIT,
+    };
+
+    // Express hit in terms of the two edges
+    let hit_position = ray.get_point(t);
+    let offset = hit_position - quad.corner;
+    let w = n / Vec3::dot(n, n);
+    let alpha = Vec3::dot(w, Vec3::cross(offset, quad.edge_v));
+    let beta = Vec3::dot(w, Vec3::cross(quad.edge_u, offset));
+    if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
         return RayHitResult::NO_HIT;
     }
+    let uv = Vec2::new(alpha, beta);
+    RayHitResult::new(true, t, hit_position, normal, uv, quad.material.id)
+        .with_partials(quad.edge_u, quad.edge_v)
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn ray_cylinder(ray: &Ray, cylinder: &Cylinder) -> RayHitResult {
+    match ray_cylinder_interval(ray, cylinder) {
+        Some(interval) => first_boundary(&interval),
+        None => RayHitResult::NO_HIT,
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn ray_cylinder_interval(ray: &Ray, cylinder: &Cylinder) -> Option<RayInterval> {
+    let axis = Vec3::normalize(cylinder.axis);
+    let (radius, height) = (cylinder.radius, cylinder.height);
+
+    // Split ray into components along and perpendicular to the axis
+    let m = ray.origin - cylinder.base;
+    let d_along = Vec3::dot(ray.direction, axis);
+    let m_along = Vec3::dot(m, axis);
+    let d_perp = ray.direction - (axis * d_along);
+    let m_perp = m - (axis * m_along);
+
+    // Find span inside the infinite tube: |m_perp + d_perp * t|^2 <= r^2
+    let a = Vec3::dot(d_perp, d_perp);
+    let c = Vec3::dot(m_perp, m_perp) - (radius * radius);
+    let (tube_near, tube_far) = if a < TOLLERANCE * TOLLERANCE {
+        if c > 0.0 {
+            return None; // parallel to axis, outside tube
+        }
+        (f32::NEG_INFINITY, f32::INFINITY)
+    } else {
+        let b = Vec3::dot(m_perp, d_perp);
+        let discr = (b * b) - (a * c);
+        if discr < 0.0 {
+            return None;
+        }
+        let sqrt_discr = discr.sqrt();
+        ((-b - sqrt_discr) / a, (-b + sqrt_discr) / a)
+    };
+
+    // Find span between the cap planes: 0 <= y <= h
+    let (slab_near, slab_far) = if d_along.abs() < TOLLERANCE * TOLLERANCE {
+        if m_along < 0.0 || m_along > height {
+            return None; // perpendicular to axis, outside caps
+        }
+        (f32::NEG_INFINITY, f32::INFINITY)
+    } else {
+        let t0 = -m_along / d_along;
+        let t1 = (height - m_along) / d_along;
+        (t0.min(t1), t0.max(t1))
+    };
+
+    // Overlap of both spans is inside the cylinder
+    let near = tube_near.max(slab_near);
+    let far = tube_far.min(slab_far);
+    if near > far {
+        return None;
+    }
+    let hit = |t: f32, on_cap: bool| {
+        let hit_position = ray.get_point(t);
+        let y = m_along + (d_along * t);
+        let radial = (hit_position - cylinder.base) - (axis * y);
+        if on_cap {
+            let normal = if y > height * 0.5 { axis } else { -axis };
+            let uv = planar_uv(radial / radius, normal);
+            let uv = Vec2::new((uv.x + 1.0) * 0.5, (uv.y + 1.0) * 0.5);
+            let (tangent, bitangent) = Vec3::orthonormal_basis(normal);
+            RayHitResult::new(true, t, hit_position, normal, uv, cylinder.material.id)
+                .with_partials(tangent * (radius * 2.0), bitangent * (radius * 2.0))
+        } else {
+            let normal = Vec3::normalize(radial);
+            let uv = Vec2::new(azimuth(radial, axis), y / height);
+            let dpdu = Vec3::cross(axis, radial) * TWO_PI;
+            RayHitResult::new(true, t, hit_position, normal, uv, cylinder.material.id)
+                .with_partials(dpdu, axis * height)
+        }
+    };
+    Some(RayInterval::new(
+        hit(near, slab_near > tube_near),
+        hit(far, slab_far < tube_far),
+    ))
+}
 
-    // Ray now found to intersect sphere, compute smallest t value of intersection
-    let mut hit_distance: f32 = -b - discr.sqrt();
+// -----------------------------------------------------------------------------------------
+
+pub fn ray_cone(ray: &Ray, cone: &Cone) -> RayHitResult {
+    let axis = Vec3::normalize(cone.axis);
+    let (radius, height) = (cone.radius, cone.height);
 
-    // If t is negative, ray started inside sphere so clamp t to zero
-    if hit_distance < 0.0 {
-        hit_distance = 0.0;
+    // Radius shrinks linearly from base to apex: |p_perp| = k * (h - y)
+    let k = radius / height;
+    let k2 = k * k;
+    let m = ray.origin - cone.base;
+    let d_along = Vec3::dot(ray.direction, axis);
+    let m_along = Vec3::dot(m, axis);
+    let to_apex = height - m_along;
+    let a = Vec3::dot(ray.direction, ray.direction) - ((1.0 + k2) * d_along * d_along);
+    let b = 2.0 * (Vec3::dot(m, ray.direction) - (m_along * d_along) + (k2 * to_apex * d_along));
+    let c = Vec3::dot(m, m) - (m_along * m_along) - (k2 * to_apex * to_apex);
+
+    // Test side (discarding the mirrored nappe above the apex)
+    let mut closest = RayHitResult::MAX_HIT;
+    let roots = polynomial::solve_quadratic([c as f64, b as f64, a as f64]);
+    for &root in roots.as_slice() {
+        let t = root as f32;
+        let y = m_along + (d_along * t);
+        if t > TOLLERANCE && t < closest.distance && y >= 0.0 && y <= height {
+            let hit_position = ray.get_point(t);
+            let radial = (hit_position - cone.base) - (axis * y);
+            let gradient = radial + (axis * (k2 * (height - y)));
+            let normal = if Vec3::length_squared(gradient) > 0.0 {
+                Vec3::normalize(gradient)
+            } else {
+                axis // apex
+            };
+            let uv = Vec2::new(azimuth(radial, axis), y / height);
+            closest = RayHitResult::new(true, t, hit_position, normal, uv, cone.material.id);
+            if Vec3::length_squared(radial) > 0.0 {
+                // Around the axis, and up the slant from base rim to apex
+                let dpdu = Vec3::cross(axis, radial) * TWO_PI;
+                let dpdv = (axis * height) - (Vec3::normalize(radial) * radius);
+                closest = closest.with_partials(dpdu, dpdv);
+            }
+        }
     }
 
-    // Return valid hit
-    const TOLLERANCE: f32 = 0.001;
-    let hit_position: Vec3 = ray.origin + (ray.direction * hit_distance);
-    RayHitResult::new(
-        hit_distance > TOLLERANCE,
-        hit_distance,
-        hit_position,
-        Vec3::UP,
-        sphere.material,
+    // Test base cap
+    let base_cap = ray_disk_internal(ray, cone.base, -axis, radius, cone.material.id);
+    nearest(closest, base_cap)
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn ray_torus(ray: &Ray, torus: &Torus) -> RayHitResult {
+    let (major, minor) = (torus.major_radius, torus.minor_radius);
+
+    // Skip ahead to the bounding sphere to keep the quartic well conditioned
+    let bounding_radius = major + minor;
+    let m = ray.origin - torus.centre;
+    let b = Vec3::dot(m, ray.direction);
+    let c = Vec3::dot(m, m) - (bounding_radius * bounding_radius);
+    let discr = (b * b) - c;
+    if (c > 0.0 && b > 0.0) || discr < 0.0 {
+        return RayHitResult::NO_HIT;
+    }
+    let t_start = (-b - discr.sqrt()).max(0.0);
+
+    // Move into torus space (ring in the xz plane, axis along y)
+    let axis = Vec3::normalize(torus.axis);
+    let (tangent, bitangent) = Vec3::orthonormal_basis(axis);
+    let to_local = |v: Vec3| Vec3::new(Vec3::dot(v, tangent), Vec3::dot(v, axis), Vec3::dot(v, bitangent));
+    let o = to_local(m + (ray.direction * t_start));
+    let d = to_local(ray.direction);
+
+    // Solve (|p|^2 - R^2 - r^2)^2 + 4R^2(p.y^2 - r^2) = 0
+    let (ox, oy, oz) = (o.x as f64, o.y as f64, o.z as f64);
+    let (dx, dy, dz) = (d.x as f64, d.y as f64, d.z as f64);
+    let (r2_major, r2_minor) = ((major * major) as f64, (minor * minor) as f64);
+    let dd = (dx * dx) + (dy * dy) + (dz * dz);
+    let f = (ox * dx) + (oy * dy) + (oz * dz);
+    let e = (ox * ox) + (oy * oy) + (oz * oz) - r2_major - r2_minor;
+    let roots = polynomial::solve_quartic([
+        (e * e) + (4.0 * r2_major * ((oy * oy) - r2_minor)),
+        (4.0 * f * e) + (8.0 * r2_major * oy * dy),
+        (4.0 * f * f) + (2.0 * dd * e) + (4.0 * r2_major * dy * dy),
+        4.0 * dd * f,
+        dd * dd,
+    ]);
+
+    // Find nearest root in front of the ray
+    let mut t_local = f32::MAX;
+    for &root in roots.as_slice() {
+        let t = root as f32;
+        if t + t_start > TOLLERANCE && t < t_local {
+            t_local = t;
+        }
+    }
+    if t_local == f32::MAX {
+        return RayHitResult::NO_HIT;
+    }
+
+    // Calculate normal from gradient (in torus space)
+    let p = o + (d * t_local);
+    let sum = Vec3::dot(p, p) - (major * major) - (minor * minor);
+    let local_normal = (p * sum) + (Vec3::UP * (2.0 * major * major * p.y));
+    let normal = Vec3::normalize(
+        (tangent * local_normal.x) + (axis * local_normal.y) + (bitangent * local_normal.z),
+    );
+
+    // Calculate uv (around ring, around tube)
+    let ring_radius = ((p.x * p.x) + (p.z * p.z)).sqrt();
+    let ring_distance = ring_radius - major;
+    let uv = Vec2::new(
+        (p.z.atan2(p.x) / TWO_PI) + 0.5,
+        (p.y.atan2(ring_distance) / TWO_PI) + 0.5,
+    );
+
+    // Calculate partials (around ring, around tube)
+    let from_local = |v: Vec3| (tangent * v.x) + (axis * v.y) + (bitangent * v.z);
+    let ring_direction = Vec3::new(p.x, 0.0, p.z) / ring_radius.max(TOLLERANCE);
+    let dpdu = from_local(Vec3::new(-p.z, 0.0, p.x) * TWO_PI);
+    let dpdv = from_local(((Vec3::UP * ring_distance) - (ring_direction * p.y)) * TWO_PI);
+    let t = t_local + t_start;
+    RayHitResult::new(true, t, ray.get_point(t), normal, uv, torus.material.id)
+        .with_partials(dpdu, dpdv)
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn ray_sdf(ray: &Ray, sdf: &SdfObject) -> RayHitResult {
+    const MAX_STEPS: u32 = 256;
+    const MAX_DISTANCE: f32 = 1000.0;
+    const HIT_THRESHOLD: f32 = 0.0001;
+
+    // Only march the span of the ray which lies within the bounds (if any)
+    let (mut t, t_end) = match sdf.shape.bounds() {
+        Some(bounds) => match ray_aabb(ray, &bounds) {
+            Some((near, far)) => (near.max(0.0), far),
+            None => return RayHitResult::NO_HIT,
+        },
+        None => (0.0, MAX_DISTANCE),
+    };
+
+    // Sphere trace (on the unsigned distance, so rays starting inside find the exit)
+    let step_scale = sdf.shape.step_scale();
+    for _ in 0..MAX_STEPS {
+        let position = ray.get_point(t);
+        let distance = sdf.shape.distance(position).abs();
+        if distance < HIT_THRESHOLD && t > TOLLERANCE {
+            let normal = sdf.shape.normal(position);
+            let uv = spherical_uv(normal);
+            return RayHitResult::new(true, t, position, normal, uv, sdf.material.id);
+        }
+        t += distance.max(HIT_THRESHOLD) * step_scale;
+        if t > t_end {
+            break;
+        }
+    }
+    RayHitResult::NO_HIT
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn ray_csg(ray: &Ray, csg: &CsgObject) -> RayHitResult {
+    // Walk spans in order, taking the first boundary in front of the ray
+    for interval in csg.shape.intervals(ray) {
+        let result = first_boundary(&interval);
+        if result.hit {
+            return result;
+        }
+    }
+    RayHitResult::NO_HIT
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn first_boundary(interval: &RayInterval) -> RayHitResult {
+    // Use exit if ray started inside
+    if interval.enter.distance > TOLLERANCE {
+        interval.enter
+    } else if interval.exit.distance > TOLLERANCE {
+        interval.exit
+    } else {
+        RayHitResult::NO_HIT
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn box_hit(ray: &Ray, cuboid: &Cuboid, t: f32) -> RayHitResult {
+    // Face is the axis along which the hit lies furthest from the centre (relative to size)
+    let hit_position = ray.get_point(t);
+    let centre = (cuboid.min + cuboid.max) * 0.5;
+    let half_size = (cuboid.max - cuboid.min) * 0.5;
+    let offset = hit_position - centre;
+
+    // Flat boxes (zero extent on an axis) are hit on that face, facing the ray
+    let local_axis = |offset: f32, half_size: f32, direction: f32| {
+        if half_size > 0.0 {
+            offset / half_size
+        } else {
+            -direction.signum()
+        }
+    };
+    let local = Vec3::new(
+        local_axis(offset.x, half_size.x, ray.direction.x),
+        local_axis(offset.y, half_size.y, ray.direction.y),
+        local_axis(offset.z, half_size.z, ray.direction.z),
+    );
+    let magnitude = Vec3::abs(local);
+    let size = cuboid.max - cuboid.min;
+    let (size_x, size_y, size_z) = (
+        Vec3::new(size.x, 0.0, 0.0),
+        Vec3::new(0.0, size.y, 0.0),
+        Vec3::new(0.0, 0.0, size.z),
+    );
+    let (normal, u, v, dpdu, dpdv) = if magnitude.x >= magnitude.y && magnitude.x >= magnitude.z {
+        (Vec3::new(local.x.signum(), 0.0, 0.0), local.z, local.y, size_z, size_y)
+    } else if magnitude.y >= magnitude.z {
+        (Vec3::new(0.0, local.y.signum(), 0.0), local.x, local.z, size_x, size_z)
+    } else {
+        (Vec3::new(0.0, 0.0, local.z.signum()), local.x, local.y, size_x, size_y)
+    };
+    let uv = Vec2::new((u + 1.0) * 0.5, (v + 1.0) * 0.5);
+    RayHitResult::new(true, t, hit_position, normal, uv, cuboid.material.id)
+        .with_partials(dpdu, dpdv)
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn ray_disk_internal(
+    ray: &Ray,
+    centre: Vec3,
+    normal: Vec3,
+    radius: f32,
+    material: MaterialId,
+) -> RayHitResult {
+    let t = match plane_distance(ray, centre, normal) {
+        Some(t) => t,
+        None => return RayHitResult::NO_HIT,
+    };
+    let hit_position = ray.get_point(t);
+    let offset = hit_position - centre;
+    if Vec3::length_squared(offset) > (radius * radius) {
+        return RayHitResult::NO_HIT;
+    }
+    let uv = planar_uv(offset / radius, normal);
+    let uv = Vec2::new((uv.x + 1.0) * 0.5, (uv.y + 1.0) * 0.5);
+    let (tangent, bitangent) = Vec3::orthonormal_basis(normal);
+    RayHitResult::new(true, t, hit_position, normal, uv, material)
+        .with_partials(tangent * (radius * 2.0), bitangent * (radius * 2.0))
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn plane_distance(ray: &Ray, position: Vec3, normal: Vec3) -> Option<f32> {
+    let denom = Vec3::dot(normal, ray.direction);
+    if denom.abs() <= TOLLERANCE {
+        return None;
+    }
+    let t = Vec3::dot(position - ray.origin, normal) / denom;
+    if t > TOLLERANCE {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn nearest(a: RayHitResult, b: RayHitResult) -> RayHitResult {
+    if b.hit && (!a.hit || b.distance < a.distance) {
+        b
+    } else {
+        a
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn planar_uv(offset: Vec3, normal: Vec3) -> Vec2 {
+    let (tangent, bitangent) = Vec3::orthonormal_basis(normal);
+    Vec2::new(Vec3::dot(offset, tangent), Vec3::dot(offset, bitangent))
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn spherical_uv(normal: Vec3) -> Vec2 {
+    Vec2::new(
+        (normal.z.atan2(normal.x) / TWO_PI) + 0.5,
+        (normal.y.clamp(-1.0, 1.0).asin() / std::f32::consts::PI) + 0.5,
     )
 }
 
 // -----------------------------------------------------------------------------------------
+
+fn spherical_partials(normal: Vec3, radius: f32) -> (Vec3, Vec3) {
+    // Derivatives of position with respect to spherical_uv (longitude, latitude)
+    let cos_latitude = ((normal.x * normal.x) + (normal.z * normal.z)).sqrt();
+    let (cos_longitude, sin_longitude) = if cos_latitude > 1e-6 {
+        (normal.x / cos_latitude, normal.z / cos_latitude)
+    } else {
+        (1.0, 0.0) // pole
+    };
+    let dpdu = Vec3::new(-normal.z, 0.0, normal.x) * (TWO_PI * radius);
+    let dpdv = Vec3::new(
+        -normal.y * cos_longitude,
+        cos_latitude,
+        -normal.y * sin_longitude,
+    ) * (std::f32::consts::PI * radius);
+    (dpdu, dpdv)
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn azimuth(radial: Vec3, axis: Vec3) -> f32 {
+    let (tangent, bitangent) = Vec3::orthonormal_basis(axis);
+    let angle = Vec3::dot(radial, bitangent).atan2(Vec3::dot(radial, tangent));
+    (angle / TWO_PI) + 0.5
+}
+
+// -----------------------------------------------------------------------------------------
@@ -2,18 +2,20 @@
 
 use super::material::MaterialBank;
 use super::quality::QualityPreset;
+use super::scene::Scene;
+use std::sync::Arc;
 
 // -----------------------------------------------------------------------------------------
 
-type ImageBuffer = std::vec::Vec<[u8; 4]>;
-
-// -----------------------------------------------------------------------------------------
-
+// Shared read-only by worker threads, reloads build a new job rather than mutating this one
+#[derive(Clone)]
 pub struct Job {
     pub quality: QualityPreset,
-    pub materials: MaterialBank,
+    pub materials: Arc<MaterialBank>,
+    pub scene: Arc<Scene>,
     pub debug_normals: bool,
     pub debug_heatmap: bool,
+    pub spectral: bool,
 }
 
 // -----------------------------------------------------------------------------------------
@@ -22,15 +24,19 @@ impl Job {
     pub fn new(
         quality: QualityPreset,
         materials: MaterialBank,
+        scene: Scene,
         debug_normals: bool,
         debug_heatmap: bool,
+        spectral: bool,
     ) -> Job {
         // Setup job
         Job {
             quality: quality,
-            materials: materials,
+            materials: Arc::new(materials),
+            scene: Arc::new(scene),
             debug_normals,
             debug_heatmap,
+            spectral,
         }
     }
 
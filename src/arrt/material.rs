@@ -1,66 +1,408 @@
 // -----------------------------------------------------------------------------------------
 
+use super::bsdf::Conductor;
+use super::ray::RayHitResult;
+use super::spectrum::Dispersion;
+use super::subsurface::Subsurface;
+use super::texture::ImageCache;
+use super::texture::Texture;
+use super::vector::Vec2;
 use super::vector::Vec3;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
 use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
 
 // -----------------------------------------------------------------------------------------
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct Material {
     #[serde(default)]
     pub name: String,
-    pub diffuse: Vec3,
+    #[serde(default)]
+    pub parent: Option<String>,
+    #[serde(default)]
+    pub diffuse: Texture,
+    #[serde(default)]
     pub absorbed: f32,
+    #[serde(default)]
+    pub roughness: Texture,
+    #[serde(default)]
+    pub metalness: Texture,
+    #[serde(default)]
+    pub specular: Texture,
+    #[serde(default)]
+    pub anisotropy: f32,
+    #[serde(default)]
+    pub conductor: Option<Conductor>,
+    #[serde(default)]
+    pub emission: Texture,
+    #[serde(default)]
+    pub normal_map: Option<Texture>,
+    #[serde(default)]
+    pub bump_map: Option<Texture>,
+    #[serde(default = "default_bump_strength")]
+    pub bump_strength: f32,
+    #[serde(default)]
+    pub principled: Option<Principled>,
+    #[serde(default)]
+    pub subsurface: Option<Subsurface>,
+}
+
+// -----------------------------------------------------------------------------------------
+// Principled Type (Disney style parameters, replaces diffuse/roughness/metalness/specular)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Principled {
+    #[serde(default = "default_base_colour")]
+    pub base_colour: Texture,
+    #[serde(default)]
+    pub metallic: Texture,
+    #[serde(default = "default_half")]
+    pub roughness: Texture,
+    #[serde(default = "default_half")]
+    pub specular: Texture,
+    #[serde(default)]
+    pub specular_tint: f32,
+    #[serde(default)]
+    pub anisotropy: f32,
+    #[serde(default)]
+    pub sheen: f32,
+    #[serde(default = "default_sheen_tint")]
+    pub sheen_tint: f32,
+    #[serde(default)]
+    pub clearcoat: f32,
+    #[serde(default = "default_clearcoat_roughness")]
+    pub clearcoat_roughness: f32,
+    #[serde(default)]
+    pub transmission: f32,
+    #[serde(default = "default_ior")]
+    pub ior: f32,
+    #[serde(default)]
+    pub dispersion: Option<Dispersion>,
+    #[serde(default)]
+    pub subsurface: f32,
+}
+
+// -----------------------------------------------------------------------------------------
+
+impl Material {
+    fn load_images(&mut self, directory: &Path, cache: &mut ImageCache) -> Result<(), String> {
+        self.diffuse.load_images(directory, cache)?;
+        self.roughness.load_images(directory, cache)?;
+        self.metalness.load_images(directory, cache)?;
+        self.specular.load_images(directory, cache)?;
+        self.emission.load_images(directory, cache)?;
+        if let Some(normal_map) = &mut self.normal_map {
+            normal_map.load_images(directory, cache)?;
+        }
+        if let Some(bump_map) = &mut self.bump_map {
+            bump_map.load_images(directory, cache)?;
+        }
+        if let Some(principled) = &mut self.principled {
+            principled.base_colour.load_images(directory, cache)?;
+            principled.metallic.load_images(directory, cache)?;
+            principled.roughness.load_images(directory, cache)?;
+            principled.specular.load_images(directory, cache)?;
+        }
+        Ok(())
+    }
+
+    pub fn albedo(&self, hit: &RayHitResult) -> Vec3 {
+        // Flat colour, used for paths which stop at the bounce limit
+        if let Some(subsurface) = &self.subsurface {
+            return subsurface.colour * (1.0 - self.absorbed);
+        }
+        match &self.principled {
+            Some(principled) => principled.base_colour.sample(hit) * (1.0 - self.absorbed),
+            None => self.diffuse.sample(hit) * (1.0 - self.absorbed),
+        }
+    }
+
+    pub fn shading_normal(&self, hit: &RayHitResult) -> Vec3 {
+        // Bump map perturbs the geometric normal, normal map is then applied in that frame
+        let mut shaded = *hit;
+        if let Some(bump_map) = &self.bump_map {
+            shaded.shading_normal = self.bump(bump_map, hit);
+        }
+        if let Some(normal_map) = &self.normal_map {
+            let (tangent, bitangent) = shaded.tangent_frame();
+            let n = (normal_map.sample(hit) * 2.0) - Vec3::ONE;
+            let normal = (tangent * n.x) + (bitangent * n.y) + (shaded.shading_normal * n.z);
+            if Vec3::length_squared(normal) > 0.0 {
+                shaded.shading_normal = Vec3::normalize(normal);
+            }
+        }
+        shaded.shading_normal
+    }
+
+    fn bump(&self, bump_map: &Texture, hit: &RayHitResult) -> Vec3 {
+        // Finite difference the height along u and v, stepping by the pixel footprint
+        const MIN_DELTA: f32 = 0.0005;
+        let du = (0.5 * (hit.duv_dx.x.abs() + hit.duv_dy.x.abs())).max(MIN_DELTA);
+        let dv = (0.5 * (hit.duv_dx.y.abs() + hit.duv_dy.y.abs())).max(MIN_DELTA);
+        let height_at = |offset: Vec3, uv: Vec2| {
+            let mut shifted = *hit;
+            shifted.position += offset;
+            shifted.local_position += offset;
+            shifted.uv = uv;
+            bump_map.sample(&shifted).x
+        };
+        let height = bump_map.sample(hit).x;
+        let height_u = height_at(hit.dpdu * du, Vec2::new(hit.uv.x + du, hit.uv.y));
+        let height_v = height_at(hit.dpdv * dv, Vec2::new(hit.uv.x, hit.uv.y + dv));
+
+        // Displace surface along normal by height, and take the normal of the displaced partials
+        let dpdu = hit.dpdu + (hit.normal * (((height_u - height) / du) * self.bump_strength));
+        let dpdv = hit.dpdv + (hit.normal * (((height_v - height) / dv) * self.bump_strength));
+        let normal = Vec3::cross(dpdu, dpdv);
+        if Vec3::length_squared(normal) == 0.0 {
+            return hit.normal;
+        }
+        let normal = Vec3::normalize(normal);
+        if Vec3::dot(normal, hit.normal) < 0.0 {
+            -normal
+        } else {
+            normal
+        }
+    }
 }
 
 // -----------------------------------------------------------------------------------------
 
+// Index into a material bank (primitives and hit results carry these rather than names)
+pub type MaterialId = u32;
+
 type MaterialTable = HashMap<String, Material>;
+type MaterialSource = serde_json::Map<String, Value>;
 
 // -----------------------------------------------------------------------------------------
 
 pub struct MaterialBank {
     _name: String,
-    materials: MaterialTable,
+    directory: PathBuf,
+    materials: Vec<Material>,
+    ids: HashMap<String, MaterialId>,
+    images: Vec<PathBuf>,
 }
 
 // -----------------------------------------------------------------------------------------
 
 impl MaterialBank {
+    // Slot zero always holds the default material (used for unknown names)
+    pub const DEFAULT: MaterialId = 0;
+
     // -------------------------------------------------------------------------------------
 
     pub fn load_from_file(file: &str) -> MaterialBank {
+        MaterialBank::try_load_from_file(file).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn try_load_from_file(file: &str) -> Result<MaterialBank, String> {
         // Load material bank file
         let data = fs::read_to_string(file)
-            .expect(&format!("ERROR: Could not load materials file: '{}'", file));
+            .map_err(|_| format!("ERROR: Could not load materials file: '{}'", file))?;
+
+        // Referenced images are relative to the materials file
+        let directory = Path::new(file).parent().unwrap_or_else(|| Path::new(""));
+        MaterialBank::try_load_from_str(file, &data, directory)
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn try_load_from_str(
+        file: &str,
+        data: &str,
+        directory: &Path,
+    ) -> Result<MaterialBank, String> {
+        // Materials file contents (for example sent to the render service)
+
+        // Validate each material as written (reports line and column of the first problem)
+        if let Err(error) = serde_json::from_str::<MaterialTable>(data) {
+            return Err(format!(
+                "ERROR: Invalid materials file '{}': {}",
+                file, error
+            ));
+        }
 
-        // Deserialise
-        let mut materials: MaterialTable = serde_json::from_str(&data).unwrap();
+        // Resolve parents, then deserialise the flattened descriptions (sorted for stable ids)
+        let sources: HashMap<String, MaterialSource> = serde_json::from_str(data).unwrap();
+        let mut names: Vec<&String> = sources.keys().collect();
+        names.sort_unstable();
+        let mut materials = vec![MaterialBank::get_default()];
+        let mut ids = HashMap::new();
+        for name in names {
+            let source = MaterialBank::inherit(file, name, &sources, &mut Vec::new())?;
+            let mut material: Material =
+                serde_json::from_value(Value::Object(source)).map_err(|error| {
+                    format!(
+                        "ERROR: Invalid material '{}' in '{}': {}",
+                        name, file, error
+                    )
+                })?;
 
-        // Use JSON key names as material names
-        for (key, value) in &mut materials {
-            value.name = key.clone();
+            // Use JSON key names as material names
+            material.name = name.clone();
+            ids.insert(name.clone(), materials.len() as MaterialId);
+            materials.push(material);
+        }
+
+        // Load referenced images
+        let mut image_cache = ImageCache::new();
+        for material in &mut materials {
+            material.load_images(directory, &mut image_cache)?;
         }
 
         // Return bank
-        MaterialBank {
+        Ok(MaterialBank {
             _name: String::from(file),
+            directory: directory.to_path_buf(),
             materials,
-        }
+            ids,
+            images: image_cache.keys().cloned().collect(),
+        })
     }
 
     // -------------------------------------------------------------------------------------
 
-    pub fn get(&self, name: &str) -> Material {
-        match self.materials.get(name) {
-            Some(material) => material.clone(),
-            None => {
-                println!("Failed to find material: {}", name);
-                MaterialBank::get_default()
+    fn inherit(
+        file: &str,
+        name: &str,
+        sources: &HashMap<String, MaterialSource>,
+        chain: &mut Vec<String>,
+    ) -> Result<MaterialSource, String> {
+        // Guard against materials which (indirectly) inherit from themselves
+        if chain.iter().any(|visited| visited == name) {
+            chain.push(String::from(name));
+            return Err(format!(
+                "ERROR: Material parents form a cycle in '{}': {}",
+                file,
+                chain.join(" -> ")
+            ));
+        }
+        chain.push(String::from(name));
+
+        // Start from the fully resolved parent (if any)
+        let source = &sources[name];
+        let mut resolved = match source.get("parent") {
+            Some(Value::String(parent)) => {
+                if !sources.contains_key(parent) {
+                    return Err(format!(
+                        "ERROR: Material '{}' in '{}' has unknown parent '{}'",
+                        name, file, parent
+                    ));
+                }
+                MaterialBank::inherit(file, parent, sources, chain)?
+            }
+            _ => MaterialSource::new(),
+        };
+
+        // Override inherited fields (principled parameters are overridden individually)
+        for (key, value) in source {
+            match (resolved.get_mut(key), value) {
+                (Some(Value::Object(inherited)), Value::Object(overrides))
+                    if key == "principled" =>
+                {
+                    for (parameter, value) in overrides {
+                        inherited.insert(parameter.clone(), value.clone());
+                    }
+                }
+                _ => {
+                    resolved.insert(key.clone(), value.clone());
+                }
             }
         }
+        Ok(resolved)
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn resolve(&self, names: &[String]) -> Vec<MaterialId> {
+        // Unknown materials fall back to the default, reported once here rather than per ray
+        let mut missing: Vec<&str> = names
+            .iter()
+            .map(|name| name.as_str())
+            .filter(|name| !self.ids.contains_key(*name))
+            .collect();
+        if !missing.is_empty() {
+            missing.sort_unstable();
+            missing.dedup();
+            println!(
+                "WARNING: Scene references unknown materials, using default: {}",
+                missing.join(", ")
+            );
+        }
+        names
+            .iter()
+            .map(|name| *self.ids.get(name).unwrap_or(&MaterialBank::DEFAULT))
+            .collect()
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn get(&self, id: MaterialId) -> &Material {
+        &self.materials[id as usize]
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn names(&self) -> Vec<&str> {
+        // Sorted (ids are in name order), without the default material
+        self.materials[1..]
+            .iter()
+            .map(|material| material.name.as_str())
+            .collect()
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn images(&self) -> &[PathBuf] {
+        &self.images
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn description(&self) -> String {
+        // Resolved materials as JSON (for telling material banks apart, image data excluded)
+        serde_json::to_string(&self.materials).unwrap()
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn from_description(
+        description: &str,
+        directory: &Path,
+        image_cache: &mut ImageCache,
+    ) -> Result<MaterialBank, String> {
+        // Materials written by description, images come from the cache if they're in it
+        let mut materials: Vec<Material> = serde_json::from_str(description)
+            .map_err(|error| format!("ERROR: Invalid materials description: {}", error))?;
+        let mut ids = HashMap::new();
+        for (id, material) in materials.iter().enumerate().skip(1) {
+            ids.insert(material.name.clone(), id as MaterialId);
+        }
+        for material in &mut materials {
+            material.load_images(directory, image_cache)?;
+        }
+        Ok(MaterialBank {
+            _name: String::from("description"),
+            directory: directory.to_path_buf(),
+            materials,
+            ids,
+            images: image_cache.keys().cloned().collect(),
+        })
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn directory(&self) -> &Path {
+        // Images are relative to the materials file
+        &self.directory
     }
 
     // -------------------------------------------------------------------------------------
@@ -68,12 +410,24 @@ impl MaterialBank {
     pub fn get_default() -> Material {
         Material {
             name: String::from("default"),
+            parent: None,
             absorbed: 0.3,
-            diffuse: Vec3 {
+            diffuse: Texture::Constant(Vec3 {
                 x: 1.0,
                 y: 0.0,
                 z: 0.0,
-            },
+            }),
+            roughness: Texture::default(),
+            metalness: Texture::default(),
+            specular: Texture::default(),
+            anisotropy: 0.0,
+            conductor: None,
+            emission: Texture::default(),
+            normal_map: None,
+            bump_map: None,
+            bump_strength: default_bump_strength(),
+            principled: None,
+            subsurface: None,
         }
     }
 
@@ -81,3 +435,29 @@ impl MaterialBank {
 }
 
 // -----------------------------------------------------------------------------------------
+// Serde defaults
+fn default_bump_strength() -> f32 {
+    1.0
+}
+
+fn default_base_colour() -> Texture {
+    Texture::Scalar(0.8)
+}
+
+fn default_half() -> Texture {
+    Texture::Scalar(0.5)
+}
+
+fn default_sheen_tint() -> f32 {
+    0.5
+}
+
+fn default_clearcoat_roughness() -> f32 {
+    0.1
+}
+
+fn default_ior() -> f32 {
+    1.5
+}
+
+// -----------------------------------------------------------------------------------------
@@ -0,0 +1,308 @@
+// -----------------------------------------------------------------------------------------
+// Participating media (fog, smoke) sampled by delta tracking
+// -----------------------------------------------------------------------------------------
+
+use super::geometry::Aabb;
+use super::intersect;
+use super::noise;
+use super::random::Pcg32;
+use super::ray::Ray;
+use super::texture;
+use super::texture::NoiseType;
+use super::vector::Vec3;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// -----------------------------------------------------------------------------------------
+// Medium Type (coefficients are per unit distance, scaled by density)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Medium {
+    #[serde(default)]
+    pub absorption: Vec3,
+    #[serde(default)]
+    pub scattering: Vec3,
+    #[serde(default)]
+    pub anisotropy: f32,
+    #[serde(default)]
+    pub density: Density,
+}
+
+// -----------------------------------------------------------------------------------------
+// Density Type (tagged by "type" in scene files, grids span the volume's bounds)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Density {
+    Constant {
+        #[serde(default = "default_one")]
+        value: f32,
+    },
+    Noise {
+        #[serde(default)]
+        noise: NoiseType,
+        #[serde(default = "default_one")]
+        scale: f32,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+        #[serde(default = "default_one")]
+        value: f32,
+    },
+    Grid {
+        resolution: [usize; 3],
+        values: Vec<f32>,
+    },
+}
+
+impl Default for Density {
+    fn default() -> Density {
+        Density::Constant { value: 1.0 }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// VolumeShape Type (boundary of a medium, tagged by "type" in scene files)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum VolumeShape {
+    Sphere { centre: Vec3, radius: f32 },
+    Box { min: Vec3, max: Vec3 },
+}
+
+// -----------------------------------------------------------------------------------------
+// Volume Type (a medium filling a shape, the boundary itself is invisible)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Volume {
+    pub shape: VolumeShape,
+    pub medium: Medium,
+}
+
+// -----------------------------------------------------------------------------------------
+// MediumSegment Type (part of a ray inside a medium)
+pub struct MediumSegment<'a> {
+    pub medium: &'a Medium,
+    pub bounds: Aabb,
+    pub near: f32,
+    pub far: f32,
+}
+
+// -----------------------------------------------------------------------------------------
+// MediumEvent Type (outcome of tracking a ray through media)
+pub enum MediumEvent {
+    // Reached the surface (or sky), weight accounts for chromatic media
+    Pass(Vec3),
+    // Scattered at position, albedo is used in place of lighting at the bounce limit
+    Scatter {
+        position: Vec3,
+        weight: Vec3,
+        anisotropy: f32,
+        albedo: Vec3,
+    },
+    Absorb,
+}
+
+// -----------------------------------------------------------------------------------------
+// Medium Members
+impl Medium {
+    pub fn majorant(&self) -> f32 {
+        let extinction = self.absorption + self.scattering;
+        extinction.x.max(extinction.y).max(extinction.z) * self.density.max()
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Density Members
+impl Density {
+    pub fn sample(&self, position: Vec3, bounds: &Aabb) -> f32 {
+        match self {
+            Density::Constant { value } => *value,
+            Density::Noise {
+                noise,
+                scale,
+                octaves,
+                value,
+            } => {
+                let n = noise::fbm(position * *scale, *octaves, |p| {
+                    texture::evaluate(*noise, p)
+                });
+                value * (n + 0.5).clamp(0.0, 1.0)
+            }
+            Density::Grid { resolution, values } => {
+                let local = (position - bounds.min) / (bounds.max - bounds.min);
+                sample_grid(*resolution, values, local)
+            }
+        }
+    }
+
+    pub fn max(&self) -> f32 {
+        match self {
+            Density::Constant { value } => *value,
+            Density::Noise { value, .. } => *value,
+            Density::Grid { values, .. } => values.iter().cloned().fold(0.0, f32::max),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// VolumeShape Members
+impl VolumeShape {
+    pub fn interval(&self, ray: &Ray) -> Option<(f32, f32)> {
+        match self {
+            VolumeShape::Sphere { centre, radius } => {
+                let m = ray.origin - *centre;
+                let b = Vec3::dot(m, ray.direction);
+                let c = Vec3::dot(m, m) - (radius * radius);
+                let discr = (b * b) - c;
+                if discr < 0.0 {
+                    return None;
+                }
+                let sqrt_discr = discr.sqrt();
+                Some((-b - sqrt_discr, -b + sqrt_discr))
+            }
+            VolumeShape::Box { min, max } => intersect::ray_aabb(ray, &Aabb::new(*min, *max)),
+        }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        match self {
+            VolumeShape::Sphere { centre, radius } => Aabb::around(*centre, Vec3::ONE * *radius),
+            VolumeShape::Box { min, max } => Aabb::new(*min, *max),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn track(ray: &Ray, extent: f32, segments: &[MediumSegment], rng: &mut Pcg32) -> MediumEvent {
+    // Majorant bounds the combined extinction of every medium along the ray
+    let majorant: f32 = segments
+        .iter()
+        .map(|segment| segment.medium.majorant())
+        .sum();
+    if majorant <= 0.0 {
+        return MediumEvent::Pass(Vec3::ONE);
+    }
+    let start = segments
+        .iter()
+        .fold(extent, |start, segment| start.min(segment.near));
+    let end = segments
+        .iter()
+        .fold(0.0, |end: f32, segment| end.max(segment.far));
+    let end = end.min(extent);
+
+    // Step between tentative collisions, choosing absorb, scatter or null at each
+    let mut weight = Vec3::ONE;
+    let mut t = start.max(0.0);
+    loop {
+        let step = -(1.0 - rng.gen::<f32>()).ln() / majorant;
+        if t + step >= end {
+            return MediumEvent::Pass(weight);
+        }
+
+        // Far along the ray a step can be too small to move t, which would never reach the end
+        if step > 0.0 && t + step <= t {
+            return MediumEvent::Pass(weight);
+        }
+        t += step;
+
+        // Sum the coefficients of every medium containing this point
+        let position = ray.get_point(t);
+        let (mut absorption, mut scattering, mut anisotropy) = (Vec3::BLACK, Vec3::BLACK, 0.0);
+        for segment in segments {
+            if t < segment.near || t >= segment.far {
+                continue;
+            }
+            let density = segment.medium.density.sample(position, &segment.bounds);
+            let medium_scattering = segment.medium.scattering * density;
+            absorption += segment.medium.absorption * density;
+            scattering += medium_scattering;
+            anisotropy += segment.medium.anisotropy * average(medium_scattering);
+        }
+        let null = (Vec3::ONE * majorant) - absorption - scattering;
+
+        // Event probabilities follow the average over channels, weights correct for colour
+        let absorb_probability = average(absorption) / majorant;
+        let scatter_probability = average(scattering) / majorant;
+        let u = rng.gen::<f32>();
+        if u < absorb_probability {
+            return MediumEvent::Absorb;
+        }
+        if u < absorb_probability + scatter_probability {
+            return MediumEvent::Scatter {
+                position,
+                weight: weight * scattering / (majorant * scatter_probability),
+                anisotropy: anisotropy / average(scattering),
+                albedo: scattering / (absorption + scattering),
+            };
+        }
+        let null_probability = 1.0 - absorb_probability - scatter_probability;
+        if null_probability <= 0.0 {
+            return MediumEvent::Absorb;
+        }
+        weight *= null / (majorant * null_probability);
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn sample_henyey_greenstein(direction: Vec3, g: f32, rng: &mut Pcg32) -> Vec3 {
+    // Cosine is measured from the direction of travel, positive g scatters forwards
+    let (u1, u2) = (rng.gen::<f32>(), rng.gen::<f32>());
+    let cos_theta = if g.abs() < 0.001 {
+        1.0 - (2.0 * u1)
+    } else {
+        let s = (1.0 - (g * g)) / (1.0 + g - (2.0 * g * u1));
+        (1.0 + (g * g) - (s * s)) / (2.0 * g)
+    };
+    let sin_theta = (1.0 - (cos_theta * cos_theta)).max(0.0).sqrt();
+    let phi = std::f32::consts::PI * 2.0 * u2;
+    let (tangent, bitangent) = Vec3::orthonormal_basis(direction);
+    (tangent * (sin_theta * phi.cos()))
+        + (bitangent * (sin_theta * phi.sin()))
+        + (direction * cos_theta)
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn sample_grid(resolution: [usize; 3], values: &[f32], local: Vec3) -> f32 {
+    // Trilinear, voxel centres at half integer coordinates (clamped at the edges)
+    let [rx, ry, rz] = resolution;
+    if values.len() < rx * ry * rz || rx * ry * rz == 0 {
+        return 0.0;
+    }
+    let voxel = |x: i64, y: i64, z: i64| {
+        let x = x.clamp(0, rx as i64 - 1) as usize;
+        let y = y.clamp(0, ry as i64 - 1) as usize;
+        let z = z.clamp(0, rz as i64 - 1) as usize;
+        values[x + (y * rx) + (z * rx * ry)]
+    };
+    let fx = (local.x * rx as f32) - 0.5;
+    let fy = (local.y * ry as f32) - 0.5;
+    let fz = (local.z * rz as f32) - 0.5;
+    let (x0, y0, z0) = (fx.floor(), fy.floor(), fz.floor());
+    let (tx, ty, tz) = (fx - x0, fy - y0, fz - z0);
+    let (x0, y0, z0) = (x0 as i64, y0 as i64, z0 as i64);
+    let lerp = |a: f32, b: f32, t: f32| a + ((b - a) * t);
+    let plane = |z: i64| {
+        let bottom = lerp(voxel(x0, y0, z), voxel(x0 + 1, y0, z), tx);
+        let top = lerp(voxel(x0, y0 + 1, z), voxel(x0 + 1, y0 + 1, z), tx);
+        lerp(bottom, top, ty)
+    };
+    lerp(plane(z0), plane(z0 + 1), tz)
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn average(v: Vec3) -> f32 {
+    (v.x + v.y + v.z) / 3.0
+}
+
+// -----------------------------------------------------------------------------------------
+// Serde defaults
+fn default_one() -> f32 {
+    1.0
+}
+
+fn default_octaves() -> u32 {
+    4
+}
+
+// -----------------------------------------------------------------------------------------
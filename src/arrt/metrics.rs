@@ -0,0 +1,212 @@
+// -----------------------------------------------------------------------------------------
+// Image comparison metrics (MSE, relMSE, PSNR, SSIM) and false colour error maps
+// -----------------------------------------------------------------------------------------
+
+use super::framebuffer::Framebuffer;
+use super::vector::Vec3;
+
+// -----------------------------------------------------------------------------------------
+
+// Added to the reference in relative MSE, so near black pixels don't dominate the mean
+const REL_MSE_EPSILON: f32 = 0.01;
+
+// SSIM uses an 11x11 gaussian window and the constants for values in [0, 1] (Wang 2004)
+const SSIM_RADIUS: i32 = 5;
+const SSIM_SIGMA: f32 = 1.5;
+const SSIM_C1: f32 = 0.01 * 0.01;
+const SSIM_C2: f32 = 0.03 * 0.03;
+
+// Error map range (log scale), errors outside are clamped to the first and last colours
+const ERROR_MAP_MIN: f32 = 1e-5;
+const ERROR_MAP_MAX: f32 = 1.0;
+const ERROR_MAP_COLOURS: [Vec3; 5] = [
+    Vec3 {
+        x: 0.0,
+        y: 0.0,
+        z: 0.5,
+    },
+    Vec3 {
+        x: 0.0,
+        y: 0.5,
+        z: 1.0,
+    },
+    Vec3 {
+        x: 0.0,
+        y: 1.0,
+        z: 0.0,
+    },
+    Vec3 {
+        x: 1.0,
+        y: 1.0,
+        z: 0.0,
+    },
+    Vec3 {
+        x: 1.0,
+        y: 0.0,
+        z: 0.0,
+    },
+];
+
+// -----------------------------------------------------------------------------------------
+// Metrics Type
+#[derive(Debug, Copy, Clone)]
+pub struct Metrics {
+    pub mse: f32,
+    pub rel_mse: f32,
+    pub psnr: f32,
+    pub ssim: f32,
+}
+
+// -----------------------------------------------------------------------------------------
+// Metrics Constructor
+impl Metrics {
+    pub fn compare(reference: &Framebuffer, image: &Framebuffer) -> Result<Metrics, String> {
+        check_dimensions(reference, image)?;
+
+        // Squared errors, averaged over pixels and channels
+        let (mut mse, mut rel_mse) = (0.0, 0.0);
+        for (a, b) in reference.pixels.iter().zip(image.pixels.iter()) {
+            mse += sum(squared_error(*a, *b));
+            rel_mse += sum(relative_squared_error(*a, *b));
+        }
+        let count = (reference.pixels.len() * 3) as f32;
+        mse /= count;
+        rel_mse /= count;
+
+        // Peak value is 1, identical images have infinite PSNR
+        let psnr = -10.0 * mse.log10();
+        let ssim = ssim(reference, image);
+        Ok(Metrics {
+            mse,
+            rel_mse,
+            psnr,
+            ssim,
+        })
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn error_map(reference: &Framebuffer, image: &Framebuffer) -> Result<Framebuffer, String> {
+    // Per pixel relative squared error, coloured blue (low) to red (high)
+    check_dimensions(reference, image)?;
+    let pixels = reference
+        .pixels
+        .iter()
+        .zip(image.pixels.iter())
+        .map(|(a, b)| false_colour(sum(relative_squared_error(*a, *b)) / 3.0))
+        .collect();
+    Ok(Framebuffer::new(reference.width, reference.height, pixels))
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn check_dimensions(reference: &Framebuffer, image: &Framebuffer) -> Result<(), String> {
+    if (reference.width, reference.height) != (image.width, image.height) {
+        return Err(format!(
+            "ERROR: Image sizes differ: {}x{} (reference) and {}x{}",
+            reference.width, reference.height, image.width, image.height
+        ));
+    }
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn squared_error(reference: Vec3, value: Vec3) -> Vec3 {
+    let delta = value - reference;
+    delta * delta
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn relative_squared_error(reference: Vec3, value: Vec3) -> Vec3 {
+    let denominator = reference * reference + Vec3::ONE * REL_MSE_EPSILON;
+    squared_error(reference, value) / denominator
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn sum(value: Vec3) -> f32 {
+    value.x + value.y + value.z
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn luminance(colour: Vec3) -> f32 {
+    // Rec. 709 weights
+    Vec3::dot(colour, Vec3::new(0.2126, 0.7152, 0.0722))
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn ssim(reference: &Framebuffer, image: &Framebuffer) -> f32 {
+    // Mean structural similarity of luminance, window statistics come from gaussian blurs
+    let (width, height) = (reference.width as usize, reference.height as usize);
+    let x: Vec<f32> = reference.pixels.iter().map(|p| luminance(*p)).collect();
+    let y: Vec<f32> = image.pixels.iter().map(|p| luminance(*p)).collect();
+    let product =
+        |a: &[f32], b: &[f32]| -> Vec<f32> { a.iter().zip(b.iter()).map(|(a, b)| a * b).collect() };
+    let mean_x = blur(&x, width, height);
+    let mean_y = blur(&y, width, height);
+    let mean_xx = blur(&product(&x, &x), width, height);
+    let mean_yy = blur(&product(&y, &y), width, height);
+    let mean_xy = blur(&product(&x, &y), width, height);
+
+    let mut total = 0.0;
+    for i in 0..x.len() {
+        let (mx, my) = (mean_x[i], mean_y[i]);
+        let variance_x = mean_xx[i] - mx * mx;
+        let variance_y = mean_yy[i] - my * my;
+        let covariance = mean_xy[i] - mx * my;
+        let numerator = (2.0 * mx * my + SSIM_C1) * (2.0 * covariance + SSIM_C2);
+        let denominator = (mx * mx + my * my + SSIM_C1) * (variance_x + variance_y + SSIM_C2);
+        total += numerator / denominator;
+    }
+    total / (x.len() as f32)
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn blur(values: &[f32], width: usize, height: usize) -> Vec<f32> {
+    // Separable gaussian, weights are renormalised where the window leaves the image
+    let weights: Vec<f32> = (-SSIM_RADIUS..=SSIM_RADIUS)
+        .map(|offset| (-((offset * offset) as f32) / (2.0 * SSIM_SIGMA * SSIM_SIGMA)).exp())
+        .collect();
+    let pass = |values: &[f32], stride: usize, length: usize| -> Vec<f32> {
+        let mut output = vec![0.0; values.len()];
+        for (i, output) in output.iter_mut().enumerate() {
+            let position = ((i / stride) % length) as i32;
+            let (mut sum, mut weight_sum) = (0.0, 0.0);
+            for (weight, offset) in weights.iter().zip(-SSIM_RADIUS..=SSIM_RADIUS) {
+                let sample = position + offset;
+                if sample >= 0 && sample < length as i32 {
+                    let index = (i as i32 + offset * stride as i32) as usize;
+                    sum += weight * values[index];
+                    weight_sum += weight;
+                }
+            }
+            *output = sum / weight_sum;
+        }
+        output
+    };
+    let horizontal = pass(values, 1, width);
+    pass(&horizontal, width, height)
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn false_colour(error: f32) -> Vec3 {
+    // Position on a log scale between the map limits
+    let range = (ERROR_MAP_MAX / ERROR_MAP_MIN).log10();
+    let t = ((error.max(ERROR_MAP_MIN) / ERROR_MAP_MIN).log10() / range).min(1.0);
+    let scaled = t * (ERROR_MAP_COLOURS.len() - 1) as f32;
+    let index = (scaled as usize).min(ERROR_MAP_COLOURS.len() - 2);
+    Vec3::lerp(
+        ERROR_MAP_COLOURS[index],
+        ERROR_MAP_COLOURS[index + 1],
+        scaled - index as f32,
+    )
+}
+
+// -----------------------------------------------------------------------------------------
@@ -0,0 +1,176 @@
+// -----------------------------------------------------------------------------------------
+// Image pyramids for filtered texture lookups
+// -----------------------------------------------------------------------------------------
+
+use super::vector::Vec2;
+use super::vector::Vec3;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// -----------------------------------------------------------------------------------------
+// WrapMode Type (addressing for uvs outside [0, 1])
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WrapMode {
+    #[default]
+    Repeat,
+    Clamp,
+}
+
+// -----------------------------------------------------------------------------------------
+// FilterMode Type
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterMode {
+    Nearest,
+    Bilinear,
+    #[default]
+    Trilinear,
+}
+
+// -----------------------------------------------------------------------------------------
+// MipLevel Type
+#[derive(Debug)]
+struct MipLevel {
+    width: usize,
+    height: usize,
+    texels: Vec<Vec3>,
+}
+
+// -----------------------------------------------------------------------------------------
+// MipMap Type (level 0 is full resolution, each level halves until 1x1)
+#[derive(Debug)]
+pub struct MipMap {
+    levels: Vec<MipLevel>,
+}
+
+// -----------------------------------------------------------------------------------------
+// MipMap Constructor
+impl MipMap {
+    pub fn load_from_file(file: &Path) -> Result<MipMap, String> {
+        // Decode to float, colour values are used as stored (output is not gamma corrected)
+        let image = image::open(file).map_err(|error| error.to_string())?;
+        Ok(MipMap::from_image(image))
+    }
+
+    pub fn load_from_memory(bytes: &[u8]) -> Result<MipMap, String> {
+        // Same as load_from_file, for image files received rather than read
+        let image = image::load_from_memory(bytes).map_err(|error| error.to_string())?;
+        Ok(MipMap::from_image(image))
+    }
+
+    fn from_image(image: image::DynamicImage) -> MipMap {
+        let image = image.to_rgb32f();
+        let texels = image
+            .pixels()
+            .map(|pixel| Vec3::new(pixel[0], pixel[1], pixel[2]))
+            .collect();
+        MipMap::new(image.width() as usize, image.height() as usize, texels)
+    }
+
+    pub fn new(width: usize, height: usize, texels: Vec<Vec3>) -> MipMap {
+        // Build chain by box filtering each level down
+        let mut levels = vec![MipLevel {
+            width,
+            height,
+            texels,
+        }];
+        loop {
+            let previous = levels.last().unwrap();
+            if previous.width == 1 && previous.height == 1 {
+                break;
+            }
+            let next = previous.downsample();
+            levels.push(next);
+        }
+        MipMap { levels }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// MipMap Members
+impl MipMap {
+    pub fn sample(&self, uv: Vec2, footprint: Vec2, wrap: WrapMode, filter: FilterMode) -> Vec3 {
+        // Images are stored top row first, uvs start bottom left
+        let (x, y) = (uv.x, 1.0 - uv.y);
+        let base = &self.levels[0];
+        match filter {
+            FilterMode::Nearest => base.nearest(x, y, wrap),
+            FilterMode::Bilinear => base.bilinear(x, y, wrap),
+            FilterMode::Trilinear => {
+                // Pick levels by the number of texels the pixel footprint covers
+                let texels =
+                    (footprint.x * base.width as f32).max(footprint.y * base.height as f32);
+                let max_level = (self.levels.len() - 1) as f32;
+                let lod = texels.max(1.0).log2().min(max_level);
+                let lower = lod.floor() as usize;
+                let upper = lod.ceil() as usize;
+                let colour = self.levels[lower].bilinear(x, y, wrap);
+                if upper == lower {
+                    return colour;
+                }
+                let t = lod - lower as f32;
+                Vec3::lerp(colour, self.levels[upper].bilinear(x, y, wrap), t)
+            }
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// MipLevel Members
+impl MipLevel {
+    fn texel(&self, x: i64, y: i64, wrap: WrapMode) -> Vec3 {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let (x, y) = match wrap {
+            WrapMode::Repeat => (x.rem_euclid(width), y.rem_euclid(height)),
+            WrapMode::Clamp => (x.clamp(0, width - 1), y.clamp(0, height - 1)),
+        };
+        self.texels[((y * width) + x) as usize]
+    }
+
+    fn nearest(&self, x: f32, y: f32, wrap: WrapMode) -> Vec3 {
+        let tx = (x * self.width as f32).floor() as i64;
+        let ty = (y * self.height as f32).floor() as i64;
+        self.texel(tx, ty, wrap)
+    }
+
+    fn bilinear(&self, x: f32, y: f32, wrap: WrapMode) -> Vec3 {
+        // Texel centres lie at half integer coordinates
+        let fx = (x * self.width as f32) - 0.5;
+        let fy = (y * self.height as f32) - 0.5;
+        let (x0, y0) = (fx.floor(), fy.floor());
+        let (tx, ty) = (fx - x0, fy - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = Vec3::lerp(self.texel(x0, y0, wrap), self.texel(x0 + 1, y0, wrap), tx);
+        let bottom = Vec3::lerp(
+            self.texel(x0, y0 + 1, wrap),
+            self.texel(x0 + 1, y0 + 1, wrap),
+            tx,
+        );
+        Vec3::lerp(top, bottom, ty)
+    }
+
+    fn downsample(&self) -> MipLevel {
+        // Average 2x2 blocks (odd edges repeat the last row/column)
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut texels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let (x0, y0) = ((x * 2) as i64, (y * 2) as i64);
+                let sum = self.texel(x0, y0, WrapMode::Clamp)
+                    + self.texel(x0 + 1, y0, WrapMode::Clamp)
+                    + self.texel(x0, y0 + 1, WrapMode::Clamp)
+                    + self.texel(x0 + 1, y0 + 1, WrapMode::Clamp);
+                texels.push(sum * 0.25);
+            }
+        }
+        MipLevel {
+            width,
+            height,
+            texels,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
@@ -1 +1,14 @@
 pub type StringLiteral = &'static str;
+
+// -----------------------------------------------------------------------------------------
+
+pub fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    // Message of a caught panic (panics with a format string carry a String)
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => String::from(*message),
+            Err(_) => String::from("ERROR: Render failed"),
+        },
+    }
+}
@@ -1,11 +1,29 @@
+pub mod bsdf;
+pub mod bvh;
 pub mod camera;
-pub mod command_line;
+pub mod checkpoint;
+pub mod csg;
+pub mod distributed;
+pub mod framebuffer;
 pub mod geometry;
 pub mod intersect;
 pub mod job;
 pub mod material;
 pub mod matrix;
+pub mod medium;
+pub mod metrics;
 pub mod misc;
+pub mod mipmap;
+pub mod noise;
+pub mod polynomial;
 pub mod quality;
+pub mod random;
 pub mod ray;
+pub mod renderer;
+pub mod scene;
+pub mod sdf;
+pub mod service;
+pub mod spectrum;
+pub mod subsurface;
+pub mod texture;
 pub mod vector;
@@ -0,0 +1,220 @@
+// -----------------------------------------------------------------------------------------
+// Procedural noise functions (Perlin, simplex, Worley and their fractal sums)
+// -----------------------------------------------------------------------------------------
+
+use super::vector::Vec3;
+
+// -----------------------------------------------------------------------------------------
+// Ken Perlin's reference permutation
+const PERMUTATION: [u8; 256] = [
+    151, 160, 137, 91, 90, 15, 131, 13, 201, 95, 96, 53, 194, 233, 7, 225, 140, 36, 103, 30, 69,
+    142, 8, 99, 37, 240, 21, 10, 23, 190, 6, 148, 247, 120, 234, 75, 0, 26, 197, 62, 94, 252, 219,
+    203, 117, 35, 11, 32, 57, 177, 33, 88, 237, 149, 56, 87, 174, 20, 125, 136, 171, 168, 68, 175,
+    74, 165, 71, 134, 139, 48, 27, 166, 77, 146, 158, 231, 83, 111, 229, 122, 60, 211, 133, 230,
+    220, 105, 92, 41, 55, 46, 245, 40, 244, 102, 143, 54, 65, 25, 63, 161, 1, 216, 80, 73, 209, 76,
+    132, 187, 208, 89, 18, 169, 200, 196, 135, 130, 116, 188, 159, 86, 164, 100, 109, 198, 173,
+    186, 3, 64, 52, 217, 226, 250, 124, 123, 5, 202, 38, 147, 118, 126, 255, 82, 85, 212, 207, 206,
+    59, 227, 47, 16, 58, 17, 182, 189, 28, 42, 223, 183, 170, 213, 119, 248, 152, 2, 44, 154, 163,
+    70, 221, 153, 101, 155, 167, 43, 172, 9, 129, 22, 39, 253, 19, 98, 108, 110, 79, 113, 224, 232,
+    178, 185, 112, 104, 218, 246, 97, 228, 251, 34, 242, 193, 238, 210, 144, 12, 191, 179, 162,
+    241, 81, 51, 145, 235, 249, 14, 239, 107, 49, 192, 214, 31, 181, 199, 106, 157, 184, 84, 204,
+    176, 115, 121, 50, 45, 127, 4, 150, 254, 138, 236, 205, 93, 222, 114, 67, 29, 24, 72, 243, 141,
+    128, 195, 78, 66, 215, 61, 156, 180,
+];
+
+// -----------------------------------------------------------------------------------------
+// Simplex gradients (cube edge midpoints)
+const GRADIENTS: [[f32; 3]; 12] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+];
+
+// -----------------------------------------------------------------------------------------
+
+fn perm(i: i32) -> i32 {
+    PERMUTATION[(i & 255) as usize] as i32
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    a + (t * (b - a))
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn perlin(p: Vec3) -> f32 {
+    // Improved noise (Perlin 2002), result in roughly [-1, 1]
+    fn fade(t: f32) -> f32 {
+        t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+    }
+    fn grad(hash: i32, x: f32, y: f32, z: f32) -> f32 {
+        let h = hash & 15;
+        let u = if h < 8 { x } else { y };
+        let v = if h < 4 {
+            y
+        } else if h == 12 || h == 14 {
+            x
+        } else {
+            z
+        };
+        (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+    }
+
+    // Find unit cube containing point, and relative position within it
+    let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+    let (xi, yi, zi) = (fx as i32, fy as i32, fz as i32);
+    let (x, y, z) = (p.x - fx, p.y - fy, p.z - fz);
+    let (u, v, w) = (fade(x), fade(y), fade(z));
+
+    // Hash cube corners
+    let a = perm(xi) + yi;
+    let aa = perm(a) + zi;
+    let ab = perm(a + 1) + zi;
+    let b = perm(xi + 1) + yi;
+    let ba = perm(b) + zi;
+    let bb = perm(b + 1) + zi;
+
+    // Blend corner contributions
+    lerp(
+        w,
+        lerp(
+            v,
+            lerp(u, grad(perm(aa), x, y, z), grad(perm(ba), x - 1.0, y, z)),
+            lerp(u, grad(perm(ab), x, y - 1.0, z), grad(perm(bb), x - 1.0, y - 1.0, z)),
+        ),
+        lerp(
+            v,
+            lerp(
+                u,
+                grad(perm(aa + 1), x, y, z - 1.0),
+                grad(perm(ba + 1), x - 1.0, y, z - 1.0),
+            ),
+            lerp(
+                u,
+                grad(perm(ab + 1), x, y - 1.0, z - 1.0),
+                grad(perm(bb + 1), x - 1.0, y - 1.0, z - 1.0),
+            ),
+        ),
+    )
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn simplex(p: Vec3) -> f32 {
+    // 3D simplex noise (Gustavson 2005), result in roughly [-1, 1]
+    const F3: f32 = 1.0 / 3.0;
+    const G3: f32 = 1.0 / 6.0;
+
+    // Skew input space to find simplex cell
+    let s = (p.x + p.y + p.z) * F3;
+    let (i, j, k) = ((p.x + s).floor(), (p.y + s).floor(), (p.z + s).floor());
+    let t = (i + j + k) * G3;
+    let x0 = Vec3::new(p.x - (i - t), p.y - (j - t), p.z - (k - t));
+
+    // Determine which of the six simplices we are in
+    let (i1, i2) = if x0.x >= x0.y {
+        if x0.y >= x0.z {
+            ([1, 0, 0], [1, 1, 0])
+        } else if x0.x >= x0.z {
+            ([1, 0, 0], [1, 0, 1])
+        } else {
+            ([0, 0, 1], [1, 0, 1])
+        }
+    } else if x0.y < x0.z {
+        ([0, 0, 1], [0, 1, 1])
+    } else if x0.x < x0.z {
+        ([0, 1, 0], [0, 1, 1])
+    } else {
+        ([0, 1, 0], [1, 1, 0])
+    };
+    let offset = |c: [i32; 3], scale: f32| {
+        x0 - Vec3::new(c[0] as f32, c[1] as f32, c[2] as f32) + (Vec3::ONE * (G3 * scale))
+    };
+    let corners = [x0, offset(i1, 1.0), offset(i2, 2.0), offset([1, 1, 1], 3.0)];
+    let steps = [[0, 0, 0], i1, i2, [1, 1, 1]];
+
+    // Sum corner contributions
+    let (ii, jj, kk) = (i as i32, j as i32, k as i32);
+    let mut total = 0.0;
+    for (corner, step) in corners.iter().zip(steps.iter()) {
+        let falloff = 0.6 - Vec3::length_squared(*corner);
+        if falloff > 0.0 {
+            let hash = perm(ii + step[0] + perm(jj + step[1] + perm(kk + step[2])));
+            let g = GRADIENTS[(hash % 12) as usize];
+            let falloff = falloff * falloff;
+            total += falloff * falloff * Vec3::dot(Vec3::new(g[0], g[1], g[2]), *corner);
+        }
+    }
+    32.0 * total
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn worley(p: Vec3) -> (f32, f32) {
+    // Cellular noise, returns distances to the nearest and second nearest feature points
+    let cell = Vec3::new(p.x.floor(), p.y.floor(), p.z.floor());
+    let (mut f1, mut f2) = (f32::MAX, f32::MAX);
+    for dz in -1..=1 {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let neighbour = cell + Vec3::new(dx as f32, dy as f32, dz as f32);
+                let (x, y, z) = (neighbour.x as i32, neighbour.y as i32, neighbour.z as i32);
+                let feature = neighbour
+                    + Vec3::new(hash_unit(x, y, z, 0), hash_unit(x, y, z, 1), hash_unit(x, y, z, 2));
+                let distance = Vec3::length(p - feature);
+                if distance < f1 {
+                    f2 = f1;
+                    f1 = distance;
+                } else if distance < f2 {
+                    f2 = distance;
+                }
+            }
+        }
+    }
+    (f1, f2)
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn fbm<F: Fn(Vec3) -> f32>(p: Vec3, octaves: u32, noise: F) -> f32 {
+    // Fractional Brownian motion: octaves at doubling frequency and halving amplitude
+    let (mut total, mut amplitude, mut frequency) = (0.0, 0.5, 1.0);
+    for _ in 0..octaves {
+        total += amplitude * noise(p * frequency);
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    total
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn turbulence<F: Fn(Vec3) -> f32>(p: Vec3, octaves: u32, noise: F) -> f32 {
+    fbm(p, octaves, |q| noise(q).abs())
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn hash_unit(x: i32, y: i32, z: i32, seed: u32) -> f32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xd816_3841)
+        ^ (z as u32).wrapping_mul(0xcb1a_b31f)
+        ^ seed.wrapping_mul(0x9e37_79b9);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0x5bd1_e995);
+    h ^= h >> 15;
+    (h >> 8) as f32 / 16_777_216.0
+}
+
+// -----------------------------------------------------------------------------------------
@@ -0,0 +1,185 @@
+// -----------------------------------------------------------------------------------------
+// Closed-form polynomial roots, ported from Jochen Schwarze's "Cubic and Quartic Roots"
+// (Graphics Gems I). Coefficients are ordered from the constant term upwards.
+// -----------------------------------------------------------------------------------------
+
+const EQN_EPSILON: f64 = 1e-9;
+
+// -----------------------------------------------------------------------------------------
+// Roots Type
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Roots {
+    values: [f64; 4],
+    count: usize,
+}
+
+// -----------------------------------------------------------------------------------------
+// Roots Members
+impl Roots {
+    fn push(&mut self, value: f64) {
+        self.values[self.count] = value;
+        self.count += 1;
+    }
+
+    fn offset(&mut self, amount: f64) {
+        for value in &mut self.values[..self.count] {
+            *value += amount;
+        }
+    }
+
+    pub fn as_slice(&self) -> &[f64] {
+        &self.values[..self.count]
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn is_zero(x: f64) -> bool {
+    x > -EQN_EPSILON && x < EQN_EPSILON
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn solve_quadratic(c: [f64; 3]) -> Roots {
+    let mut roots = Roots::default();
+
+    // Degenerate to linear?
+    if is_zero(c[2]) {
+        if !is_zero(c[1]) {
+            roots.push(-c[0] / c[1]);
+        }
+        return roots;
+    }
+
+    // Normal form: x^2 + 2px + q = 0
+    let p = c[1] / (2.0 * c[2]);
+    let q = c[0] / c[2];
+    let d = (p * p) - q;
+    if is_zero(d) {
+        roots.push(-p);
+    } else if d > 0.0 {
+        let sqrt_d = d.sqrt();
+        roots.push(sqrt_d - p);
+        roots.push(-sqrt_d - p);
+    }
+    roots
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn solve_cubic(c: [f64; 4]) -> Roots {
+    let mut roots = Roots::default();
+
+    // Normal form: x^3 + Ax^2 + Bx + C = 0
+    let a = c[2] / c[3];
+    let b = c[1] / c[3];
+    let cc = c[0] / c[3];
+
+    // Substitute x = y - A/3 to eliminate the quadric term: y^3 + 3py + 2q = 0
+    let sq_a = a * a;
+    let p = (1.0 / 3.0) * ((-1.0 / 3.0) * sq_a + b);
+    let q = 0.5 * ((2.0 / 27.0) * a * sq_a - (1.0 / 3.0) * a * b + cc);
+
+    // Use Cardano's formula
+    let cb_p = p * p * p;
+    let d = (q * q) + cb_p;
+    if is_zero(d) {
+        if is_zero(q) {
+            roots.push(0.0);
+        } else {
+            let u = (-q).cbrt();
+            roots.push(2.0 * u);
+            roots.push(-u);
+        }
+    } else if d < 0.0 {
+        // Casus irreducibilis: three real solutions
+        let phi = (1.0 / 3.0) * (-q / (-cb_p).sqrt()).acos();
+        let t = 2.0 * (-p).sqrt();
+        roots.push(t * phi.cos());
+        roots.push(-t * (phi + std::f64::consts::PI / 3.0).cos());
+        roots.push(-t * (phi - std::f64::consts::PI / 3.0).cos());
+    } else {
+        let sqrt_d = d.sqrt();
+        let u = (sqrt_d - q).cbrt();
+        let v = -(sqrt_d + q).cbrt();
+        roots.push(u + v);
+    }
+
+    // Resubstitute
+    roots.offset(-a / 3.0);
+    roots
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn solve_quartic(c: [f64; 5]) -> Roots {
+    let mut roots = Roots::default();
+
+    // Normal form: x^4 + Ax^3 + Bx^2 + Cx + D = 0
+    let a = c[3] / c[4];
+    let b = c[2] / c[4];
+    let cc = c[1] / c[4];
+    let d = c[0] / c[4];
+
+    // Substitute x = y - A/4 to eliminate the cubic term: y^4 + py^2 + qy + r = 0
+    let sq_a = a * a;
+    let p = (-3.0 / 8.0) * sq_a + b;
+    let q = (1.0 / 8.0) * sq_a * a - 0.5 * a * b + cc;
+    let r = (-3.0 / 256.0) * sq_a * sq_a + (1.0 / 16.0) * sq_a * b - 0.25 * a * cc + d;
+
+    if is_zero(r) {
+        // No absolute term: y(y^3 + py + q) = 0
+        for &root in solve_cubic([q, p, 0.0, 1.0]).as_slice() {
+            roots.push(root);
+        }
+        roots.push(0.0);
+    } else {
+        // Solve the resolvent cubic and take the one real solution
+        let resolvent = solve_cubic([0.5 * r * p - (1.0 / 8.0) * q * q, -r, -0.5 * p, 1.0]);
+        let z = resolvent.values[0];
+
+        // ...to build two quadric equations
+        let mut u = (z * z) - r;
+        let mut v = (2.0 * z) - p;
+        if is_zero(u) {
+            u = 0.0;
+        } else if u > 0.0 {
+            u = u.sqrt();
+        } else {
+            return roots;
+        }
+        if is_zero(v) {
+            v = 0.0;
+        } else if v > 0.0 {
+            v = v.sqrt();
+        } else {
+            return roots;
+        }
+
+        let sign = if q < 0.0 { -1.0 } else { 1.0 };
+        for &root in solve_quadratic([z - u, sign * v, 1.0]).as_slice() {
+            roots.push(root);
+        }
+        for &root in solve_quadratic([z + u, -sign * v, 1.0]).as_slice() {
+            roots.push(root);
+        }
+    }
+
+    // Resubstitute
+    roots.offset(-a / 4.0);
+
+    // Polish with a couple of Newton iterations on the original polynomial
+    for root in &mut roots.values[..roots.count] {
+        for _ in 0..2 {
+            let x = *root;
+            let f = (((c[4] * x + c[3]) * x + c[2]) * x + c[1]) * x + c[0];
+            let df = ((4.0 * c[4] * x + 3.0 * c[3]) * x + 2.0 * c[2]) * x + c[1];
+            if df.abs() > EQN_EPSILON {
+                *root = x - (f / df);
+            }
+        }
+    }
+    roots
+}
+
+// -----------------------------------------------------------------------------------------
@@ -1,12 +1,14 @@
 // -----------------------------------------------------------------------------------------
 
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
 use std::collections::HashMap;
 use std::fs;
 
 // -----------------------------------------------------------------------------------------
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct QualityPreset {
     #[serde(default)]
     pub name: String,
@@ -14,6 +16,45 @@ pub struct QualityPreset {
     pub image_height: u32,
     pub samples_per_pixel: usize,
     pub max_bounces: u32,
+
+    // Stop after this many seconds of rendering ("90", "90s", "5m", "1h30m" or a number)
+    #[serde(
+        default,
+        rename = "time_limit",
+        deserialize_with = "deserialize_time_limit",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub time_limit_s: Option<f64>,
+
+    // Stop sampling pixels once their relative standard error drops below this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub noise_threshold: Option<f32>,
+
+    // Spend samples in rounds where the estimated error is highest (samples_per_pixel is then
+    // the average per pixel)
+    #[serde(default)]
+    pub adaptive: bool,
+}
+
+// -----------------------------------------------------------------------------------------
+
+impl QualityPreset {
+    pub fn with_overrides(&self, overrides: &Map<String, Value>) -> Result<QualityPreset, String> {
+        // Fields written as in a presets file (for example "max_bounces": 4 or "time_limit": "5m")
+        let mut fields = match serde_json::to_value(self).unwrap() {
+            Value::Object(fields) => fields,
+            _ => unreachable!(),
+        };
+        for (field, value) in overrides {
+            fields.insert(field.clone(), value.clone());
+        }
+        serde_json::from_value(Value::Object(fields)).map_err(|error| {
+            format!(
+                "ERROR: Invalid override of quality preset '{}': {}",
+                self.name, error
+            )
+        })
+    }
 }
 
 // -----------------------------------------------------------------------------------------
@@ -33,37 +74,51 @@ impl QualityPresetBank {
     // -------------------------------------------------------------------------------------
 
     pub fn load_from_file(file: &str) -> QualityPresetBank {
+        QualityPresetBank::try_load_from_file(file).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn try_load_from_file(file: &str) -> Result<QualityPresetBank, String> {
         // Load material bank file
-        let data = fs::read_to_string(file).expect(&format!(
-            "ERROR: Could not load quality presets file: '{}'",
-            file
-        ));
+        let data = fs::read_to_string(file)
+            .map_err(|_| format!("ERROR: Could not load quality presets file: '{}'", file))?;
 
         // Deserialise
-        let mut presets: QualityPresetTable = serde_json::from_str(&data).unwrap();
+        let mut presets: QualityPresetTable = serde_json::from_str(&data).map_err(|error| {
+            format!("ERROR: Invalid quality presets file '{}': {}", file, error)
+        })?;
 
-        // Use JSON key names as preset names
+        // Use JSON key names as preset names, the built-in default is kept unless overridden
         for (key, value) in &mut presets {
             value.name = key.clone();
         }
+        presets
+            .entry(String::from("default"))
+            .or_insert_with(QualityPresetBank::get_default);
 
         // Return bank
-        QualityPresetBank {
+        Ok(QualityPresetBank {
             _name: String::from(file),
             presets,
-        }
+        })
     }
 
     // -----------------------------------------------------------------------------------------
 
-    pub fn get(&self, name: &str) -> QualityPreset {
-        match self.presets.get(name) {
-            Some(preset) => preset.clone(),
-            None => {
-                println!("Failed to find preset: {}", name);
-                QualityPresetBank::get_default()
-            }
-        }
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.presets.keys().map(|name| name.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn try_get(&self, name: &str) -> Result<QualityPreset, String> {
+        self.presets
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("ERROR: Unknown quality preset: '{}'", name))
     }
 
     // -------------------------------------------------------------------------------------
@@ -75,6 +130,9 @@ impl QualityPresetBank {
             image_height: 480,
             samples_per_pixel: 8,
             max_bounces: 8,
+            time_limit_s: None,
+            noise_threshold: None,
+            adaptive: false,
         }
     }
 
@@ -82,3 +140,50 @@ impl QualityPresetBank {
 }
 
 // -----------------------------------------------------------------------------------------
+
+pub fn parse_duration(text: &str) -> Result<f64, String> {
+    // Numbers with an optional unit (s, m or h), summed so "1h30m" works
+    let error = || format!("ERROR: Invalid duration: '{}'", text);
+    let (mut total_s, mut number) = (0.0, String::new());
+    for character in text.trim().chars() {
+        let unit_s = match character {
+            's' => 1.0,
+            'm' => 60.0,
+            'h' => 3600.0,
+            _ => {
+                number.push(character);
+                continue;
+            }
+        };
+        total_s += number.parse::<f64>().map_err(|_| error())? * unit_s;
+        number.clear();
+    }
+    if !number.is_empty() {
+        total_s += number.parse::<f64>().map_err(|_| error())?;
+    }
+    if text.trim().is_empty() || total_s < 0.0 {
+        return Err(error());
+    }
+    Ok(total_s)
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn deserialize_time_limit<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum TimeLimit {
+        Seconds(f64),
+        Text(String),
+    }
+    match Option::<TimeLimit>::deserialize(deserializer)? {
+        Some(TimeLimit::Seconds(seconds)) => Ok(Some(seconds)),
+        Some(TimeLimit::Text(text)) => parse_duration(&text).map(Some).map_err(de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+// -----------------------------------------------------------------------------------------
@@ -0,0 +1,98 @@
+// -----------------------------------------------------------------------------------------
+// Counter based random streams (PCG), keyed so renders don't depend on thread scheduling
+// -----------------------------------------------------------------------------------------
+
+use rand::{Error, RngCore};
+
+// -----------------------------------------------------------------------------------------
+
+const MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+
+// -----------------------------------------------------------------------------------------
+// Pcg32 Type (PCG XSH RR 64/32, O'Neill 2014)
+#[derive(Debug, Copy, Clone)]
+pub struct Pcg32 {
+    state: u64,
+    increment: u64,
+}
+
+// -----------------------------------------------------------------------------------------
+// Pcg32 Constructor
+impl Pcg32 {
+    // Stream used for camera jitter (paths use their bounce index)
+    pub const CAMERA: u64 = u64::MAX;
+
+    pub fn new(seed: u64, stream: u64) -> Pcg32 {
+        let mut rng = Pcg32 {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.step();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.step();
+        rng
+    }
+
+    pub fn stream(seed: u64, index: u64, dimension: u64) -> Pcg32 {
+        // Hash the key so neighbouring indices don't start on related sequences
+        let key = mix(index ^ mix(dimension ^ mix(seed)));
+        Pcg32::new(mix(key), key)
+    }
+
+    pub fn from_state(state: u64, increment: u64) -> Pcg32 {
+        // Continues a stream saved with state (checkpoints)
+        Pcg32 { state, increment }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Pcg32 Members
+impl Pcg32 {
+    pub fn state(&self) -> (u64, u64) {
+        (self.state, self.increment)
+    }
+
+    fn step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(MULTIPLIER)
+            .wrapping_add(self.increment);
+    }
+}
+
+impl RngCore for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let state = self.state;
+        self.step();
+        let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+        xorshifted.rotate_right((state >> 59) as u32)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        (u64::from(self.next_u32()) << 32) | u64::from(self.next_u32())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn mix(value: u64) -> u64 {
+    // SplitMix64 finaliser
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// -----------------------------------------------------------------------------------------
@@ -1,6 +1,7 @@
 // -----------------------------------------------------------------------------------------
 
-use super::misc::StringLiteral;
+use super::material::MaterialId;
+use super::vector::Vec2;
 use super::vector::Vec3;
 
 // -----------------------------------------------------------------------------------------
@@ -9,6 +10,15 @@ use super::vector::Vec3;
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
+    pub differentials: Option<RayDifferentials>,
+}
+
+// -----------------------------------------------------------------------------------------
+// RayDifferentials Type (change in direction per pixel step, rays share an origin)
+#[derive(Debug, Default, Copy, Clone)]
+pub struct RayDifferentials {
+    pub dx: Vec3,
+    pub dy: Vec3,
 }
 
 // -----------------------------------------------------------------------------------------
@@ -17,10 +27,15 @@ impl Ray {
     pub const FORWARD: Ray = Ray {
         origin: Vec3::ZERO,
         direction: Vec3::FORWARD,
+        differentials: None,
     };
 
     pub fn new(origin: Vec3, direction: Vec3) -> Ray {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            differentials: None,
+        }
     }
 }
 
@@ -40,7 +55,14 @@ pub struct RayHitResult {
     pub distance: f32,
     pub position: Vec3,
     pub normal: Vec3,
-    pub material_name: StringLiteral,
+    pub shading_normal: Vec3,
+    pub uv: Vec2,
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    pub duv_dx: Vec2,
+    pub duv_dy: Vec2,
+    pub local_position: Vec3,
+    pub material: MaterialId,
 }
 
 // -----------------------------------------------------------------------------------------
@@ -51,16 +73,32 @@ impl RayHitResult {
         distance: f32,
         position: Vec3,
         normal: Vec3,
-        material_name: StringLiteral,
+        uv: Vec2,
+        material: MaterialId,
     ) -> RayHitResult {
+        // Default partials are unit length in the tangent plane, primitives override them
+        let (dpdu, dpdv) = Vec3::orthonormal_basis(normal);
         RayHitResult {
             hit,
             distance,
             position,
             normal,
-            material_name,
+            shading_normal: normal,
+            uv,
+            dpdu,
+            dpdv,
+            duv_dx: Vec2::ZERO,
+            duv_dy: Vec2::ZERO,
+            local_position: position,
+            material,
         }
     }
+
+    pub fn with_partials(mut self, dpdu: Vec3, dpdv: Vec3) -> RayHitResult {
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        self
+    }
 }
 
 // -----------------------------------------------------------------------------------------
@@ -71,7 +109,14 @@ impl RayHitResult {
         distance: 0.0,
         position: Vec3::ZERO,
         normal: Vec3::UP,
-        material_name: "debug",
+        shading_normal: Vec3::UP,
+        uv: Vec2::ZERO,
+        dpdu: Vec3::ZERO,
+        dpdv: Vec3::ZERO,
+        duv_dx: Vec2::ZERO,
+        duv_dy: Vec2::ZERO,
+        local_position: Vec3::ZERO,
+        material: 0,
     };
 
     pub const MAX_HIT: RayHitResult = RayHitResult {
@@ -79,8 +124,87 @@ impl RayHitResult {
         distance: std::f32::MAX,
         position: Vec3::ZERO,
         normal: Vec3::UP,
-        material_name: "debug",
+        shading_normal: Vec3::UP,
+        uv: Vec2::ZERO,
+        dpdu: Vec3::ZERO,
+        dpdv: Vec3::ZERO,
+        duv_dx: Vec2::ZERO,
+        duv_dy: Vec2::ZERO,
+        local_position: Vec3::ZERO,
+        material: 0,
     };
 }
 
 // -----------------------------------------------------------------------------------------
+// RayHitResult Members
+impl RayHitResult {
+    pub fn tangent_frame(&self) -> (Vec3, Vec3) {
+        // Orthogonalise dpdu against the shading normal, keeping the bitangent on dpdv's side
+        let normal = self.shading_normal;
+        let tangent = self.dpdu - (normal * Vec3::dot(normal, self.dpdu));
+        let tangent = if Vec3::length_squared(tangent) > 1e-12 {
+            Vec3::normalize(tangent)
+        } else {
+            Vec3::orthonormal_basis(normal).0
+        };
+        let bitangent = Vec3::cross(normal, tangent);
+        if Vec3::dot(bitangent, self.dpdv) < 0.0 {
+            (tangent, -bitangent)
+        } else {
+            (tangent, bitangent)
+        }
+    }
+
+    pub fn compute_differentials(&mut self, ray: &Ray) {
+        // Transfer the ray differentials onto the surface tangent plane (Igehy 1999)
+        let differentials = match ray.differentials {
+            Some(differentials) => differentials,
+            None => return,
+        };
+        let denom = Vec3::dot(ray.direction, self.normal);
+        if denom.abs() < 1e-6 {
+            return;
+        }
+        let transfer = |dd: Vec3| {
+            let dp = dd * self.distance;
+            dp - (ray.direction * (Vec3::dot(dp, self.normal) / denom))
+        };
+        let dpdx = transfer(differentials.dx);
+        let dpdy = transfer(differentials.dy);
+
+        // Solve dp = du * dpdu + dv * dpdv in the least squares sense
+        let a00 = Vec3::dot(self.dpdu, self.dpdu);
+        let a01 = Vec3::dot(self.dpdu, self.dpdv);
+        let a11 = Vec3::dot(self.dpdv, self.dpdv);
+        let det = (a00 * a11) - (a01 * a01);
+        if det.abs() < 1e-12 {
+            return;
+        }
+        let solve = |dp: Vec3| {
+            let b0 = Vec3::dot(dp, self.dpdu);
+            let b1 = Vec3::dot(dp, self.dpdv);
+            Vec2::new(((a11 * b0) - (a01 * b1)) / det, ((a00 * b1) - (a01 * b0)) / det)
+        };
+        let (duv_dx, duv_dy) = (solve(dpdx), solve(dpdy));
+        self.duv_dx = duv_dx;
+        self.duv_dy = duv_dy;
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// RayInterval Type (span of a ray spent inside a solid)
+#[derive(Copy, Clone)]
+pub struct RayInterval {
+    pub enter: RayHitResult,
+    pub exit: RayHitResult,
+}
+
+// -----------------------------------------------------------------------------------------
+// RayInterval Constructor
+impl RayInterval {
+    pub fn new(enter: RayHitResult, exit: RayHitResult) -> RayInterval {
+        RayInterval { enter, exit }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
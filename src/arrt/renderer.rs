@@ -0,0 +1,1080 @@
+// -----------------------------------------------------------------------------------------
+// Renderer, traces a scene into a framebuffer (blocking, progressive or in the background)
+// -----------------------------------------------------------------------------------------
+
+use super::bsdf::Bsdf;
+use super::camera::Camera;
+use super::checkpoint;
+use super::checkpoint::{Checkpoint, Fingerprint};
+use super::framebuffer::Framebuffer;
+use super::job::Job;
+use super::material::MaterialBank;
+use super::medium;
+use super::medium::MediumEvent;
+use super::misc;
+use super::quality::QualityPreset;
+use super::random::Pcg32;
+use super::ray::Ray;
+use super::ray::RayHitResult;
+use super::scene::Scene;
+use super::spectrum;
+use super::vector::Vec3;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+
+// -----------------------------------------------------------------------------------------
+// Config
+const EPSILON: f32 = 0.001;
+const CHUNK_SIZE: usize = 256;
+const CHECKPOINT_INTERVAL_S: f64 = 300.0;
+
+// -----------------------------------------------------------------------------------------
+// Config | Noise threshold
+const NOISE_MIN_SAMPLES: usize = 4;
+const NOISE_EPSILON: f32 = 0.01;
+
+// -----------------------------------------------------------------------------------------
+// Config | Adaptive sampling (rounds start after NOISE_MIN_SAMPLES uniform passes)
+const ADAPTIVE_MAX_SAMPLES_SCALE: usize = 16;
+const ADAPTIVE_MAX_ROUND_SAMPLES: usize = 8;
+
+// -----------------------------------------------------------------------------------------
+// Config | Sky
+const SKY_COLOUR_BOTTOM: Vec3 = Vec3 {
+    x: 1.0,
+    y: 1.0,
+    z: 1.0,
+};
+const SKY_COLOUR_TOP: Vec3 = Vec3 {
+    x: 0.5,
+    y: 0.7,
+    z: 1.0,
+};
+
+// -----------------------------------------------------------------------------------------
+// RayJob Type (one bounce of one sample)
+#[derive(Debug, Default, Copy, Clone)]
+pub struct RayJob {
+    pub pixel_index: usize,
+    pub sample_index: usize,
+    pub ray_index: usize,
+    pub bounce_index: u32,
+    pub ray: Ray,
+    pub throughput: Vec3,
+    pub wavelengths: Vec3,
+    pub max_bounces: u32,
+}
+
+// -----------------------------------------------------------------------------------------
+// RayJobResult Type
+#[derive(Debug, Default, Copy, Clone)]
+pub struct RayJobResult {
+    pub pixel_index: usize,
+    pub sample_index: usize,
+    pub ray_index: usize,
+    pub bounce_index: u32,
+    pub colour: Vec3,
+}
+
+// -----------------------------------------------------------------------------------------
+// Progress Type (time excludes progressive callbacks)
+#[derive(Serialize, Debug, Default, Copy, Clone)]
+pub struct Progress {
+    pub samples_per_pixel: usize,
+    pub total_samples_per_pixel: usize,
+    pub converged_pixel_count: usize,
+    pub pixel_count: usize,
+    pub time_s: f64,
+}
+
+// -----------------------------------------------------------------------------------------
+// Region Type (rows count up from the bottom, a region keeps the full frame's rays and streams)
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// -----------------------------------------------------------------------------------------
+// RegionSums Type (unresolved samples of a region, so they can be merged into a frame)
+#[derive(Debug, Clone)]
+pub struct RegionSums {
+    pub region: Region,
+    pub colour_sums: Vec<Vec3>,
+    pub sample_counts: Vec<usize>,
+}
+
+// -----------------------------------------------------------------------------------------
+// ErrorCallback Type (errors which don't stop a render)
+pub type ErrorCallback = Arc<dyn Fn(&str) + Send + Sync>;
+
+// -----------------------------------------------------------------------------------------
+// Renderer Type (scenes must be bound to the same material bank)
+#[derive(Clone)]
+pub struct Renderer {
+    pub quality: QualityPreset,
+    pub materials: Arc<MaterialBank>,
+    pub camera: Camera,
+    pub seed: u64,
+    pub debug_normals: bool,
+    pub debug_heatmap: bool,
+    pub spectral: bool,
+    pub debug_samples: bool,
+    pub thread_count: usize,
+    pub checkpoint_file: Option<String>,
+    pub checkpoint_interval_s: f64,
+
+    // Called when a checkpoint can't be saved (the render carries on, keeping the last one)
+    pub on_checkpoint_error: Option<ErrorCallback>,
+}
+
+// -----------------------------------------------------------------------------------------
+// Renderer Constructor
+impl Renderer {
+    pub fn new(quality: QualityPreset, materials: MaterialBank, camera: Camera) -> Renderer {
+        Renderer {
+            quality,
+            materials: Arc::new(materials),
+            camera,
+            seed: 0,
+            debug_normals: false,
+            debug_heatmap: false,
+            spectral: false,
+            debug_samples: false,
+            thread_count: num_cpus::get(),
+            checkpoint_file: None,
+            checkpoint_interval_s: CHECKPOINT_INTERVAL_S,
+            on_checkpoint_error: None,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Renderer Members
+impl Renderer {
+    pub fn render(&self, scene: &Scene) -> Framebuffer {
+        let state = RenderState::new(self, self.frame());
+        self.run(&self.job(scene), &state, self.sampler(self.frame()), |_| {
+            true
+        });
+        state.resolve()
+    }
+
+    pub fn render_region(&self, scene: &Scene, region: Region) -> RegionSums {
+        // Samples match those of the same pixels in a full frame render (uniform sampling)
+        let state = RenderState::new(self, region);
+        self.run(&self.job(scene), &state, self.sampler(region), |_| true);
+        let accumulation = state.accumulation.into_inner().unwrap();
+        RegionSums {
+            region,
+            colour_sums: accumulation.colour_sums,
+            sample_counts: accumulation.sample_counts,
+        }
+    }
+
+    pub fn render_progressive<F>(&self, scene: &Scene, mut on_pass: F) -> Framebuffer
+    where
+        F: FnMut(&Progress, &Framebuffer) -> bool,
+    {
+        // Called after each pass (or adaptive round), returning false stops the render
+        let state = RenderState::new(self, self.frame());
+        self.run(
+            &self.job(scene),
+            &state,
+            self.sampler(self.frame()),
+            |state| on_pass(&state.progress(), &state.resolve()),
+        );
+        state.resolve()
+    }
+
+    pub fn resume_progressive<F>(
+        &self,
+        scene: &Scene,
+        checkpoint_file: &str,
+        mut on_pass: F,
+    ) -> Result<Framebuffer, String>
+    where
+        F: FnMut(&Progress, &Framebuffer) -> bool,
+    {
+        // Continues a checkpoint of the same scene, preset, camera and settings
+        let checkpoint = Checkpoint::load_from_file(checkpoint_file)?;
+        self.fingerprint(scene)
+            .check(&checkpoint.fingerprint, checkpoint_file)?;
+        let state = RenderState::new(self, self.frame());
+        let sampler = state.restore(checkpoint, self.frame());
+        self.run(&self.job(scene), &state, sampler, |state| {
+            on_pass(&state.progress(), &state.resolve())
+        });
+        Ok(state.resolve())
+    }
+
+    pub fn render_async(&self, scene: &Scene) -> RenderHandle {
+        let renderer = self.clone();
+        let job = self.job(scene);
+        let state = Arc::new(RenderState::new(self, self.frame()));
+        let thread_state = state.clone();
+        thread::spawn(move || {
+            // A render that panics fails (with its message), so waiting on the handle always ends
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                renderer.run(
+                    &job,
+                    &thread_state,
+                    renderer.sampler(renderer.frame()),
+                    |_| true,
+                )
+            }));
+            if let Err(payload) = result {
+                *thread_state.error.lock().unwrap() = Some(misc::panic_message(payload));
+            }
+            thread_state.finished.store(true, Ordering::Relaxed);
+        });
+        RenderHandle { state }
+    }
+
+    pub fn resolve_sums(&self, colour_sums: &[Vec3], sample_counts: &[usize]) -> Framebuffer {
+        // Full frame sums (for example merged from regions) to an image
+        let frame = self.frame();
+        resolve_pixels(
+            frame,
+            colour_sums,
+            sample_counts,
+            self.spectral,
+            self.debug_samples,
+        )
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    fn frame(&self) -> Region {
+        Region {
+            x: 0,
+            y: 0,
+            width: self.quality.image_width,
+            height: self.quality.image_height,
+        }
+    }
+
+    fn job(&self, scene: &Scene) -> Job {
+        Job {
+            quality: self.quality.clone(),
+            materials: self.materials.clone(),
+            scene: Arc::new(scene.clone()),
+            debug_normals: self.debug_normals,
+            debug_heatmap: self.debug_heatmap,
+            spectral: self.spectral,
+        }
+    }
+
+    fn sampler(&self, region: Region) -> Sampler {
+        // Each pixel jitters its camera rays from its own stream
+        let quality = &self.quality;
+        let pixel_count = (region.width * region.height) as usize;
+        let max_samples_per_pixel = max_samples_per_pixel(quality);
+        let mut pixel_rngs = Vec::with_capacity(pixel_count);
+        for pixel_y in region.y..(region.y + region.height) {
+            for pixel_x in region.x..(region.x + region.width) {
+                let pixel_index = Camera::get_pixel_index(
+                    pixel_x,
+                    pixel_y,
+                    quality.image_width,
+                    max_samples_per_pixel,
+                );
+                pixel_rngs.push(Pcg32::stream(self.seed, pixel_index as u64, Pcg32::CAMERA));
+            }
+        }
+        Sampler {
+            region,
+            pass_rngs: pixel_rngs.clone(),
+            pixel_rngs,
+            scheduled: vec![0; pixel_count],
+            scheduled_count: 0,
+            pass_index: 0,
+        }
+    }
+
+    fn fingerprint(&self, scene: &Scene) -> Fingerprint {
+        // Scenes include their materials and images, presets leave out the name and time limit
+        let mut scene_parts = vec![
+            scene.description().into_bytes(),
+            self.materials.description().into_bytes(),
+        ];
+        for image in self.materials.images() {
+            scene_parts.push(fs::read(image).unwrap_or_default());
+        }
+        let mut quality = self.quality.clone();
+        quality.name = String::new();
+        quality.time_limit_s = None;
+        let preset = serde_json::to_string(&quality).unwrap();
+        let camera = &self.camera;
+        let settings = format!(
+            "{:?}|{:?}|{:?}|{:?}|{}|{}|{}|{}",
+            camera.position,
+            camera.lookat,
+            camera.fov,
+            camera.near_distance,
+            self.seed,
+            self.spectral,
+            self.debug_normals,
+            self.debug_samples
+        );
+        let scene_parts: Vec<&[u8]> = scene_parts.iter().map(|part| part.as_slice()).collect();
+        Fingerprint {
+            scene: checkpoint::hash(&scene_parts),
+            preset: checkpoint::hash(&[preset.as_bytes()]),
+            settings: checkpoint::hash(&[settings.as_bytes()]),
+        }
+    }
+
+    fn run<F>(&self, job: &Job, state: &RenderState, mut sampler: Sampler, mut on_pass: F)
+    where
+        F: FnMut(&RenderState) -> bool,
+    {
+        // Time limits include the time spent before a resume
+        let quality = &self.quality;
+        let deadline = quality.time_limit_s.map(|time_limit_s| {
+            time::precise_time_s() + (time_limit_s - state.progress().time_s).max(0.0)
+        });
+        let pixel_count = (sampler.region.width * sampler.region.height) as usize;
+        let max_samples_per_pixel = max_samples_per_pixel(quality);
+        let fingerprint = self
+            .checkpoint_file
+            .as_ref()
+            .map(|_| self.fingerprint(&job.scene));
+        let mut checkpoint_time = time::precise_time_s();
+
+        // Each pass adds one sample to every pixel that hasn't converged, adaptive renders then
+        // spend a pass worth of samples per round where the error is highest
+        let sample_budget = quality.samples_per_pixel * pixel_count;
+        while sampler.scheduled_count < sample_budget {
+            let timer_begin = time::precise_time_s();
+            let pass_samples = {
+                let accumulation = state.accumulation.lock().unwrap();
+                if quality.adaptive && sampler.pass_index >= NOISE_MIN_SAMPLES {
+                    let round_samples = pixel_count.min(sample_budget - sampler.scheduled_count);
+                    accumulation.allocate(round_samples, &sampler.scheduled, max_samples_per_pixel)
+                } else {
+                    accumulation.uniform(&sampler.scheduled, max_samples_per_pixel)
+                }
+            };
+            let ray_jobs = schedule_pass(
+                quality,
+                &self.camera,
+                max_samples_per_pixel,
+                &pass_samples,
+                &mut sampler,
+            );
+            if ray_jobs.is_empty() {
+                break;
+            }
+            self.trace_paths(&ray_jobs, job, state, deadline);
+            sampler.scheduled_count += ray_jobs.len();
+            sampler.pass_index += 1;
+            let stopped = state.stopped(deadline);
+
+            // Update convergence (once there are enough samples for a variance estimate)
+            let mut converged_pixel_count = 0;
+            if let Some(noise_threshold) = quality.noise_threshold {
+                if sampler.pass_index >= NOISE_MIN_SAMPLES {
+                    let mut accumulation = state.accumulation.lock().unwrap();
+                    converged_pixel_count = accumulation.update_converged(noise_threshold);
+                }
+            }
+            {
+                let mut progress = state.progress.lock().unwrap();
+                progress.time_s += time::precise_time_s() - timer_begin;
+                progress.converged_pixel_count = converged_pixel_count;
+                if !stopped {
+                    progress.samples_per_pixel = sampler.scheduled_count / pixel_count;
+                }
+            }
+
+            // Checkpoint every so often
+            if let (Some(file), Some(fingerprint)) = (&self.checkpoint_file, fingerprint) {
+                if !stopped
+                    && time::precise_time_s() - checkpoint_time >= self.checkpoint_interval_s
+                {
+                    self.save_checkpoint(&state.checkpoint(&sampler, fingerprint), file);
+                    checkpoint_time = time::precise_time_s();
+                }
+            }
+            if stopped || !on_pass(state) {
+                break;
+            }
+        }
+
+        // Final checkpoint (stopped renders can be resumed, for example with a longer time limit)
+        if let (Some(file), Some(fingerprint)) = (&self.checkpoint_file, fingerprint) {
+            self.save_checkpoint(&state.checkpoint(&sampler, fingerprint), file);
+        }
+    }
+
+    fn save_checkpoint(&self, checkpoint: &Checkpoint, file: &str) {
+        // A failed checkpoint doesn't stop the render (the previous one is kept)
+        if let Err(error) = checkpoint.save(file) {
+            if let Some(on_checkpoint_error) = &self.on_checkpoint_error {
+                on_checkpoint_error(&error);
+            }
+        }
+    }
+
+    fn trace_paths(
+        &self,
+        ray_jobs: &[RayJob],
+        job: &Job,
+        state: &RenderState,
+        deadline: Option<f64>,
+    ) {
+        // Threads take chunks of paths until none are left (or the render is stopped)
+        let chunks = pixel_chunks(ray_jobs);
+        let next_chunk = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            for _ in 0..self.thread_count.max(1) {
+                scope.spawn(|| {
+                    let mut results = Vec::with_capacity(CHUNK_SIZE);
+                    loop {
+                        let chunk_index = next_chunk.fetch_add(1, Ordering::Relaxed);
+                        if chunk_index >= chunks.len() || state.stopped(deadline) {
+                            return;
+                        }
+                        results.clear();
+                        for ray_job in chunks[chunk_index] {
+                            let colour = trace_path(*ray_job, job, self.seed);
+                            results.push((ray_job.pixel_index, colour));
+                        }
+                        state.accumulation.lock().unwrap().add(&results);
+                    }
+                });
+            }
+        });
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// RenderHandle Type (dropping the handle leaves the render running, cancel to stop it)
+pub struct RenderHandle {
+    state: Arc<RenderState>,
+}
+
+// -----------------------------------------------------------------------------------------
+// RenderHandle Members
+impl RenderHandle {
+    pub fn progress(&self) -> Progress {
+        self.state.progress()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.state.finished.load(Ordering::Relaxed)
+    }
+
+    pub fn preview(&self) -> Framebuffer {
+        // Pixels show the samples traced so far
+        self.state.resolve()
+    }
+
+    pub fn samples(&self) -> Framebuffer {
+        // Sample count of each pixel so far, relative to the most sampled pixel
+        let accumulation = self.state.accumulation.lock().unwrap();
+        resolve_pixels(
+            accumulation.region,
+            &accumulation.colour_sums,
+            &accumulation.sample_counts,
+            accumulation.spectral,
+            true,
+        )
+    }
+
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn error(&self) -> Option<String> {
+        // Set once a render has failed
+        self.state.error.lock().unwrap().clone()
+    }
+
+    pub fn wait(self) -> Result<Framebuffer, String> {
+        while !self.is_finished() {
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        match self.error() {
+            Some(error) => Err(error),
+            None => Ok(self.state.resolve()),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// RenderState Type (shared between the render threads and its handle)
+struct RenderState {
+    accumulation: Mutex<Accumulation>,
+    progress: Mutex<Progress>,
+    cancelled: AtomicBool,
+    finished: AtomicBool,
+    error: Mutex<Option<String>>,
+}
+
+// -----------------------------------------------------------------------------------------
+// RenderState Constructor
+impl RenderState {
+    fn new(renderer: &Renderer, region: Region) -> RenderState {
+        let pixel_count = (region.width * region.height) as usize;
+        let accumulation = Accumulation {
+            region,
+            spectral: renderer.spectral,
+            debug_samples: renderer.debug_samples,
+            colour_sums: vec![Vec3::BLACK; pixel_count],
+            sample_counts: vec![0; pixel_count],
+            luminance_sums: vec![0.0; pixel_count],
+            luminance_square_sums: vec![0.0; pixel_count],
+            converged: vec![false; pixel_count],
+        };
+        let progress = Progress {
+            total_samples_per_pixel: renderer.quality.samples_per_pixel,
+            pixel_count,
+            ..Progress::default()
+        };
+        RenderState {
+            accumulation: Mutex::new(accumulation),
+            progress: Mutex::new(progress),
+            cancelled: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+            error: Mutex::new(None),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// RenderState Members
+impl RenderState {
+    fn progress(&self) -> Progress {
+        *self.progress.lock().unwrap()
+    }
+
+    fn resolve(&self) -> Framebuffer {
+        self.accumulation.lock().unwrap().resolve()
+    }
+
+    fn restore(&self, checkpoint: Checkpoint, region: Region) -> Sampler {
+        // Accumulation and progress from a checkpoint, pixels continue from their sample count
+        let mut accumulation = self.accumulation.lock().unwrap();
+        let mut progress = self.progress.lock().unwrap();
+        let scheduled_count = checkpoint.sample_counts.iter().sum();
+        progress.samples_per_pixel = scheduled_count / progress.pixel_count;
+        progress.converged_pixel_count = checkpoint.converged.iter().filter(|c| **c).count();
+        progress.time_s = checkpoint.time_s;
+        accumulation.colour_sums = checkpoint.colour_sums;
+        accumulation.sample_counts = checkpoint.sample_counts;
+        accumulation.luminance_sums = checkpoint.luminance_sums;
+        accumulation.luminance_square_sums = checkpoint.luminance_square_sums;
+        accumulation.converged = checkpoint.converged;
+        Sampler {
+            region,
+            pass_rngs: checkpoint.pixel_rngs.clone(),
+            pixel_rngs: checkpoint.pixel_rngs,
+            scheduled: accumulation.sample_counts.clone(),
+            scheduled_count,
+            pass_index: checkpoint.pass_index,
+        }
+    }
+
+    fn checkpoint(&self, sampler: &Sampler, fingerprint: Fingerprint) -> Checkpoint {
+        // Samples of a stopped pass that weren't traced are scheduled again after a resume, from
+        // the streams their pixels had at the start of the pass (a pixel is traced all or none)
+        let accumulation = self.accumulation.lock().unwrap();
+        let traced_count: usize = accumulation.sample_counts.iter().sum();
+        let pass_index = if traced_count < sampler.scheduled_count {
+            sampler.pass_index - 1
+        } else {
+            sampler.pass_index
+        };
+        let pixel_rngs = (0..sampler.pixel_rngs.len())
+            .map(|pixel_index| {
+                if accumulation.sample_counts[pixel_index] < sampler.scheduled[pixel_index] {
+                    sampler.pass_rngs[pixel_index]
+                } else {
+                    sampler.pixel_rngs[pixel_index]
+                }
+            })
+            .collect();
+        Checkpoint {
+            fingerprint,
+            pass_index,
+            time_s: self.progress().time_s,
+            colour_sums: accumulation.colour_sums.clone(),
+            sample_counts: accumulation.sample_counts.clone(),
+            luminance_sums: accumulation.luminance_sums.clone(),
+            luminance_square_sums: accumulation.luminance_square_sums.clone(),
+            converged: accumulation.converged.clone(),
+            pixel_rngs,
+        }
+    }
+
+    fn stopped(&self, deadline: Option<f64>) -> bool {
+        // Cancelled, or out of time
+        let out_of_time = deadline.is_some_and(|deadline| time::precise_time_s() >= deadline);
+        out_of_time || self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Sampler Type (samples scheduled per pixel, and the streams their camera rays are jittered by)
+struct Sampler {
+    region: Region,
+    pixel_rngs: Vec<Pcg32>,
+    pass_rngs: Vec<Pcg32>,
+    scheduled: Vec<usize>,
+    scheduled_count: usize,
+    pass_index: usize,
+}
+
+// -----------------------------------------------------------------------------------------
+// Accumulation Type (running sums per pixel, passes add samples in the same order every run)
+struct Accumulation {
+    region: Region,
+    spectral: bool,
+    debug_samples: bool,
+    colour_sums: Vec<Vec3>,
+    sample_counts: Vec<usize>,
+    luminance_sums: Vec<f32>,
+    luminance_square_sums: Vec<f32>,
+    converged: Vec<bool>,
+}
+
+// -----------------------------------------------------------------------------------------
+// Accumulation Members
+impl Accumulation {
+    fn add(&mut self, results: &[(usize, Vec3)]) {
+        for (pixel_index, colour) in results {
+            let pixel_index = *pixel_index;
+            let luminance = self.luminance(*colour);
+            self.colour_sums[pixel_index] += *colour;
+            self.sample_counts[pixel_index] += 1;
+            self.luminance_sums[pixel_index] += luminance;
+            self.luminance_square_sums[pixel_index] += luminance * luminance;
+        }
+    }
+
+    fn luminance(&self, colour: Vec3) -> f32 {
+        // Spectral results are XYZ, where Y is luminance
+        if self.spectral {
+            colour.y
+        } else {
+            Vec3::dot(colour, Vec3::new(0.2126, 0.7152, 0.0722))
+        }
+    }
+
+    fn relative_error(&self, pixel_index: usize) -> f32 {
+        // Standard error of the mean luminance, relative to the mean
+        let sample_count = self.sample_counts[pixel_index] as f32;
+        if sample_count < 2.0 {
+            return f32::INFINITY;
+        }
+        let mean = self.luminance_sums[pixel_index] / sample_count;
+        let square_mean = self.luminance_square_sums[pixel_index] / sample_count;
+        let variance = (square_mean - mean * mean).max(0.0) * sample_count / (sample_count - 1.0);
+        (variance / sample_count).sqrt() / (mean + NOISE_EPSILON)
+    }
+
+    fn update_converged(&mut self, noise_threshold: f32) -> usize {
+        for pixel_index in 0..self.converged.len() {
+            if !self.converged[pixel_index] {
+                self.converged[pixel_index] = self.relative_error(pixel_index) < noise_threshold;
+            }
+        }
+        self.converged
+            .iter()
+            .filter(|converged| **converged)
+            .count()
+    }
+
+    fn uniform(&self, scheduled: &[usize], max_samples_per_pixel: usize) -> Vec<usize> {
+        // One sample for every pixel that hasn't converged (or run out of samples)
+        (0..scheduled.len())
+            .map(|pixel_index| {
+                let full = scheduled[pixel_index] >= max_samples_per_pixel;
+                (!full && !self.converged[pixel_index]) as usize
+            })
+            .collect()
+    }
+
+    fn allocate(
+        &self,
+        round_samples: usize,
+        scheduled: &[usize],
+        max_samples_per_pixel: usize,
+    ) -> Vec<usize> {
+        // Samples in proportion to each pixel's relative error, fractions carry over to the
+        // next pixel so the round spends its samples (in the same way every run)
+        let errors: Vec<f32> = (0..scheduled.len())
+            .map(|pixel_index| {
+                let full = scheduled[pixel_index] >= max_samples_per_pixel;
+                if full || self.converged[pixel_index] {
+                    0.0
+                } else {
+                    self.relative_error(pixel_index)
+                }
+            })
+            .collect();
+        let error_sum: f32 = errors.iter().sum();
+        let mut pass_samples = vec![0; scheduled.len()];
+        if error_sum <= 0.0 {
+            return pass_samples;
+        }
+        let mut carry = 0.0;
+        for (pixel_index, error) in errors.iter().enumerate() {
+            let share = carry + (round_samples as f32 * error / error_sum);
+            let remaining = max_samples_per_pixel - scheduled[pixel_index];
+            let samples = (share as usize)
+                .min(ADAPTIVE_MAX_ROUND_SAMPLES)
+                .min(remaining);
+            pass_samples[pixel_index] = samples;
+            carry = (share - samples as f32).min(1.0);
+        }
+        pass_samples
+    }
+
+    fn resolve(&self) -> Framebuffer {
+        resolve_pixels(
+            self.region,
+            &self.colour_sums,
+            &self.sample_counts,
+            self.spectral,
+            self.debug_samples,
+        )
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn resolve_pixels(
+    region: Region,
+    colour_sums: &[Vec3],
+    sample_counts: &[usize],
+    spectral: bool,
+    debug_samples: bool,
+) -> Framebuffer {
+    // Flipped to top row first, and clamped
+    let (width, height) = (region.width, region.height);
+    let max_sample_count = sample_counts.iter().copied().max().unwrap_or(0).max(1);
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+    for pixel_y in (0..height).rev() {
+        for pixel_x in 0..width {
+            let pixel_index = ((pixel_y * width) + pixel_x) as usize;
+            let sample_count = sample_counts[pixel_index];
+            let colour = if debug_samples {
+                // Sample count, relative to the most sampled pixel
+                Vec3::ONE * (sample_count as f32 / max_sample_count as f32)
+            } else if sample_count > 0 {
+                resolve(colour_sums[pixel_index], sample_count, spectral)
+            } else {
+                Vec3::BLACK
+            };
+            pixels.push(Vec3::min(Vec3::max(colour, Vec3::ZERO), Vec3::ONE));
+        }
+    }
+    Framebuffer::new(width, height, pixels)
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn schedule_pass(
+    quality: &QualityPreset,
+    camera: &Camera,
+    max_samples_per_pixel: usize,
+    pass_samples: &[usize],
+    sampler: &mut Sampler,
+) -> Vec<RayJob> {
+    // Camera rays are generated per pass, pixels only advance their stream when sampled
+    sampler.pass_rngs.clone_from(&sampler.pixel_rngs);
+    let (image_width, image_height) = (quality.image_width, quality.image_height);
+    let (near_origin, pixel_size) = camera.frustum(image_width, image_height);
+    let region = sampler.region;
+    let mut ray_jobs = Vec::with_capacity(pass_samples.iter().sum());
+    for pixel_y in region.y..(region.y + region.height) {
+        for pixel_x in region.x..(region.x + region.width) {
+            let pixel_index = (((pixel_y - region.y) * region.width) + pixel_x - region.x) as usize;
+            let first_ray_index =
+                Camera::get_pixel_index(pixel_x, pixel_y, image_width, max_samples_per_pixel);
+            for _ in 0..pass_samples[pixel_index] {
+                let sample_index = sampler.scheduled[pixel_index];
+                sampler.scheduled[pixel_index] += 1;
+                let ray = camera.get_sample_ray(
+                    pixel_x,
+                    pixel_y,
+                    sample_index,
+                    &mut sampler.pixel_rngs[pixel_index],
+                    near_origin,
+                    pixel_size,
+                );
+                ray_jobs.push(RayJob {
+                    pixel_index,
+                    sample_index,
+                    ray_index: first_ray_index + sample_index,
+                    ray,
+                    throughput: Vec3::ONE,
+                    wavelengths: Vec3::ZERO,
+                    bounce_index: 0,
+                    max_bounces: quality.max_bounces,
+                });
+            }
+        }
+    }
+    ray_jobs
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn max_samples_per_pixel(quality: &QualityPreset) -> usize {
+    // Adaptive renders can give a pixel more than the average (ray indices are spaced by this)
+    if quality.adaptive {
+        quality.samples_per_pixel * ADAPTIVE_MAX_SAMPLES_SCALE
+    } else {
+        quality.samples_per_pixel
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn pixel_chunks(ray_jobs: &[RayJob]) -> Vec<&[RayJob]> {
+    // Chunks never split a pixel's samples, so each pixel sums its samples in the same order
+    let mut chunks = Vec::new();
+    let mut chunk_begin = 0;
+    for index in 1..=ray_jobs.len() {
+        let full = index - chunk_begin >= CHUNK_SIZE;
+        if index == ray_jobs.len()
+            || (full && ray_jobs[index].pixel_index != ray_jobs[index - 1].pixel_index)
+        {
+            chunks.push(&ray_jobs[chunk_begin..index]);
+            chunk_begin = index;
+        }
+    }
+    chunks
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn trace_path(ray_job: RayJob, job: &Job, rng_seed: u64) -> Vec3 {
+    // Every bounce of one sample on this thread, summed
+    let mut colour = Vec3::BLACK;
+    let mut next_job = Some(ray_job);
+    while let Some(ray_job) = next_job {
+        let (job_result, bounce_job) = trace(ray_job, job, rng_seed);
+        colour += job_result.colour;
+        next_job = bounce_job;
+    }
+    colour
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn trace(mut ray_job: RayJob, job: &Job, rng_seed: u64) -> (RayJobResult, Option<RayJob>) {
+    // Every bounce of every sample has its own stream, so results don't depend on scheduling
+    let mut rng = Pcg32::stream(
+        rng_seed,
+        ray_job.ray_index as u64,
+        u64::from(ray_job.bounce_index),
+    );
+
+    // Spectral paths pick their wavelengths on the first bounce, and keep them after
+    if job.spectral && ray_job.bounce_index == 0 {
+        ray_job.wavelengths = spectrum::sample_wavelengths(&mut rng);
+    }
+    let wavelengths = if job.spectral {
+        Some(ray_job.wavelengths)
+    } else {
+        None
+    };
+    let to_path = |rgb: Vec3| match wavelengths {
+        Some(wavelengths) => spectrum::from_rgb(rgb, wavelengths),
+        None => rgb,
+    };
+    let ray = &ray_job.ray;
+    let (colour, emission, result) = sample_scene(ray, job);
+    let (colour, emission) = (to_path(colour), to_path(emission));
+
+    // Participating media between the ray origin and the surface (or sky)
+    let extent = if result.hit {
+        result.distance
+    } else {
+        f32::MAX
+    };
+    let event = if job.debug_normals {
+        MediumEvent::Pass(Vec3::ONE)
+    } else {
+        job.scene.sample_media(ray, extent, &mut rng)
+    };
+
+    // Paths keep scattering until the bounce limit, where their colour is used directly
+    let can_bounce = (ray_job.bounce_index + 1) <= ray_job.max_bounces;
+    let mut throughput = ray_job.throughput;
+    let mut next_ray = None;
+    let radiance = match event {
+        MediumEvent::Absorb => Vec3::BLACK,
+        MediumEvent::Scatter {
+            position,
+            weight,
+            anisotropy,
+            albedo,
+        } => {
+            throughput *= to_path(weight);
+            if can_bounce {
+                let direction =
+                    medium::sample_henyey_greenstein(ray.direction, anisotropy, &mut rng);
+                next_ray = Some((Ray::new(position, direction), Vec3::ONE));
+                Vec3::BLACK
+            } else {
+                to_path(albedo)
+            }
+        }
+        MediumEvent::Pass(weight) => {
+            throughput *= to_path(weight);
+            if result.hit && !job.debug_normals && can_bounce {
+                next_ray = scatter_surface(ray, &result, job, wavelengths, &mut rng);
+                emission
+            } else {
+                colour + emission
+            }
+        }
+    };
+
+    // Result (spectral results are accumulated as XYZ, then resolved to sRGB)
+    let colour = match wavelengths {
+        Some(wavelengths) => spectrum::to_xyz(throughput * radiance, wavelengths),
+        None => throughput * radiance,
+    };
+    let job_result = RayJobResult {
+        pixel_index: ray_job.pixel_index,
+        sample_index: ray_job.sample_index,
+        ray_index: ray_job.ray_index,
+        bounce_index: ray_job.bounce_index,
+        colour,
+    };
+
+    // Setup bounce job?
+    let bounce_job = next_ray.map(|(next_ray, weight)| {
+        let mut bounce_job = ray_job;
+        bounce_job.ray = next_ray;
+        bounce_job.throughput = throughput * weight;
+        bounce_job.bounce_index = ray_job.bounce_index + 1;
+        bounce_job
+    });
+    (job_result, bounce_job)
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn scatter_surface(
+    ray: &Ray,
+    result: &RayHitResult,
+    job: &Job,
+    wavelengths: Option<Vec3>,
+    rng: &mut Pcg32,
+) -> Option<(Ray, Vec3)> {
+    // Weights are per wavelength for spectral paths
+    let to_path = |rgb: Vec3| match wavelengths {
+        Some(wavelengths) => spectrum::from_rgb(rgb, wavelengths),
+        None => rgb,
+    };
+
+    // Subsurface materials walk beneath the surface and continue from where they leave it
+    let material = job.materials.get(result.material);
+    if let Some(subsurface) = &material.subsurface {
+        let (exit_ray, weight) =
+            subsurface.scatter(ray, result, |ray| job.scene.intersect(ray), rng)?;
+        return Some((exit_ray, to_path(weight)));
+    }
+
+    // Sample material for reflected direction (dispersive glass uses the hero wavelength)
+    let mut bsdf = Bsdf::new(material, result);
+    if let Some(wavelengths) = wavelengths {
+        bsdf = bsdf.at_wavelength(wavelengths.x);
+    }
+    let sample = bsdf.sample(-ray.direction, rng)?;
+
+    // Shading normals can tilt the bounce across the surface, mirror it back
+    let mut refelcted_ray_direction = sample.direction;
+    let geometric_side = Vec3::dot(refelcted_ray_direction, result.normal);
+    let shading_side = Vec3::dot(refelcted_ray_direction, result.shading_normal);
+    if (geometric_side < 0.0) != (shading_side < 0.0) {
+        refelcted_ray_direction -= result.normal * (2.0 * geometric_side);
+    }
+
+    // Calculate reflected ray (offset along the geometric normal, to the exit side)
+    let side = Vec3::dot(refelcted_ray_direction, result.normal).signum();
+    let reflected_ray_origin = result.position + (result.normal * (EPSILON * side));
+    let reflected_ray = Ray::new(reflected_ray_origin, refelcted_ray_direction);
+
+    // Refraction through dispersive glass splits the wavelengths apart, only the hero continues
+    let mut weight = to_path(sample.weight);
+    let refracted = side != Vec3::dot(-ray.direction, result.normal).signum();
+    if wavelengths.is_some() && bsdf.is_dispersive() && refracted {
+        weight = Vec3::new(weight.x * 3.0, 0.0, 0.0);
+    }
+    Some((reflected_ray, weight))
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn resolve(sum: Vec3, sample_count: usize, spectral: bool) -> Vec3 {
+    // Average of a pixel's samples, spectral renders accumulate XYZ
+    let mut average = sum;
+    average /= sample_count as f32;
+    if spectral {
+        average = spectrum::xyz_to_rgb(average);
+    }
+    average
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn sample_scene(ray: &Ray, job: &Job) -> (Vec3, Vec3, RayHitResult) {
+    // Test against scene primitives
+    let mut result = job.scene.intersect(ray);
+    result.compute_differentials(ray);
+
+    // Use background?
+    let colour: Vec3;
+    let mut emission = Vec3::BLACK;
+    if job.debug_normals {
+        if result.hit {
+            // Show shading normal (includes normal and bump maps)
+            let material = job.materials.get(result.material);
+            result.shading_normal = material.shading_normal(&result);
+        }
+        colour = Vec3::new(
+            (result.shading_normal.x + 1.0) * 0.5,
+            (result.shading_normal.y + 1.0) * 0.5,
+            (result.shading_normal.z + 1.0) * 0.5,
+        );
+    } else if !result.hit {
+        colour = sample_background(ray);
+    } else {
+        // Grab material
+        let material = job.materials.get(result.material);
+        result.shading_normal = material.shading_normal(&result);
+
+        // Shade pixel (diffuse)
+        colour = material.albedo(&result);
+        emission = material.emission.sample(&result);
+    }
+
+    // Return info
+    (colour, emission, result)
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn sample_background(ray: &Ray) -> Vec3 {
+    let t = (ray.direction.y + 1.0) * 0.5;
+    Vec3::lerp(SKY_COLOUR_BOTTOM, SKY_COLOUR_TOP, t)
+}
+
+// -----------------------------------------------------------------------------------------
@@ -0,0 +1,401 @@
+// -----------------------------------------------------------------------------------------
+
+use super::bvh::Bvh;
+use super::geometry::Aabb;
+use super::geometry::MaterialRef;
+use super::geometry::Primitive;
+use super::intersect;
+use super::material::MaterialBank;
+use super::medium;
+use super::medium::Medium;
+use super::medium::MediumEvent;
+use super::medium::MediumSegment;
+use super::medium::Volume;
+use super::random::Pcg32;
+use super::ray::Ray;
+use super::ray::RayHitResult;
+use super::vector::Vec3;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+// -----------------------------------------------------------------------------------------
+
+// Rays which reach the sky only track fog this far (it has no end otherwise)
+const FOG_DISTANCE: f32 = 1000.0;
+
+// -----------------------------------------------------------------------------------------
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SceneDescription {
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    primitives: Vec<Primitive>,
+    #[serde(default)]
+    fog: Option<Medium>,
+    #[serde(default)]
+    volumes: Vec<Volume>,
+}
+
+// -----------------------------------------------------------------------------------------
+// SceneLayer Type (the primitives of one scene file, partitioned separately)
+#[derive(Clone)]
+struct SceneLayer {
+    file: PathBuf,
+    modified: Option<SystemTime>,
+    includes: Vec<PathBuf>,
+    primitives: Vec<Primitive>,
+    unbounded: Vec<usize>,
+    bvh: Bvh,
+    fog: Option<Medium>,
+    volumes: Vec<Volume>,
+}
+
+// -----------------------------------------------------------------------------------------
+
+#[derive(Clone)]
+pub struct Scene {
+    file: String,
+    layers: Vec<Arc<SceneLayer>>,
+}
+
+// -----------------------------------------------------------------------------------------
+
+impl Scene {
+    // -------------------------------------------------------------------------------------
+
+    pub fn try_load_from_file(file: &str, materials: &MaterialBank) -> Result<Scene, String> {
+        Scene::load_layers(file, None, &[], materials)
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn try_load_from_str(
+        name: &str,
+        data: &str,
+        materials: &MaterialBank,
+    ) -> Result<Scene, String> {
+        // Scene file contents (for example sent to the render service), includes are still
+        // files, relative to the working directory
+        let root = SceneLayer::parse(Path::new(name), None, data, materials)?;
+        Scene::load_layers(name, Some(root), &[], materials)
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn reload(&self, materials: &MaterialBank) -> Result<Scene, String> {
+        // Files which haven't been modified keep their primitives and partitioning
+        Scene::load_layers(&self.file, None, &self.layers, materials)
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    fn load_layers(
+        file: &str,
+        root: Option<SceneLayer>,
+        previous: &[Arc<SceneLayer>],
+        materials: &MaterialBank,
+    ) -> Result<Scene, String> {
+        let mut layers = Vec::new();
+        let mut pending = vec![PathBuf::from(file)];
+        if let Some(root) = root {
+            // Already parsed, only its includes are loaded
+            pending = root.includes.iter().rev().cloned().collect();
+            layers.push(Arc::new(root));
+        }
+        while let Some(file) = pending.pop() {
+            // Files included more than once (or recursively) are only added the first time
+            if layers
+                .iter()
+                .any(|layer: &Arc<SceneLayer>| layer.file == file)
+            {
+                continue;
+            }
+            let modified = fs::metadata(&file)
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            let unchanged = previous.iter().find(|layer| {
+                layer.file == file && modified.is_some() && layer.modified == modified
+            });
+            let layer = match unchanged {
+                Some(layer) => layer.clone(),
+                None => Arc::new(SceneLayer::load(&file, modified, materials)?),
+            };
+            pending.extend(layer.includes.iter().rev().cloned());
+            layers.push(layer);
+        }
+
+        // Return scene
+        Ok(Scene {
+            file: String::from(file),
+            layers,
+        })
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn new(name: &str, primitives: Vec<Primitive>) -> Scene {
+        Scene {
+            file: String::from(name),
+            layers: vec![Arc::new(SceneLayer::new(
+                PathBuf::from(name),
+                None,
+                Vec::new(),
+                primitives,
+            ))],
+        }
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn from_description(
+        name: &str,
+        description: &str,
+        materials: &MaterialBank,
+    ) -> Result<Scene, String> {
+        // Layers written by description (for example sent to a distributed render's workers)
+        let layers: Vec<(Vec<Primitive>, Option<Medium>, Vec<Volume>)> =
+            serde_json::from_str(description)
+                .map_err(|error| format!("ERROR: Invalid scene description: {}", error))?;
+        let layers = layers
+            .into_iter()
+            .map(|(primitives, fog, volumes)| {
+                let mut layer = SceneLayer::new(PathBuf::from(name), None, Vec::new(), primitives);
+                layer.bind_materials(materials);
+                layer.fog = fog;
+                layer.volumes = volumes;
+                Arc::new(layer)
+            })
+            .collect();
+        Ok(Scene {
+            file: String::from(name),
+            layers,
+        })
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.layers.iter().map(|layer| layer.file.clone()).collect()
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn primitive_count(&self) -> usize {
+        self.layers.iter().map(|layer| layer.primitives.len()).sum()
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn bind_materials(&mut self, materials: &MaterialBank) {
+        // Must be repeated whenever the material bank is replaced (ids are per bank)
+        for layer in &mut self.layers {
+            Arc::make_mut(layer).bind_materials(materials);
+        }
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn intersect(&self, ray: &Ray) -> RayHitResult {
+        let mut result = RayHitResult::MAX_HIT;
+        for layer in &self.layers {
+            let layer_result = layer.intersect(ray);
+            if layer_result.hit && (layer_result.distance < result.distance) {
+                result = layer_result;
+            }
+        }
+        result
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn sample_media(&self, ray: &Ray, extent: f32, rng: &mut Pcg32) -> MediumEvent {
+        // Gather the media the ray passes through before reaching the surface
+        let mut segments = Vec::new();
+        for layer in &self.layers {
+            if let Some(fog) = &layer.fog {
+                segments.push(MediumSegment {
+                    medium: fog,
+                    bounds: Aabb::new(Vec3::ZERO, Vec3::ONE),
+                    near: 0.0,
+                    far: extent.min(FOG_DISTANCE),
+                });
+            }
+            for volume in &layer.volumes {
+                if let Some((near, far)) = volume.shape.interval(ray) {
+                    let (near, far) = (near.max(0.0), far.min(extent));
+                    if near < far {
+                        segments.push(MediumSegment {
+                            medium: &volume.medium,
+                            bounds: volume.shape.bounds(),
+                            near,
+                            far,
+                        });
+                    }
+                }
+            }
+        }
+        if segments.is_empty() {
+            return MediumEvent::Pass(Vec3::ONE);
+        }
+        medium::track(ray, extent, &segments, rng)
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn description(&self) -> String {
+        // Loaded primitives and media of every layer as JSON (for telling scenes apart)
+        let layers: Vec<_> = self
+            .layers
+            .iter()
+            .map(|layer| (&layer.primitives, &layer.fog, &layer.volumes))
+            .collect();
+        serde_json::to_string(&layers).unwrap()
+    }
+
+    // -------------------------------------------------------------------------------------
+}
+
+// -----------------------------------------------------------------------------------------
+// SceneLayer Constructor
+impl SceneLayer {
+    fn load(
+        file: &Path,
+        modified: Option<SystemTime>,
+        materials: &MaterialBank,
+    ) -> Result<SceneLayer, String> {
+        // Load scene file
+        let data = fs::read_to_string(file)
+            .map_err(|_| format!("ERROR: Could not load scene file: '{}'", file.display()))?;
+        SceneLayer::parse(file, modified, &data, materials)
+    }
+
+    fn parse(
+        file: &Path,
+        modified: Option<SystemTime>,
+        data: &str,
+        materials: &MaterialBank,
+    ) -> Result<SceneLayer, String> {
+        // Deserialise
+        let description: SceneDescription = serde_json::from_str(data).map_err(|error| {
+            format!("ERROR: Invalid scene file '{}': {}", file.display(), error)
+        })?;
+
+        // Included files are relative to the including file
+        let directory = file.parent().unwrap_or_else(|| Path::new(""));
+        let includes = description
+            .include
+            .iter()
+            .map(|include| normalise(&directory.join(include)))
+            .collect();
+
+        // Bind material names to ids once, so hits never look materials up by name
+        let mut layer = SceneLayer::new(
+            PathBuf::from(file),
+            modified,
+            includes,
+            description.primitives,
+        );
+        layer.bind_materials(materials);
+        layer.fog = description.fog;
+        layer.volumes = description.volumes;
+        Ok(layer)
+    }
+
+    fn new(
+        file: PathBuf,
+        modified: Option<SystemTime>,
+        includes: Vec<PathBuf>,
+        primitives: Vec<Primitive>,
+    ) -> SceneLayer {
+        // Split primitives into those which can be partitioned and those which can't (planes)
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        for (index, primitive) in primitives.iter().enumerate() {
+            match primitive.bounds() {
+                Some(bounds) => bounded.push((index, bounds)),
+                None => unbounded.push(index),
+            }
+        }
+
+        // Return layer
+        SceneLayer {
+            file,
+            modified,
+            includes,
+            bvh: Bvh::build(&bounded),
+            primitives,
+            unbounded,
+            fog: None,
+            volumes: Vec::new(),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// SceneLayer Members
+impl SceneLayer {
+    fn bind_materials(&mut self, materials: &MaterialBank) {
+        let mut references: Vec<&mut MaterialRef> = self
+            .primitives
+            .iter_mut()
+            .flat_map(|primitive| primitive.materials_mut())
+            .collect();
+        let names: Vec<String> = references
+            .iter()
+            .map(|reference| reference.name.clone())
+            .collect();
+        for (reference, id) in references.iter_mut().zip(materials.resolve(&names)) {
+            reference.id = id;
+        }
+    }
+
+    fn intersect(&self, ray: &Ray) -> RayHitResult {
+        // Test against partitioned primitives
+        let primitives = &self.primitives;
+        let mut result = self.bvh.intersect(ray, |index| {
+            SceneLayer::intersect_primitive(ray, &primitives[index])
+        });
+
+        // Test against unbounded primitives
+        for &index in &self.unbounded {
+            let primitive_result = SceneLayer::intersect_primitive(ray, &primitives[index]);
+            if primitive_result.hit && (primitive_result.distance < result.distance) {
+                result = primitive_result;
+            }
+        }
+        result
+    }
+
+    fn intersect_primitive(ray: &Ray, primitive: &Primitive) -> RayHitResult {
+        let mut result = intersect::ray_primitive(ray, primitive);
+        if result.hit {
+            result.local_position = result.position - primitive.origin();
+        }
+        result
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn normalise(path: &Path) -> PathBuf {
+    // Collapse "." and ".." so the same file is recognised however it was included
+    let mut normalised = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normalised.file_name().is_some() => {
+                normalised.pop();
+            }
+            _ => normalised.push(component),
+        }
+    }
+    normalised
+}
+
+// -----------------------------------------------------------------------------------------
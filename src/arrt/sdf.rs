@@ -0,0 +1,241 @@
+// -----------------------------------------------------------------------------------------
+// Signed distance functions, see https://iquilezles.org/www/articles/distfunctions/distfunctions.htm
+// -----------------------------------------------------------------------------------------
+
+use super::geometry::Aabb;
+use super::geometry::MaterialRef;
+use super::vector::Vec3;
+use serde::{Deserialize, Serialize};
+
+// -----------------------------------------------------------------------------------------
+// SdfNode Type (tagged by "type" in scene files)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SdfNode {
+    // Primitives
+    Sphere {
+        centre: Vec3,
+        radius: f32,
+    },
+    Box {
+        centre: Vec3,
+        half_size: Vec3,
+    },
+    RoundBox {
+        centre: Vec3,
+        half_size: Vec3,
+        radius: f32,
+    },
+    Torus {
+        centre: Vec3,
+        major_radius: f32,
+        minor_radius: f32,
+    },
+    Capsule {
+        a: Vec3,
+        b: Vec3,
+        radius: f32,
+    },
+
+    // Combinators
+    Union {
+        children: Vec<SdfNode>,
+    },
+    Intersection {
+        a: Box<SdfNode>,
+        b: Box<SdfNode>,
+    },
+    Subtraction {
+        a: Box<SdfNode>,
+        b: Box<SdfNode>,
+    },
+    SmoothUnion {
+        a: Box<SdfNode>,
+        b: Box<SdfNode>,
+        k: f32,
+    },
+
+    // Domain operators
+    Repeat {
+        period: Vec3,
+        child: Box<SdfNode>,
+    },
+    Twist {
+        amount: f32,
+        child: Box<SdfNode>,
+    },
+}
+
+// -----------------------------------------------------------------------------------------
+// SdfNode Members
+impl SdfNode {
+    pub fn distance(&self, p: Vec3) -> f32 {
+        match self {
+            SdfNode::Sphere { centre, radius } => Vec3::length(p - *centre) - radius,
+            SdfNode::Box { centre, half_size } => sdf_box(p - *centre, *half_size),
+            SdfNode::RoundBox {
+                centre,
+                half_size,
+                radius,
+            } => sdf_box(p - *centre, *half_size - (Vec3::ONE * *radius)) - radius,
+            SdfNode::Torus {
+                centre,
+                major_radius,
+                minor_radius,
+            } => {
+                let q = p - *centre;
+                let ring = ((q.x * q.x) + (q.z * q.z)).sqrt() - major_radius;
+                ((ring * ring) + (q.y * q.y)).sqrt() - minor_radius
+            }
+            SdfNode::Capsule { a, b, radius } => {
+                let pa = p - *a;
+                let ba = *b - *a;
+                let h = (Vec3::dot(pa, ba) / Vec3::dot(ba, ba)).clamp(0.0, 1.0);
+                Vec3::length(pa - (ba * h)) - radius
+            }
+            SdfNode::Union { children } => children
+                .iter()
+                .map(|child| child.distance(p))
+                .fold(f32::MAX, f32::min),
+            SdfNode::Intersection { a, b } => a.distance(p).max(b.distance(p)),
+            SdfNode::Subtraction { a, b } => a.distance(p).max(-b.distance(p)),
+            SdfNode::SmoothUnion { a, b, k } => smooth_min(a.distance(p), b.distance(p), *k),
+            SdfNode::Repeat { period, child } => {
+                let q = Vec3::new(
+                    repeat_axis(p.x, period.x),
+                    repeat_axis(p.y, period.y),
+                    repeat_axis(p.z, period.z),
+                );
+                child.distance(q)
+            }
+            SdfNode::Twist { amount, child } => {
+                let (s, c) = (amount * p.y).sin_cos();
+                let q = Vec3::new((c * p.x) - (s * p.z), p.y, (s * p.x) + (c * p.z));
+                child.distance(q)
+            }
+        }
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn normal(&self, p: Vec3) -> Vec3 {
+        // Tetrahedral central differences of the distance field gradient
+        const H: f32 = 0.0005;
+        let k0 = Vec3::new(1.0, -1.0, -1.0);
+        let k1 = Vec3::new(-1.0, -1.0, 1.0);
+        let k2 = Vec3::new(-1.0, 1.0, -1.0);
+        let k3 = Vec3::new(1.0, 1.0, 1.0);
+        let gradient = (k0 * self.distance(p + (k0 * H)))
+            + (k1 * self.distance(p + (k1 * H)))
+            + (k2 * self.distance(p + (k2 * H)))
+            + (k3 * self.distance(p + (k3 * H)));
+        Vec3::normalize(gradient)
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn bounds(&self) -> Option<Aabb> {
+        match self {
+            SdfNode::Sphere { centre, radius } => Some(Aabb::around(*centre, Vec3::ONE * *radius)),
+            SdfNode::Box { centre, half_size } | SdfNode::RoundBox {
+                centre, half_size, ..
+            } => Some(Aabb::around(*centre, *half_size)),
+            SdfNode::Torus {
+                centre,
+                major_radius,
+                minor_radius,
+            } => {
+                let radius = major_radius + minor_radius;
+                Some(Aabb::around(*centre, Vec3::new(radius, *minor_radius, radius)))
+            }
+            SdfNode::Capsule { a, b, radius } => {
+                let mut bounds = Aabb::around(*a, Vec3::ONE * *radius);
+                bounds.merge(&Aabb::around(*b, Vec3::ONE * *radius));
+                Some(bounds)
+            }
+            SdfNode::Union { children } => {
+                let mut bounds = Aabb::EMPTY;
+                for child in children {
+                    bounds.merge(&child.bounds()?);
+                }
+                Some(bounds)
+            }
+            SdfNode::Intersection { a, .. } | SdfNode::Subtraction { a, .. } => a.bounds(),
+            SdfNode::SmoothUnion { a, b, k } => {
+                // Blending can bulge the surface out by up to k / 4
+                let mut bounds = a.bounds()?;
+                bounds.merge(&b.bounds()?);
+                Some(Aabb::around(bounds.centroid(), bounds.half_size() + (Vec3::ONE * (k * 0.25))))
+            }
+            SdfNode::Repeat { .. } => None,
+            SdfNode::Twist { child, .. } => {
+                // Twisting sweeps the child around the y axis
+                let bounds = child.bounds()?;
+                let corner = Vec3::max(Vec3::abs(bounds.min), Vec3::abs(bounds.max));
+                let radius = ((corner.x * corner.x) + (corner.z * corner.z)).sqrt();
+                Some(Aabb::new(
+                    Vec3::new(-radius, bounds.min.y, -radius),
+                    Vec3::new(radius, bounds.max.y, radius),
+                ))
+            }
+        }
+    }
+
+    // -------------------------------------------------------------------------------------
+
+    pub fn step_scale(&self) -> f32 {
+        // Twisting stretches space so distances are no longer exact, under-step to compensate
+        match self {
+            SdfNode::Union { children } => children
+                .iter()
+                .map(|child| child.step_scale())
+                .fold(1.0, f32::min),
+            SdfNode::Intersection { a, b }
+            | SdfNode::Subtraction { a, b }
+            | SdfNode::SmoothUnion { a, b, .. } => a.step_scale().min(b.step_scale()),
+            SdfNode::Repeat { child, .. } => child.step_scale(),
+            SdfNode::Twist { child, .. } => child.step_scale() * 0.5,
+            _ => 1.0,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// SdfObject Type (a distance field shape placed in the scene)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SdfObject {
+    pub shape: SdfNode,
+    pub material: MaterialRef,
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn sdf_box(p: Vec3, half_size: Vec3) -> f32 {
+    let q = Vec3::abs(p) - half_size;
+    let outside = Vec3::length(Vec3::max(q, Vec3::ZERO));
+    let inside = q.x.max(q.y).max(q.z).min(0.0);
+    outside + inside
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn smooth_min(a: f32, b: f32, k: f32) -> f32 {
+    // Polynomial smooth minimum
+    if k <= 0.0 {
+        return a.min(b);
+    }
+    let h = (k - (a - b).abs()).max(0.0) / k;
+    a.min(b) - (h * h * k * 0.25)
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn repeat_axis(x: f32, period: f32) -> f32 {
+    // Zero period disables repetition along this axis
+    if period <= 0.0 {
+        return x;
+    }
+    x - (period * (x / period).round())
+}
+
+// -----------------------------------------------------------------------------------------
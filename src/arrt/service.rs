@@ -0,0 +1,719 @@
+// -----------------------------------------------------------------------------------------
+// Render service, a local HTTP/JSON API which queues render jobs and serves their images
+// -----------------------------------------------------------------------------------------
+
+use super::camera::Camera;
+use super::framebuffer::Framebuffer;
+use super::material::MaterialBank;
+use super::misc;
+use super::quality::{QualityPreset, QualityPresetBank};
+use super::renderer::{Progress, RenderHandle, Renderer};
+use super::scene::Scene;
+use super::vector::Vec3;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::path::{Component, Path};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+// -----------------------------------------------------------------------------------------
+
+const POLL_INTERVAL_MS: u64 = 100;
+const KEEP_ALIVE_INTERVAL_S: u64 = 15;
+const MAX_REQUEST_SIZE: u64 = 64 * 1024 * 1024;
+const AOV_SAMPLES_PER_PIXEL: usize = 16;
+
+// Largest image and sample count a job can ask for
+const MAX_IMAGE_SIZE: u32 = 8192;
+const MAX_SAMPLES_PER_PIXEL: usize = 1 << 16;
+const EVENT_STREAM_HEADER: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+    Cache-Control: no-cache\r\nTransfer-Encoding: chunked\r\n\r\n";
+
+// -----------------------------------------------------------------------------------------
+// Defaults Type (used for whatever a job request leaves out)
+pub struct Defaults {
+    pub quality_presets: QualityPresetBank,
+    pub quality: String,
+    pub materials_file: String,
+    pub seed: u64,
+
+    // Fields overridden in named presets (a preset written out in a request is used as is)
+    pub overrides: Map<String, Value>,
+    pub camera: Camera,
+    pub thread_count: usize,
+}
+
+// -----------------------------------------------------------------------------------------
+// JobRequest Type (body of POST /jobs, scene and materials are written as in their files)
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct JobRequest {
+    scene: Value,
+    #[serde(default)]
+    materials: Option<Value>,
+    #[serde(default)]
+    quality: Option<QualityRequest>,
+    #[serde(default)]
+    camera: Option<CameraRequest>,
+    #[serde(default)]
+    seed: Option<u64>,
+    #[serde(default)]
+    spectral: bool,
+    #[serde(default)]
+    aovs: Vec<Aov>,
+}
+
+// -----------------------------------------------------------------------------------------
+// QualityRequest Type (a preset by name, or written out in full)
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum QualityRequest {
+    Name(String),
+    Preset(QualityPreset),
+}
+
+// -----------------------------------------------------------------------------------------
+// CameraRequest Type
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct CameraRequest {
+    position: Vec3,
+    lookat: Vec3,
+    #[serde(default)]
+    fov: Option<f32>,
+}
+
+// -----------------------------------------------------------------------------------------
+// Aov Type (extra images of a job, rendered once its image is finished)
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Aov {
+    Normal,
+    Samples,
+}
+
+// -----------------------------------------------------------------------------------------
+// Status Type
+#[derive(Serialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Queued,
+    Rendering,
+    Finished,
+    Cancelled,
+    Failed,
+}
+
+// -----------------------------------------------------------------------------------------
+// JobStatus Type (what the API reports about a job)
+#[derive(Serialize, Debug)]
+struct JobStatus {
+    id: usize,
+    status: Status,
+    progress: Progress,
+    aovs: Vec<Aov>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+// -----------------------------------------------------------------------------------------
+// Job Type (images are kept encoded, update_count changes whenever anything else does)
+struct Job {
+    id: usize,
+    status: Status,
+    progress: Progress,
+    aovs: Vec<Aov>,
+    render: Option<(Renderer, Scene)>,
+    cancelled: bool,
+    preview: Option<Arc<Vec<u8>>>,
+    image: Option<Arc<Vec<u8>>>,
+    aov_images: BTreeMap<Aov, Arc<Vec<u8>>>,
+    error: Option<String>,
+    update_count: usize,
+}
+
+// -----------------------------------------------------------------------------------------
+// Job Members
+impl Job {
+    fn status(&self) -> JobStatus {
+        JobStatus {
+            id: self.id,
+            status: self.status,
+            progress: self.progress,
+            aovs: self.aovs.clone(),
+            error: self.error.clone(),
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        matches!(
+            self.status,
+            Status::Finished | Status::Cancelled | Status::Failed
+        )
+    }
+
+    fn unfinished(&self) -> (u16, String) {
+        match &self.error {
+            Some(error) => (409, error.clone()),
+            None => (409, format!("ERROR: Job {} isn't finished", self.id)),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Jobs Type (queue holds ids of jobs waiting to render, oldest first)
+struct Jobs {
+    next_id: usize,
+    jobs: BTreeMap<usize, Job>,
+    queue: VecDeque<usize>,
+}
+
+// -----------------------------------------------------------------------------------------
+// Service Type (changed is notified whenever a job is added or updated)
+pub(crate) struct Service {
+    defaults: Defaults,
+    jobs: Mutex<Jobs>,
+    changed: Condvar,
+}
+
+// -----------------------------------------------------------------------------------------
+// Reply Type (errors are sent as JSON too)
+pub(crate) enum Reply {
+    Json(u16, String),
+    Png(Arc<Vec<u8>>),
+}
+
+type Handled = Result<Reply, (u16, String)>;
+
+// -----------------------------------------------------------------------------------------
+
+pub fn serve(listener: TcpListener, defaults: Defaults) -> Result<(), String> {
+    Arc::new(Service::new(defaults)).serve(listener)
+}
+
+// -----------------------------------------------------------------------------------------
+// Service Constructor
+impl Service {
+    pub(crate) fn new(defaults: Defaults) -> Service {
+        Service {
+            defaults,
+            jobs: Mutex::new(Jobs {
+                next_id: 1,
+                jobs: BTreeMap::new(),
+                queue: VecDeque::new(),
+            }),
+            changed: Condvar::new(),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Service Members | Requests
+impl Service {
+    pub(crate) fn serve(self: Arc<Self>, listener: TcpListener) -> Result<(), String> {
+        let server = Server::from_listener(listener, None)
+            .map_err(|error| format!("ERROR: Could not start render service ({})", error))?;
+
+        // Jobs render one at a time (each uses every thread), in the order they were queued
+        let runner = self.clone();
+        thread::spawn(move || runner.run_jobs());
+
+        // Requests are handled on their own threads, event streams stay open while a job renders
+        for request in server.incoming_requests() {
+            let service = self.clone();
+            thread::spawn(move || service.handle(request));
+        }
+        Ok(())
+    }
+
+    fn handle(&self, mut request: Request) {
+        let url = request.url().to_string();
+        let path: Vec<&str> = url
+            .split('?')
+            .next()
+            .unwrap()
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .collect();
+        let method = request.method().clone();
+        let handled = match (&method, path.as_slice()) {
+            (Method::Get, ["jobs"]) => self.list(),
+            (Method::Post, ["jobs"]) => self.submit(&mut request),
+            (Method::Get, ["jobs", id]) => self.job(id, |job| Ok(status_reply(200, job))),
+            (Method::Delete, ["jobs", id]) => self.cancel(id),
+            (Method::Get, ["jobs", id, "image"]) => self.job(id, |job| match &job.image {
+                Some(image) => Ok(Reply::Png(image.clone())),
+                None => Err(job.unfinished()),
+            }),
+            (Method::Get, ["jobs", id, "preview"]) => self.job(id, |job| match &job.preview {
+                Some(preview) => Ok(Reply::Png(preview.clone())),
+                None => Err((409, format!("ERROR: Job {} hasn't started", job.id))),
+            }),
+            (Method::Get, ["jobs", id, "aovs", name]) => self.job(id, |job| {
+                let aov = parse_aov(name)?;
+                match job.aov_images.get(&aov) {
+                    Some(image) => Ok(Reply::Png(image.clone())),
+                    None if job.aovs.contains(&aov) => Err(job.unfinished()),
+                    None => Err((404, format!("ERROR: Job {} has no '{}' AOV", job.id, name))),
+                }
+            }),
+            (Method::Get, ["jobs", id, "events"]) => match self.job(id, |job| Ok(job.id)) {
+                Ok(id) => {
+                    // Failures mean the client went away
+                    let _ = self.stream_events(request.into_writer(), id);
+                    return;
+                }
+                Err(error) => Err(error),
+            },
+            _ => Err((404, format!("ERROR: Unknown request: {} {}", method, url))),
+        };
+        let (status_code, content_type, body) = match handled {
+            Ok(Reply::Json(status_code, json)) => (status_code, "application/json", json.into()),
+            Ok(Reply::Png(png)) => (200, "image/png", png.to_vec()),
+            Err((status_code, error)) => {
+                let json = serde_json::json!({ "error": error }).to_string();
+                (status_code, "application/json", json.into())
+            }
+        };
+        let header = Header::from_bytes("Content-Type", content_type).unwrap();
+        let response = Response::from_data(body)
+            .with_status_code(status_code)
+            .with_header(header);
+        let _ = request.respond(response);
+    }
+
+    fn list(&self) -> Handled {
+        let jobs = self.jobs.lock().unwrap();
+        let statuses: Vec<JobStatus> = jobs.jobs.values().map(Job::status).collect();
+        Ok(Reply::Json(200, serde_json::to_string(&statuses).unwrap()))
+    }
+
+    fn submit(&self, request: &mut Request) -> Handled {
+        // Scene and materials are loaded straight away, so mistakes are reported here
+        let mut body = String::new();
+        request
+            .as_reader()
+            .take(MAX_REQUEST_SIZE)
+            .read_to_string(&mut body)
+            .map_err(|error| (400, format!("ERROR: Could not read request ({})", error)))?;
+        let job_request: JobRequest = serde_json::from_str(&body)
+            .map_err(|error| (400, format!("ERROR: Invalid job request: {}", error)))?;
+        let aovs = job_request.aovs.clone();
+        let render = self.setup(job_request).map_err(|error| (400, error))?;
+        let (_, reply) = self.queue(render, aovs);
+        Ok(reply)
+    }
+
+    pub(crate) fn queue(&self, render: (Renderer, Scene), aovs: Vec<Aov>) -> (usize, Reply) {
+        // Jobs are queued as they were set up (tests queue renderers directly)
+        let mut jobs = self.jobs.lock().unwrap();
+        let id = jobs.next_id;
+        jobs.next_id += 1;
+        let job = Job {
+            id,
+            status: Status::Queued,
+            progress: Progress::default(),
+            aovs,
+            render: Some(render),
+            cancelled: false,
+            preview: None,
+            image: None,
+            aov_images: BTreeMap::new(),
+            error: None,
+            update_count: 0,
+        };
+        let reply = status_reply(201, &job);
+        jobs.jobs.insert(id, job);
+        jobs.queue.push_back(id);
+        self.changed.notify_all();
+        (id, reply)
+    }
+
+    fn setup(&self, request: JobRequest) -> Result<(Renderer, Scene), String> {
+        // Relative paths (includes and images) are from the working directory, and can't leave it
+        let defaults = &self.defaults;
+        check_paths(&request)?;
+        let materials = match &request.materials {
+            Some(materials) => {
+                MaterialBank::try_load_from_str("request", &materials.to_string(), Path::new(""))?
+            }
+            None => MaterialBank::try_load_from_file(&defaults.materials_file)?,
+        };
+        let scene = Scene::try_load_from_str("request", &request.scene.to_string(), &materials)?;
+        let quality = match request.quality {
+            Some(QualityRequest::Name(name)) => defaults
+                .quality_presets
+                .try_get(&name)?
+                .with_overrides(&defaults.overrides)?,
+            Some(QualityRequest::Preset(preset)) => preset,
+            None => defaults
+                .quality_presets
+                .try_get(&defaults.quality)?
+                .with_overrides(&defaults.overrides)?,
+        };
+        check_quality(&quality)?;
+        let camera = match request.camera {
+            Some(camera) => Camera::new(
+                camera.position,
+                camera.lookat,
+                camera.fov.unwrap_or(defaults.camera.fov),
+            ),
+            None => defaults.camera.clone(),
+        };
+        let mut renderer = Renderer::new(quality, materials, camera);
+        renderer.seed = request.seed.unwrap_or(defaults.seed);
+        renderer.spectral = request.spectral;
+        renderer.thread_count = defaults.thread_count;
+        Ok((renderer, scene))
+    }
+
+    fn cancel(&self, id: &str) -> Handled {
+        // Stops a queued or rendering job, deleting a job that's done frees it
+        let id = parse_id(id)?;
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = jobs.jobs.get_mut(&id).ok_or_else(|| unknown_job(id))?;
+        if job.is_done() {
+            let reply = status_reply(200, job);
+            jobs.jobs.remove(&id);
+            self.changed.notify_all();
+            return Ok(reply);
+        }
+        job.cancelled = true;
+        if job.status == Status::Queued {
+            job.render = None;
+            job.status = Status::Cancelled;
+            job.update_count += 1;
+        }
+        let reply = status_reply(200, job);
+        self.changed.notify_all();
+        Ok(reply)
+    }
+
+    fn job<T, F>(&self, id: &str, f: F) -> Result<T, (u16, String)>
+    where
+        F: FnOnce(&Job) -> Result<T, (u16, String)>,
+    {
+        let id = parse_id(id)?;
+        let jobs = self.jobs.lock().unwrap();
+        jobs.jobs
+            .get(&id)
+            .ok_or_else(|| unknown_job(id))
+            .and_then(f)
+    }
+
+    fn stream_events(&self, mut writer: Box<dyn Write + Send>, id: usize) -> io::Result<()> {
+        // Server-sent events, a status event for every update and a preview event whenever
+        // the preview changes, until the job is done (then an end event)
+        writer.write_all(EVENT_STREAM_HEADER)?;
+        writer.flush()?;
+        let keep_alive_interval = Duration::from_secs(KEEP_ALIVE_INTERVAL_S);
+        let (mut update_count, mut preview): (_, Option<Arc<Vec<u8>>>) = (None, None);
+        loop {
+            let jobs = self.jobs.lock().unwrap();
+            let job = match jobs.jobs.get(&id) {
+                Some(job) => job,
+                None => break,
+            };
+            if update_count == Some(job.update_count) {
+                // Comments keep idle connections open (and notice closed ones)
+                let (jobs, timeout) = self
+                    .changed
+                    .wait_timeout(jobs, keep_alive_interval)
+                    .unwrap();
+                drop(jobs);
+                if timeout.timed_out() {
+                    write_chunk(&mut writer, b": keep-alive\n\n")?;
+                }
+                continue;
+            }
+            update_count = Some(job.update_count);
+            let status = serde_json::to_string(&job.status()).unwrap();
+            let done = job.is_done();
+            let new_preview = job.preview.clone().filter(|job_preview| {
+                !preview
+                    .as_ref()
+                    .is_some_and(|sent| Arc::ptr_eq(job_preview, sent))
+            });
+            drop(jobs);
+
+            // Previews are PNG data URLs (usable as an image source)
+            let event = format!("event: status\ndata: {}\n\n", status);
+            write_chunk(&mut writer, event.as_bytes())?;
+            if let Some(new_preview) = new_preview {
+                let png = base64::engine::general_purpose::STANDARD.encode(new_preview.as_slice());
+                let event = format!("event: preview\ndata: data:image/png;base64,{}\n\n", png);
+                write_chunk(&mut writer, event.as_bytes())?;
+                preview = Some(new_preview);
+            }
+            if done {
+                break;
+            }
+        }
+
+        // Clients would otherwise reconnect, the empty chunk ends the response
+        write_chunk(&mut writer, b"event: end\ndata: {}\n\n")?;
+        write_chunk(&mut writer, b"")
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Service Members | Rendering
+impl Service {
+    fn run_jobs(&self) {
+        loop {
+            // Wait for the next queued job (cancelled jobs are skipped)
+            let (id, renderer, scene, aovs) = {
+                let mut jobs = self.jobs.lock().unwrap();
+                loop {
+                    let id = match jobs.queue.pop_front() {
+                        Some(id) => id,
+                        None => {
+                            jobs = self.changed.wait(jobs).unwrap();
+                            continue;
+                        }
+                    };
+                    if let Some(job) = jobs.jobs.get_mut(&id) {
+                        if let Some((renderer, scene)) = job.render.take() {
+                            job.status = Status::Rendering;
+                            job.update_count += 1;
+                            break (id, renderer, scene, job.aovs.clone());
+                        }
+                    }
+                }
+            };
+            self.changed.notify_all();
+
+            // A job that fails (or panics) is marked failed, the next job still renders
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                self.render_job(id, &renderer, &scene, &aovs)
+            }));
+            let error = match result {
+                Ok(Ok(())) => continue,
+                Ok(Err(error)) => error,
+                Err(payload) => misc::panic_message(payload),
+            };
+            self.update(id, |job| {
+                job.status = Status::Failed;
+                job.error = Some(error);
+            });
+        }
+    }
+
+    fn render_job(
+        &self,
+        id: usize,
+        renderer: &Renderer,
+        scene: &Scene,
+        aovs: &[Aov],
+    ) -> Result<(), String> {
+        // Progress and previews are updated after each pass (or adaptive round)
+        let render = renderer.render_async(scene);
+        let mut last_progress = None;
+        while !render.is_finished() {
+            thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+            if self.inspect(id, |job| job.cancelled) {
+                render.cancel();
+            }
+            let progress = render.progress();
+            let key = (progress.samples_per_pixel, progress.converged_pixel_count);
+            if last_progress != Some(key) {
+                last_progress = Some(key);
+                let preview = match render.preview().encode_png() {
+                    Ok(preview) => Arc::new(preview),
+                    Err(error) => {
+                        render.cancel();
+                        return Err(error);
+                    }
+                };
+                self.update(id, |job| {
+                    job.progress = progress;
+                    job.preview = Some(preview);
+                });
+            }
+        }
+
+        // A render that panicked fails the job
+        if let Some(error) = render.error() {
+            return Err(error);
+        }
+
+        // Cancelled jobs keep their last preview, finished jobs get their image and AOVs
+        let cancelled = self.inspect(id, |job| job.cancelled);
+        let mut aov_images = BTreeMap::new();
+        if !cancelled {
+            for &aov in aovs {
+                let image = render_aov(aov, renderer, scene, &render);
+                aov_images.insert(aov, Arc::new(image.encode_png()?));
+            }
+        }
+        let progress = render.progress();
+        let image = Arc::new(render.wait()?.encode_png()?);
+        self.update(id, |job| {
+            job.progress = progress;
+            if cancelled {
+                job.status = Status::Cancelled;
+            } else {
+                job.preview = Some(image.clone());
+                job.image = Some(image);
+                job.aov_images = aov_images;
+                job.status = Status::Finished;
+            }
+        });
+        Ok(())
+    }
+
+    fn inspect<F>(&self, id: usize, f: F) -> bool
+    where
+        F: FnOnce(&Job) -> bool,
+    {
+        // Jobs being rendered can't be deleted, only cancelled
+        let jobs = self.jobs.lock().unwrap();
+        f(&jobs.jobs[&id])
+    }
+
+    fn update<F>(&self, id: usize, f: F)
+    where
+        F: FnOnce(&mut Job),
+    {
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = jobs.jobs.get_mut(&id).unwrap();
+        f(job);
+        job.update_count += 1;
+        self.changed.notify_all();
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn render_aov(aov: Aov, renderer: &Renderer, scene: &Scene, render: &RenderHandle) -> Framebuffer {
+    match aov {
+        Aov::Normal => {
+            // Shading normals, with a few samples per pixel for anti-aliasing
+            let mut normals = renderer.clone();
+            normals.debug_normals = true;
+            normals.spectral = false;
+            normals.quality = QualityPreset {
+                samples_per_pixel: AOV_SAMPLES_PER_PIXEL,
+                time_limit_s: None,
+                noise_threshold: None,
+                adaptive: false,
+                ..renderer.quality.clone()
+            };
+            normals.render(scene)
+        }
+        Aov::Samples => render.samples(),
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn check_quality(quality: &QualityPreset) -> Result<(), String> {
+    // Written out presets come from the request, so nothing about them can be assumed
+    let valid_size = |size: u32| (1..=MAX_IMAGE_SIZE).contains(&size);
+    if !valid_size(quality.image_width) || !valid_size(quality.image_height) {
+        return Err(format!(
+            "ERROR: Invalid image size {}x{} (each side must be 1 to {})",
+            quality.image_width, quality.image_height, MAX_IMAGE_SIZE
+        ));
+    }
+    if !(1..=MAX_SAMPLES_PER_PIXEL).contains(&quality.samples_per_pixel) {
+        return Err(format!(
+            "ERROR: Invalid samples per pixel {} (must be 1 to {})",
+            quality.samples_per_pixel, MAX_SAMPLES_PER_PIXEL
+        ));
+    }
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn check_paths(request: &JobRequest) -> Result<(), String> {
+    // Includes of the scene and images of its materials, so a request can only name files below
+    // the working directory (files they include are the server's own)
+    let mut paths = Vec::new();
+    if let Some(Value::Array(includes)) = request.scene.get("include") {
+        paths.extend(includes.iter().filter_map(Value::as_str));
+    }
+    if let Some(materials) = &request.materials {
+        image_paths(materials, &mut paths);
+    }
+    for path in paths {
+        let below = Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if !below {
+            return Err(format!(
+                "ERROR: Invalid path '{}' (must be relative, without '..')",
+                path
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn image_paths<'a>(value: &'a Value, paths: &mut Vec<&'a str>) {
+    // Image textures can be nested anywhere in a material
+    match value {
+        Value::Object(fields) => {
+            for (key, field) in fields {
+                match (key.as_str(), field) {
+                    ("image", Value::String(path)) => paths.push(path),
+                    _ => image_paths(field, paths),
+                }
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                image_paths(value, paths);
+            }
+        }
+        _ => (),
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn write_chunk(writer: &mut dyn Write, bytes: &[u8]) -> io::Result<()> {
+    // Sent straight away (chunked transfer encoding, written by hand as events are streamed)
+    write!(writer, "{:x}\r\n", bytes.len())?;
+    writer.write_all(bytes)?;
+    writer.write_all(b"\r\n")?;
+    writer.flush()
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn status_reply(status_code: u16, job: &Job) -> Reply {
+    Reply::Json(status_code, serde_json::to_string(&job.status()).unwrap())
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn parse_id(id: &str) -> Result<usize, (u16, String)> {
+    id.parse()
+        .map_err(|_| (404, format!("ERROR: Invalid job id: '{}'", id)))
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn parse_aov(name: &str) -> Result<Aov, (u16, String)> {
+    serde_json::from_value(Value::String(String::from(name)))
+        .map_err(|_| (404, format!("ERROR: Unknown AOV: '{}'", name)))
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn unknown_job(id: usize) -> (u16, String) {
+    (404, format!("ERROR: Unknown job: {}", id))
+}
+
+// -----------------------------------------------------------------------------------------
@@ -0,0 +1,124 @@
+// -----------------------------------------------------------------------------------------
+// Spectral rendering (hero wavelength sampling, RGB upsampling and conversion to sRGB)
+// -----------------------------------------------------------------------------------------
+
+use super::random::Pcg32;
+use super::vector::Vec3;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Visible range sampled by spectral paths (nanometres)
+pub const LAMBDA_MIN: f32 = 380.0;
+pub const LAMBDA_MAX: f32 = 720.0;
+
+// Sodium d-line, where refractive indices are usually quoted (used by RGB renders)
+pub const LAMBDA_D: f32 = 587.6;
+
+// Integral of the luminance matching function over the sampled range
+const CIE_Y_INTEGRAL: f32 = 106.86;
+
+// sRGB of a constant unit spectrum, divided out so white surfaces stay white
+const WHITE: Vec3 = Vec3 {
+    x: 1.2012,
+    y: 0.9502,
+    z: 0.9082,
+};
+
+// -----------------------------------------------------------------------------------------
+// Dispersion Type (wavelength dependent refractive index, wavelengths in micrometres)
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Dispersion {
+    Cauchy { a: f32, b: f32 },
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+// -----------------------------------------------------------------------------------------
+// Dispersion Members
+impl Dispersion {
+    pub fn ior(&self, lambda: f32) -> f32 {
+        let micrometres = lambda * 0.001;
+        let l2 = micrometres * micrometres;
+        match self {
+            Dispersion::Cauchy { a, b } => a + (b / l2),
+            Dispersion::Sellmeier { b, c } => {
+                let n2 = 1.0 + (0..3).map(|i| (b[i] * l2) / (l2 - c[i])).sum::<f32>();
+                n2.max(1.0).sqrt()
+            }
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn sample_wavelengths(rng: &mut Pcg32) -> Vec3 {
+    // Hero wavelength plus two more at equal spacing, wrapping within the visible range
+    let range = LAMBDA_MAX - LAMBDA_MIN;
+    let hero = rng.gen::<f32>() * range;
+    let rotate = |offset: f32| LAMBDA_MIN + ((hero + (range * offset)) % range);
+    Vec3::new(rotate(0.0), rotate(1.0 / 3.0), rotate(2.0 / 3.0))
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn from_rgb(rgb: Vec3, wavelengths: Vec3) -> Vec3 {
+    // Smooth blue, green and red bands which sum to one (so grey stays flat)
+    let sample = |lambda: f32| {
+        let blue = 1.0 - sigmoid(lambda, 490.0, 12.0);
+        let red = sigmoid(lambda, 590.0, 12.0);
+        let green = 1.0 - blue - red;
+        (rgb.x * red) + (rgb.y * green) + (rgb.z * blue)
+    };
+    Vec3::new(
+        sample(wavelengths.x),
+        sample(wavelengths.y),
+        sample(wavelengths.z),
+    )
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn to_xyz(values: Vec3, wavelengths: Vec3) -> Vec3 {
+    // Each wavelength is uniform over the range, so the estimate averages value * cmf / pdf
+    let range = LAMBDA_MAX - LAMBDA_MIN;
+    let xyz = (cie_xyz(wavelengths.x) * values.x)
+        + (cie_xyz(wavelengths.y) * values.y)
+        + (cie_xyz(wavelengths.z) * values.z);
+    xyz * (range / (3.0 * CIE_Y_INTEGRAL))
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn xyz_to_rgb(xyz: Vec3) -> Vec3 {
+    // Linear sRGB (D65)
+    let rgb = Vec3::new(
+        (3.2406 * xyz.x) - (1.5372 * xyz.y) - (0.4986 * xyz.z),
+        (-0.9689 * xyz.x) + (1.8758 * xyz.y) + (0.0415 * xyz.z),
+        (0.0557 * xyz.x) - (0.2040 * xyz.y) + (1.0570 * xyz.z),
+    );
+    Vec3::max(rgb / WHITE, Vec3::ZERO)
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn cie_xyz(lambda: f32) -> Vec3 {
+    // Multi-lobe fit to the CIE 1931 2 degree observer (Wyman et al. 2013)
+    let lobe = |mean: f32, below: f32, above: f32| {
+        let t = (lambda - mean) / if lambda < mean { below } else { above };
+        (-0.5 * t * t).exp()
+    };
+    Vec3::new(
+        (1.056 * lobe(599.8, 37.9, 31.0)) + (0.362 * lobe(442.0, 16.0, 26.7))
+            - (0.065 * lobe(501.1, 20.4, 26.2)),
+        (0.821 * lobe(568.8, 46.9, 40.5)) + (0.286 * lobe(530.9, 16.3, 31.1)),
+        (1.217 * lobe(437.0, 11.8, 36.0)) + (0.681 * lobe(459.0, 26.0, 13.8)),
+    )
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn sigmoid(x: f32, centre: f32, width: f32) -> f32 {
+    1.0 / (1.0 + (-(x - centre) / width).exp())
+}
+
+// -----------------------------------------------------------------------------------------
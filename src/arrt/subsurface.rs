@@ -0,0 +1,159 @@
+// -----------------------------------------------------------------------------------------
+// Random walk subsurface scattering (skin, wax, marble) beneath a smooth dielectric boundary
+// -----------------------------------------------------------------------------------------
+
+use super::bsdf;
+use super::medium;
+use super::random::Pcg32;
+use super::ray::Ray;
+use super::ray::RayHitResult;
+use super::vector::Vec3;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Walks which haven't left the surface after this many scattering events are dropped
+const MAX_STEPS: u32 = 1024;
+const EPSILON: f32 = 0.001;
+
+// -----------------------------------------------------------------------------------------
+// Subsurface Type (colour is the overall look, mean free path is in scene units per channel)
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Subsurface {
+    #[serde(default = "default_colour")]
+    pub colour: Vec3,
+    #[serde(default = "default_mean_free_path")]
+    pub mean_free_path: Vec3,
+    #[serde(default)]
+    pub anisotropy: f32,
+    #[serde(default = "default_ior")]
+    pub ior: f32,
+}
+
+// -----------------------------------------------------------------------------------------
+// Subsurface Members
+impl Subsurface {
+    pub fn scatter(
+        &self,
+        ray: &Ray,
+        hit: &RayHitResult,
+        intersect: impl Fn(&Ray) -> RayHitResult,
+        rng: &mut Pcg32,
+    ) -> Option<(Ray, Vec3)> {
+        // Reflect off the boundary or refract into the medium, chosen by Fresnel
+        let normal = facing(hit.normal, -ray.direction);
+        let cos_theta = Vec3::dot(-ray.direction, normal);
+        if rng.gen::<f32>() < bsdf::fresnel_dielectric(cos_theta, self.ior) {
+            let origin = hit.position + (normal * EPSILON);
+            return Some((
+                Ray::new(origin, Vec3::reflect(ray.direction, normal)),
+                Vec3::ONE,
+            ));
+        }
+        let direction = bsdf::refract(-ray.direction, normal, self.ior)?;
+        self.walk(hit.position - (normal * EPSILON), direction, intersect, rng)
+    }
+
+    fn walk(
+        &self,
+        position: Vec3,
+        direction: Vec3,
+        intersect: impl Fn(&Ray) -> RayHitResult,
+        rng: &mut Pcg32,
+    ) -> Option<(Ray, Vec3)> {
+        let extinction = Vec3::ONE / Vec3::max(self.mean_free_path, Vec3::ONE * 1e-4);
+        let albedo = single_scattering_albedo(self.colour);
+        let (mut position, mut direction, mut weight) = (position, direction, Vec3::ONE);
+        for _ in 0..MAX_STEPS {
+            // Distances are sampled from one channel, picked in proportion to its current weight
+            let total = weight.x + weight.y + weight.z;
+            if total <= 0.0 {
+                return None;
+            }
+            let probabilities = weight / total;
+            let u = rng.gen::<f32>();
+            let channel = if u < probabilities.x {
+                0
+            } else if u < probabilities.x + probabilities.y {
+                1
+            } else {
+                2
+            };
+            let distance = -(1.0 - rng.gen::<f32>()).ln() / Vec3::axis(extinction, channel);
+            let boundary = intersect(&Ray::new(position, direction));
+            if !boundary.hit {
+                return None; // open surface, nothing to exit through
+            }
+
+            // Scatter inside the medium
+            if distance < boundary.distance {
+                let transmittance = exp(-extinction * distance);
+                let pdf = Vec3::dot(probabilities, extinction * transmittance);
+                weight *= albedo * extinction * transmittance / pdf;
+                position += direction * distance;
+                direction = medium::sample_henyey_greenstein(direction, self.anisotropy, rng);
+                continue;
+            }
+
+            // Reached the boundary, leave or reflect back inside
+            let transmittance = exp(-extinction * boundary.distance);
+            weight *= transmittance / Vec3::dot(probabilities, transmittance);
+            let normal = facing(boundary.normal, direction);
+            let cos_theta = Vec3::dot(direction, normal);
+            let fresnel = bsdf::fresnel_dielectric(cos_theta, 1.0 / self.ior);
+            if rng.gen::<f32>() >= fresnel {
+                if let Some(exit) = bsdf::refract(-direction, -normal, 1.0 / self.ior) {
+                    let origin = boundary.position + (normal * EPSILON);
+                    return Some((Ray::new(origin, exit), weight));
+                }
+            }
+            position = boundary.position - (normal * EPSILON);
+            direction = Vec3::reflect(direction, normal);
+        }
+        None
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn single_scattering_albedo(colour: Vec3) -> Vec3 {
+    // Inverts the multiple scattering albedo so colour matches the look (Chiang et al. 2016)
+    let invert = |a: f32| {
+        let a = a.clamp(0.0, 0.999);
+        let s = 4.09712 + (4.20863 * a) - (9.59217 + (41.6808 * a) + (17.7126 * a * a)).sqrt();
+        1.0 - (s * s)
+    };
+    Vec3::new(invert(colour.x), invert(colour.y), invert(colour.z))
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn facing(normal: Vec3, direction: Vec3) -> Vec3 {
+    if Vec3::dot(normal, direction) < 0.0 {
+        -normal
+    } else {
+        normal
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn exp(v: Vec3) -> Vec3 {
+    Vec3::new(v.x.exp(), v.y.exp(), v.z.exp())
+}
+
+// -----------------------------------------------------------------------------------------
+// Serde defaults
+fn default_colour() -> Vec3 {
+    Vec3::new(0.8, 0.8, 0.8)
+}
+
+fn default_mean_free_path() -> Vec3 {
+    Vec3::new(0.1, 0.1, 0.1)
+}
+
+fn default_ior() -> f32 {
+    1.4
+}
+
+// -----------------------------------------------------------------------------------------
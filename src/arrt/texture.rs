@@ -0,0 +1,334 @@
+// -----------------------------------------------------------------------------------------
+
+use super::mipmap::FilterMode;
+use super::mipmap::MipMap;
+use super::mipmap::WrapMode;
+use super::noise;
+use super::ray::RayHitResult;
+use super::vector::Vec2;
+use super::vector::Vec3;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// -----------------------------------------------------------------------------------------
+// Types
+pub type ImageCache = HashMap<PathBuf, Arc<MipMap>>;
+
+// -----------------------------------------------------------------------------------------
+// Texture Type (a plain value or colour, an image file, or a procedural pattern tagged by "type")
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Texture {
+    Scalar(f32),
+    Constant(Vec3),
+    Image(Box<ImageTexture>),
+    Procedural(Box<ProceduralTexture>),
+}
+
+impl Default for Texture {
+    fn default() -> Texture {
+        Texture::Constant(Vec3::BLACK)
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// ImageTexture Type (uv mapped, image path is relative to the materials file)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImageTexture {
+    pub image: String,
+    #[serde(default)]
+    pub wrap: WrapMode,
+    #[serde(default)]
+    pub filter: FilterMode,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    #[serde(skip)]
+    pub mipmap: Option<Arc<MipMap>>,
+}
+
+// -----------------------------------------------------------------------------------------
+// TextureSpace Type (coordinates a pattern is evaluated in)
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TextureSpace {
+    #[default]
+    World,
+    Object,
+    Uv,
+}
+
+// -----------------------------------------------------------------------------------------
+// NoiseType Type
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NoiseType {
+    #[default]
+    Perlin,
+    Simplex,
+    Worley,
+}
+
+// -----------------------------------------------------------------------------------------
+// ProceduralTexture Type
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProceduralTexture {
+    #[serde(default)]
+    pub space: TextureSpace,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    #[serde(flatten)]
+    pub pattern: Pattern,
+}
+
+// -----------------------------------------------------------------------------------------
+// Pattern Type (colour_a/colour_b are blended by the pattern value)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Pattern {
+    Checker {
+        even: Texture,
+        odd: Texture,
+    },
+    Noise {
+        #[serde(default)]
+        noise: NoiseType,
+        #[serde(default = "default_colour_a")]
+        colour_a: Vec3,
+        #[serde(default = "default_colour_b")]
+        colour_b: Vec3,
+    },
+    Fbm {
+        #[serde(default)]
+        noise: NoiseType,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+        #[serde(default = "default_colour_a")]
+        colour_a: Vec3,
+        #[serde(default = "default_colour_b")]
+        colour_b: Vec3,
+    },
+    Turbulence {
+        #[serde(default)]
+        noise: NoiseType,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+        #[serde(default = "default_colour_a")]
+        colour_a: Vec3,
+        #[serde(default = "default_colour_b")]
+        colour_b: Vec3,
+    },
+    Marble {
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+        #[serde(default = "default_strength")]
+        strength: f32,
+        #[serde(default = "default_colour_a")]
+        colour_a: Vec3,
+        #[serde(default = "default_colour_b")]
+        colour_b: Vec3,
+    },
+    Granite {
+        #[serde(default = "default_colour_a")]
+        colour_a: Vec3,
+        #[serde(default = "default_colour_b")]
+        colour_b: Vec3,
+    },
+    Wood {
+        #[serde(default = "default_rings")]
+        rings: f32,
+        #[serde(default = "default_strength")]
+        strength: f32,
+        #[serde(default = "default_colour_a")]
+        colour_a: Vec3,
+        #[serde(default = "default_colour_b")]
+        colour_b: Vec3,
+    },
+}
+
+// -----------------------------------------------------------------------------------------
+// Texture Members
+impl Texture {
+    pub fn sample(&self, hit: &RayHitResult) -> Vec3 {
+        match self {
+            Texture::Scalar(value) => Vec3::ONE * *value,
+            Texture::Constant(colour) => *colour,
+            Texture::Image(image) => image.sample(hit),
+            Texture::Procedural(procedural) => procedural.sample(hit),
+        }
+    }
+
+    pub fn load_images(&mut self, directory: &Path, cache: &mut ImageCache) -> Result<(), String> {
+        match self {
+            Texture::Image(image) => image.load(directory, cache),
+            Texture::Procedural(procedural) => {
+                if let Pattern::Checker { even, odd } = &mut procedural.pattern {
+                    even.load_images(directory, cache)?;
+                    odd.load_images(directory, cache)?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// ImageTexture Members
+impl ImageTexture {
+    pub fn load(&mut self, directory: &Path, cache: &mut ImageCache) -> Result<(), String> {
+        // Images shared between materials are only decoded once
+        let file = directory.join(&self.image);
+        if !cache.contains_key(&file) {
+            let mipmap = MipMap::load_from_file(&file).map_err(|error| {
+                format!(
+                    "ERROR: Could not load image file: '{}' ({})",
+                    file.display(),
+                    error
+                )
+            })?;
+            cache.insert(file.clone(), Arc::new(mipmap));
+        }
+        self.mipmap = Some(cache[&file].clone());
+        Ok(())
+    }
+
+    pub fn sample(&self, hit: &RayHitResult) -> Vec3 {
+        let mipmap = match &self.mipmap {
+            Some(mipmap) => mipmap,
+            None => return Vec3::BLACK,
+        };
+
+        // Footprint is the uv extent covered by one pixel
+        let uv = Vec2::new(hit.uv.x * self.scale, hit.uv.y * self.scale);
+        let footprint = Vec2::new(
+            hit.duv_dx.x.abs().max(hit.duv_dy.x.abs()) * self.scale,
+            hit.duv_dx.y.abs().max(hit.duv_dy.y.abs()) * self.scale,
+        );
+        mipmap.sample(uv, footprint, self.wrap, self.filter)
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// ProceduralTexture Members
+impl ProceduralTexture {
+    pub fn sample(&self, hit: &RayHitResult) -> Vec3 {
+        let p = match self.space {
+            TextureSpace::World => hit.position,
+            TextureSpace::Object => hit.local_position,
+            TextureSpace::Uv => Vec3::new(hit.uv.x, hit.uv.y, 0.0),
+        } * self.scale;
+
+        match &self.pattern {
+            Pattern::Checker { even, odd } => {
+                let parity = (p.x.floor() + p.y.floor() + p.z.floor()) as i64;
+                if parity.rem_euclid(2) == 0 {
+                    even.sample(hit)
+                } else {
+                    odd.sample(hit)
+                }
+            }
+            Pattern::Noise {
+                noise,
+                colour_a,
+                colour_b,
+            } => {
+                let t = match noise {
+                    NoiseType::Worley => noise::worley(p).0,
+                    _ => (evaluate(*noise, p) * 0.5) + 0.5,
+                };
+                Vec3::lerp(*colour_a, *colour_b, t.clamp(0.0, 1.0))
+            }
+            Pattern::Fbm {
+                noise,
+                octaves,
+                colour_a,
+                colour_b,
+            } => {
+                let t = (noise::fbm(p, *octaves, |q| evaluate(*noise, q)) * 0.5) + 0.5;
+                Vec3::lerp(*colour_a, *colour_b, t.clamp(0.0, 1.0))
+            }
+            Pattern::Turbulence {
+                noise,
+                octaves,
+                colour_a,
+                colour_b,
+            } => {
+                let t = noise::turbulence(p, *octaves, |q| evaluate(*noise, q));
+                Vec3::lerp(*colour_a, *colour_b, t.clamp(0.0, 1.0))
+            }
+            Pattern::Marble {
+                octaves,
+                strength,
+                colour_a,
+                colour_b,
+            } => {
+                // Veins are a sine wave along x, displaced by turbulence
+                let turbulence = noise::turbulence(p, *octaves, noise::perlin);
+                let t = 0.5 + (0.5 * (p.x + (strength * turbulence)).sin());
+                Vec3::lerp(*colour_a, *colour_b, t)
+            }
+            Pattern::Granite { colour_a, colour_b } => {
+                // Speckled grains from cell borders, broken up with turbulence
+                let (f1, f2) = noise::worley(p);
+                let grain = ((f2 - f1) * 2.0).min(1.0);
+                let turbulence = noise::turbulence(p * 4.0, 4, noise::perlin);
+                let t = (grain * 0.7) + (turbulence * 0.6);
+                Vec3::lerp(*colour_a, *colour_b, t.clamp(0.0, 1.0))
+            }
+            Pattern::Wood {
+                rings,
+                strength,
+                colour_a,
+                colour_b,
+            } => {
+                // Concentric rings around the y axis, wobbled by noise
+                let radius = ((p.x * p.x) + (p.z * p.z)).sqrt();
+                let wobble = strength * noise::fbm(p, 3, noise::perlin);
+                let ring = (radius * rings) + wobble;
+                let t = ring - ring.floor();
+                Vec3::lerp(*colour_a, *colour_b, t * t)
+            }
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn evaluate(noise: NoiseType, p: Vec3) -> f32 {
+    match noise {
+        NoiseType::Perlin => noise::perlin(p),
+        NoiseType::Simplex => noise::simplex(p),
+        NoiseType::Worley => (noise::worley(p).0 * 2.0) - 1.0,
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Serde defaults
+fn default_scale() -> f32 {
+    1.0
+}
+
+fn default_octaves() -> u32 {
+    5
+}
+
+fn default_strength() -> f32 {
+    5.0
+}
+
+fn default_rings() -> f32 {
+    4.0
+}
+
+fn default_colour_a() -> Vec3 {
+    Vec3::BLACK
+}
+
+fn default_colour_b() -> Vec3 {
+    Vec3::ONE
+}
+
+// -----------------------------------------------------------------------------------------
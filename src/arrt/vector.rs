@@ -1,6 +1,7 @@
 // -----------------------------------------------------------------------------------------
 
 use super::matrix::Mat4;
+use super::random::Pcg32;
 use rand::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::ops;
@@ -139,6 +140,13 @@ impl ops::DivAssign<Vec3> for Vec3 {
     }
 }
 
+impl ops::Neg for Vec3 {
+    type Output = Vec3;
+    fn neg(self) -> Vec3 {
+        Vec3::new(-self.x, -self.y, -self.z)
+    }
+}
+
 // -----------------------------------------------------------------------------------------
 // Scalar arithmetic (via operator traits)
 impl ops::Mul<f32> for Vec3 {
@@ -185,7 +193,7 @@ impl Vec3 {
         (a.x * b.x) + (a.y * b.y) + (a.z * b.z)
     }
 
-    fn length_squared(a: Vec3) -> f32 {
+    pub fn length_squared(a: Vec3) -> f32 {
         Vec3::dot(a, a)
     }
 
@@ -224,6 +232,36 @@ impl Vec3 {
     pub fn reflect(incident: Vec3, normal: Vec3) -> Vec3 {
         incident - (normal * 2.0 * Vec3::dot(normal, incident))
     }
+
+    pub fn min(a: Vec3, b: Vec3) -> Vec3 {
+        Vec3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z))
+    }
+
+    pub fn max(a: Vec3, b: Vec3) -> Vec3 {
+        Vec3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z))
+    }
+
+    pub fn abs(a: Vec3) -> Vec3 {
+        Vec3::new(a.x.abs(), a.y.abs(), a.z.abs())
+    }
+
+    pub fn axis(a: Vec3, axis: usize) -> f32 {
+        match axis {
+            0 => a.x,
+            1 => a.y,
+            _ => a.z,
+        }
+    }
+
+    pub fn orthonormal_basis(n: Vec3) -> (Vec3, Vec3) {
+        // Building an Orthonormal Basis, Revisited (Duff et al. 2017)
+        let sign = 1.0f32.copysign(n.z);
+        let a = -1.0 / (sign + n.z);
+        let b = n.x * n.y * a;
+        let tangent = Vec3::new(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x);
+        let bitangent = Vec3::new(b, sign + n.y * n.y * a, -n.y);
+        (tangent, bitangent)
+    }
 }
 
 // -----------------------------------------------------------------------------------------
@@ -234,7 +272,7 @@ impl Vec3 {
         println!("[{:.2}, {:.2}, {:.2}]", a.x, a.y, a.z);
     }
 
-    pub fn random_point_in_unit_sphere(rng: &mut StdRng) -> Vec3 {
+    pub fn random_point_in_unit_sphere(rng: &mut Pcg32) -> Vec3 {
         let mut point = Vec3::ONE;
         while Vec3::length(point) > 1.0 {
             point.x = (rng.gen::<f32>() * 2.0) - 1.0;
@@ -246,3 +284,25 @@ impl Vec3 {
 }
 
 // -----------------------------------------------------------------------------------------
+// Vec2 Type
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+// -----------------------------------------------------------------------------------------
+// Vec2 Constants
+impl Vec2 {
+    pub const ZERO: Vec2 = Vec2 { x: 0.0, y: 0.0 };
+}
+
+// -----------------------------------------------------------------------------------------
+// Vec2 Constructor
+impl Vec2 {
+    pub fn new(x: f32, y: f32) -> Vec2 {
+        Vec2 { x, y }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
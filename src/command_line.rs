@@ -0,0 +1,233 @@
+// -----------------------------------------------------------------------------------------
+
+use clap::{crate_version, App, Arg, SubCommand};
+
+// -----------------------------------------------------------------------------------------
+
+pub fn parse<'a>() -> clap::ArgMatches<'a> {
+    App::new("Ray Tracer")
+        .version(crate_version!())
+        .author("Thomas Sampson <tmsampson@gmail.com>")
+        .about("Opens the viewer when no subcommand is given")
+        .args(&global_args())
+        .args(&render_args())
+        .subcommand(
+            SubCommand::with_name("view")
+                .about("Render the scene in a window, reloading data files as they change"),
+        )
+        .subcommand(
+            SubCommand::with_name("render")
+                .about("Render the scene without a window, then save it")
+                .arg(
+                    Arg::with_name("output-file")
+                        .long("output-file")
+                        .takes_value(true)
+                        .help("Output image filename (format from the extension)")
+                        .default_value("output.png"),
+                )
+                .arg(
+                    Arg::with_name("checkpoint")
+                        .long("checkpoint")
+                        .takes_value(true)
+                        .help("Write the render state to this file every so often, and when the render stops"),
+                )
+                .arg(
+                    Arg::with_name("checkpoint-interval")
+                        .long("checkpoint-interval")
+                        .takes_value(true)
+                        .requires("checkpoint")
+                        .help("Time between checkpoints, for example 30s or 10m (default 5m)"),
+                )
+                .arg(
+                    Arg::with_name("resume")
+                        .long("resume")
+                        .takes_value(false)
+                        .requires("checkpoint")
+                        .help("Continue from the checkpoint (refused if the scene, preset or settings have changed)"),
+                )
+                .arg(
+                    Arg::with_name("listen")
+                        .long("listen")
+                        .takes_value(true)
+                        .conflicts_with("checkpoint")
+                        .help("Coordinate a distributed render, workers connect to this address (for example 0.0.0.0:7878)"),
+                )
+                .arg(
+                    Arg::with_name("local-workers")
+                        .long("local-workers")
+                        .takes_value(true)
+                        .conflicts_with("checkpoint")
+                        .help("Coordinate a distributed render, starting this many workers on this machine"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("worker")
+                .about("Render tiles for a distributed render's coordinator")
+                .arg(
+                    Arg::with_name("connect")
+                        .long("connect")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Coordinator address (for example 192.168.0.2:7878)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("info")
+                .about("Show the config, data files and quality preset a render would use"),
+        )
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("Queue and render jobs sent to a local HTTP/JSON API")
+                .arg(
+                    Arg::with_name("listen")
+                        .long("listen")
+                        .takes_value(true)
+                        .help("Address to serve on")
+                        .default_value("127.0.0.1:8080"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("compare")
+                .about("Compare an image against a reference, or measure convergence towards it")
+                .arg(
+                    Arg::with_name("reference")
+                        .required(true)
+                        .help("Reference image (for example a high spp render)"),
+                )
+                .arg(
+                    Arg::with_name("image")
+                        .required_unless("convergence")
+                        .help("Image to compare against the reference"),
+                )
+                .arg(
+                    Arg::with_name("error-map")
+                        .long("error-map")
+                        .takes_value(true)
+                        .help("Write a false colour map of per pixel relative error"),
+                )
+                .arg(
+                    Arg::with_name("convergence")
+                        .long("convergence")
+                        .takes_value(true)
+                        .conflicts_with("image")
+                        .help("Render the scene one sample per pixel at a time, writing error against samples and time (csv)"),
+                )
+                .arg(
+                    Arg::with_name("output-file")
+                        .long("output-file")
+                        .takes_value(true)
+                        .help("Save the final render (convergence)"),
+                ),
+        )
+        .get_matches()
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn global_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    // Accepted before or after a subcommand, defaults come from the config file
+    vec![
+        Arg::with_name("config")
+            .long("config")
+            .takes_value(true)
+            .global(true)
+            .help("Config file of defaults (default: arrt/config.json in the user's config directory, if it exists)"),
+        Arg::with_name("materials")
+            .long("materials")
+            .takes_value(true)
+            .global(true)
+            .help("Materials file (default: materials.json)"),
+        Arg::with_name("quality-presets")
+            .long("quality-presets")
+            .takes_value(true)
+            .global(true)
+            .help("Quality presets file (default: quality_presets.json)"),
+        Arg::with_name("scene")
+            .long("scene")
+            .takes_value(true)
+            .global(true)
+            .help("Scene file (default: scene.json)"),
+        Arg::with_name("threads")
+            .long("threads")
+            .takes_value(true)
+            .global(true)
+            .help("Number of render threads (default: one per logical core)"),
+    ]
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn render_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    // Also accepted before or after a subcommand, read by those which render
+    vec![
+        Arg::with_name("quality")
+            .long("quality")
+            .takes_value(true)
+            .global(true)
+            .help("Quality preset (default: default)"),
+        Arg::with_name("seed")
+            .long("seed")
+            .takes_value(true)
+            .global(true)
+            .help("Seed value for random number generator (default: 0)"),
+        Arg::with_name("debug-normals")
+            .long("debug-normals")
+            .takes_value(false)
+            .global(true)
+            .help("Debug render normals"),
+        Arg::with_name("debug-heatmap")
+            .long("debug-heatmap")
+            .takes_value(false)
+            .global(true)
+            .help("Debug render heatmp"),
+        Arg::with_name("debug-samples")
+            .long("debug-samples")
+            .takes_value(false)
+            .global(true)
+            .help("Debug render samples per pixel (brightest is the most sampled)"),
+        Arg::with_name("spectral")
+            .long("spectral")
+            .takes_value(false)
+            .global(true)
+            .help("Trace sampled wavelengths rather than RGB (enables dispersion)"),
+        Arg::with_name("resolution")
+            .long("resolution")
+            .takes_value(true)
+            .global(true)
+            .help("Image size, for example 1920x1080 (overrides the preset)"),
+        Arg::with_name("max-bounces")
+            .long("max-bounces")
+            .takes_value(true)
+            .global(true)
+            .help("Maximum bounces per path (overrides the preset)"),
+        Arg::with_name("spp")
+            .long("spp")
+            .takes_value(true)
+            .global(true)
+            .help("Stop at this many samples per pixel (overrides the preset)"),
+        Arg::with_name("time-limit")
+            .long("time-limit")
+            .takes_value(true)
+            .global(true)
+            .help("Stop after this long, for example 90s, 5m or 1h30m (overrides the preset)"),
+        Arg::with_name("noise-threshold")
+            .long("noise-threshold")
+            .takes_value(true)
+            .global(true)
+            .help("Stop sampling pixels once their relative error is below this, for example 0.01 (overrides the preset)"),
+        Arg::with_name("adaptive")
+            .long("adaptive")
+            .takes_value(false)
+            .global(true)
+            .help("Spend samples where the estimated error is highest, --spp is then the average (overrides the preset)"),
+        Arg::with_name("override")
+            .long("override")
+            .takes_value(true)
+            .global(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Override any field of the preset as written in a presets file, for example max_bounces=4 (repeatable)"),
+    ]
+}
+
+// -----------------------------------------------------------------------------------------
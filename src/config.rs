@@ -0,0 +1,184 @@
+// -----------------------------------------------------------------------------------------
+// Config, the command line over the user's config file over built-in defaults
+// -----------------------------------------------------------------------------------------
+
+use arrt::misc::StringLiteral;
+use arrt::quality;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// -----------------------------------------------------------------------------------------
+// Defaults
+const QUALITY_PRESETS_FILE: StringLiteral = "quality_presets.json";
+const MATERIALS_FILE: StringLiteral = "materials.json";
+const SCENE_FILE: StringLiteral = "scene.json";
+const QUALITY: StringLiteral = "default";
+
+// -----------------------------------------------------------------------------------------
+// ConfigFile Type (paths are relative to the working directory, as on the command line)
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    materials: Option<String>,
+    quality_presets: Option<String>,
+    scene: Option<String>,
+    quality: Option<String>,
+    seed: Option<u64>,
+    threads: Option<usize>,
+
+    // Fields of the quality preset, as written in a presets file (for example max_bounces)
+    overrides: Map<String, Value>,
+}
+
+// -----------------------------------------------------------------------------------------
+// Config Type (overrides include the quality options, for example --spp)
+pub struct Config {
+    pub file: Option<PathBuf>,
+    pub materials_file: String,
+    pub quality_presets_file: String,
+    pub scene_file: String,
+    pub quality: String,
+    pub seed: u64,
+    pub thread_count: usize,
+    pub overrides: Map<String, Value>,
+}
+
+// -----------------------------------------------------------------------------------------
+// Config Constructor
+impl Config {
+    pub fn load(args: &clap::ArgMatches) -> Config {
+        // An explicit config file must exist, the user's config file is optional
+        let file = match args.value_of("config") {
+            Some(file) => Some(PathBuf::from(file)),
+            None => user_config_file().filter(|file| file.exists()),
+        };
+        let config_file = match &file {
+            Some(file) => {
+                let data = fs::read_to_string(file).unwrap_or_else(|_| {
+                    panic!("ERROR: Could not load config file: '{}'", file.display())
+                });
+                serde_json::from_str(&data).unwrap_or_else(|error| {
+                    panic!("ERROR: Invalid config file '{}': {}", file.display(), error)
+                })
+            }
+            None => ConfigFile::default(),
+        };
+
+        // Options given on the command line win
+        let value = |name, configured: Option<String>, default: StringLiteral| {
+            args.value_of(name)
+                .map(String::from)
+                .or(configured)
+                .unwrap_or_else(|| String::from(default))
+        };
+        let seed = match args.value_of("seed") {
+            Some(seed) => seed
+                .parse()
+                .unwrap_or_else(|_| panic!("ERROR: Invalid seed: '{}'", seed)),
+            None => config_file.seed.unwrap_or(0),
+        };
+        let thread_count = match args.value_of("threads") {
+            Some(threads) => threads
+                .parse()
+                .ok()
+                .filter(|count| *count > 0)
+                .unwrap_or_else(|| panic!("ERROR: Invalid thread count: '{}'", threads)),
+            None => config_file.threads.unwrap_or_else(num_cpus::get),
+        };
+        let mut overrides = config_file.overrides;
+        for text in args.values_of("override").into_iter().flatten() {
+            let (field, value) = parse_override(text);
+            overrides.insert(field, value);
+        }
+        overrides.extend(quality_options(args));
+        Config {
+            file,
+            materials_file: value("materials", config_file.materials, MATERIALS_FILE),
+            quality_presets_file: value(
+                "quality-presets",
+                config_file.quality_presets,
+                QUALITY_PRESETS_FILE,
+            ),
+            scene_file: value("scene", config_file.scene, SCENE_FILE),
+            quality: value("quality", config_file.quality, QUALITY),
+            seed,
+            thread_count,
+            overrides,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+pub fn user_config_file() -> Option<PathBuf> {
+    // XDG config directory, then the roaming application data directory (Windows)
+    let variable = |name| env::var_os(name).filter(|value| !value.is_empty());
+    let directory = variable("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| variable("APPDATA").map(PathBuf::from))
+        .or_else(|| variable("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(directory.join("arrt").join("config.json"))
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn quality_options(args: &clap::ArgMatches) -> Map<String, Value> {
+    // Fields of the quality preset with their own option, these win over --override
+    let mut options = Map::new();
+    let mut set = |field: &str, value: Value| options.insert(String::from(field), value);
+    if let Some(resolution) = args.value_of("resolution") {
+        let size = resolution
+            .split_once('x')
+            .and_then(|(width, height)| {
+                Some((width.parse::<u32>().ok()?, height.parse::<u32>().ok()?))
+            })
+            .filter(|(width, height)| *width > 0 && *height > 0);
+        let (width, height) =
+            size.unwrap_or_else(|| panic!("ERROR: Invalid resolution: '{}'", resolution));
+        set("image_width", Value::from(width));
+        set("image_height", Value::from(height));
+    }
+    if let Some(max_bounces) = args.value_of("max-bounces") {
+        let max_bounces: u32 = max_bounces
+            .parse()
+            .unwrap_or_else(|_| panic!("ERROR: Invalid max bounces: '{}'", max_bounces));
+        set("max_bounces", Value::from(max_bounces));
+    }
+    if let Some(spp) = args.value_of("spp") {
+        let spp: usize = spp
+            .parse()
+            .unwrap_or_else(|_| panic!("ERROR: Invalid samples per pixel: '{}'", spp));
+        set("samples_per_pixel", Value::from(spp));
+    }
+    if let Some(time_limit) = args.value_of("time-limit") {
+        let time_limit_s =
+            quality::parse_duration(time_limit).unwrap_or_else(|error| panic!("{}", error));
+        set("time_limit", Value::from(time_limit_s));
+    }
+    if let Some(noise_threshold) = args.value_of("noise-threshold") {
+        let noise_threshold: f32 = noise_threshold
+            .parse()
+            .unwrap_or_else(|_| panic!("ERROR: Invalid noise threshold: '{}'", noise_threshold));
+        set("noise_threshold", Value::from(noise_threshold));
+    }
+    if args.is_present("adaptive") {
+        set("adaptive", Value::Bool(true));
+    }
+    options
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn parse_override(text: &str) -> (String, Value) {
+    // Values are JSON, anything else is taken as a string (so time_limit=5m works)
+    let (field, value) = text
+        .split_once('=')
+        .unwrap_or_else(|| panic!("ERROR: Invalid override: '{}' (expected field=value)", text));
+    let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(String::from(value)));
+    (String::from(field.trim()), value)
+}
+
+// -----------------------------------------------------------------------------------------
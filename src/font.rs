@@ -0,0 +1,163 @@
+// -----------------------------------------------------------------------------------------
+// Bitmap font for on-screen messages (5x7 glyphs, upper case ASCII)
+// -----------------------------------------------------------------------------------------
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+const GLYPH_SCALE: u32 = 2;
+const CELL_WIDTH: u32 = (GLYPH_WIDTH + 1) * GLYPH_SCALE;
+const CELL_HEIGHT: u32 = (GLYPH_HEIGHT + 3) * GLYPH_SCALE;
+const MARGIN: u32 = 8;
+const TEXT_COLOUR: [u8; 4] = [255, 96, 96, 255];
+
+// -----------------------------------------------------------------------------------------
+// Glyph rows for ' ' to '_', top row first, most significant of the 5 bits on the left
+const GLYPHS: [[u8; 7]; 64] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // '!'
+    [0x0A, 0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A], // '#'
+    [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04], // '$'
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // '%'
+    [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D], // '&'
+    [0x0C, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00], // '''
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // '('
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // ')'
+    [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00], // '*'
+    [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08], // ','
+    [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C], // '.'
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // '/'
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E], // '0'
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E], // '1'
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F], // '2'
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E], // '3'
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02], // '4'
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E], // '5'
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E], // '6'
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // '7'
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E], // '8'
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C], // '9'
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00], // ':'
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08], // ';'
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // '<'
+    [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00], // '='
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // '>'
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // '?'
+    [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E], // '@'
+    [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11], // 'A'
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E], // 'B'
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], // 'C'
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C], // 'D'
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], // 'E'
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10], // 'F'
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], // 'G'
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // 'H'
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // 'I'
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C], // 'J'
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // 'K'
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F], // 'L'
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], // 'M'
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // 'N'
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // 'O'
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10], // 'P'
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], // 'Q'
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11], // 'R'
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], // 'S'
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // 'T'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // 'U'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04], // 'V'
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], // 'W'
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11], // 'X'
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04], // 'Y'
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F], // 'Z'
+    [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E], // '['
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // '\'
+    [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E], // ']'
+    [0x04, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F], // '_'
+];
+
+// -----------------------------------------------------------------------------------------
+
+pub fn draw_message(buffer: &mut [[u8; 4]], width: u32, height: u32, message: &str) {
+    // Wrap to the buffer width, one cell per character
+    let columns = (width.saturating_sub(MARGIN * 2) / CELL_WIDTH).max(1) as usize;
+    let mut lines = Vec::new();
+    for line in message.lines() {
+        let characters: Vec<char> = line.chars().collect();
+        if characters.is_empty() {
+            lines.push(Vec::new());
+        }
+        for chunk in characters.chunks(columns) {
+            lines.push(chunk.to_vec());
+        }
+    }
+
+    // Darken a band behind the text
+    let band_height = ((lines.len() as u32 * CELL_HEIGHT) + (MARGIN * 2)).min(height);
+    for y in 0..band_height {
+        for x in 0..width {
+            let pixel = &mut buffer[pixel_index(width, height, x, y)];
+            for channel in pixel.iter_mut().take(3) {
+                *channel /= 4;
+            }
+        }
+    }
+
+    // Draw glyphs
+    for (row, line) in lines.iter().enumerate() {
+        for (column, character) in line.iter().enumerate() {
+            let x = MARGIN + (column as u32 * CELL_WIDTH);
+            let y = MARGIN + (row as u32 * CELL_HEIGHT);
+            draw_glyph(buffer, width, height, x, y, *character);
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn draw_glyph(buffer: &mut [[u8; 4]], width: u32, height: u32, x: u32, y: u32, character: char) {
+    let glyph = &GLYPHS[glyph_index(character)];
+    for (glyph_y, bits) in glyph.iter().enumerate() {
+        for glyph_x in 0..GLYPH_WIDTH {
+            if (bits >> (GLYPH_WIDTH - 1 - glyph_x)) & 1 == 0 {
+                continue;
+            }
+            for offset_y in 0..GLYPH_SCALE {
+                for offset_x in 0..GLYPH_SCALE {
+                    let pixel_x = x + (glyph_x * GLYPH_SCALE) + offset_x;
+                    let pixel_y = y + (glyph_y as u32 * GLYPH_SCALE) + offset_y;
+                    if pixel_x < width && pixel_y < height {
+                        buffer[pixel_index(width, height, pixel_x, pixel_y)] = TEXT_COLOUR;
+                    }
+                }
+            }
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn glyph_index(character: char) -> usize {
+    // Lower case shares the upper case glyphs, anything else unsupported shows as '?'
+    let character = match character {
+        '`' => '\'',
+        '\t' => ' ',
+        _ => character.to_ascii_uppercase(),
+    };
+    match character {
+        ' '..='_' => (character as usize) - (' ' as usize),
+        _ => ('?' as usize) - (' ' as usize),
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn pixel_index(width: u32, height: u32, x: u32, y: u32) -> usize {
+    // Image buffers are stored bottom row first, messages are laid out from the top
+    (((height - 1 - y) * width) + x) as usize
+}
+
+// -----------------------------------------------------------------------------------------
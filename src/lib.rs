@@ -0,0 +1,20 @@
+// -----------------------------------------------------------------------------------------
+// Arrt library, everything needed to load and render a scene (the binary is a front-end)
+// -----------------------------------------------------------------------------------------
+
+mod arrt;
+#[cfg(test)]
+mod tests;
+
+// -----------------------------------------------------------------------------------------
+
+pub use arrt::*;
+
+pub use arrt::camera::Camera;
+pub use arrt::framebuffer::Framebuffer;
+pub use arrt::material::MaterialBank;
+pub use arrt::quality::QualityPreset;
+pub use arrt::renderer::{Progress, Region, RegionSums, RenderHandle, Renderer};
+pub use arrt::scene::Scene;
+
+// -----------------------------------------------------------------------------------------
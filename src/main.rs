@@ -1,45 +1,44 @@
+// -----------------------------------------------------------------------------------------
+// Front-end modules
+mod command_line;
+mod config;
+mod font;
+
 // -----------------------------------------------------------------------------------------
 // Arrt dependencies
-mod arrt;
-use arrt::camera::Camera;
-use arrt::command_line;
-use arrt::geometry::Plane;
-use arrt::geometry::Sphere;
-use arrt::job::Job;
-use arrt::material::MaterialBank;
-use arrt::misc::StringLiteral;
-use arrt::quality::QualityPresetBank;
-use arrt::ray::Ray;
-use arrt::ray::RayHitResult;
+use arrt::distributed;
+use arrt::metrics;
+use arrt::metrics::Metrics;
+use arrt::quality;
+use arrt::quality::{QualityPreset, QualityPresetBank};
+use arrt::service;
 use arrt::vector::Vec3;
+use arrt::{Camera, Framebuffer, MaterialBank, Progress, Renderer, Scene};
+use config::Config;
 
 // -----------------------------------------------------------------------------------------
 // External dependencies
 use hotwatch::{Event, Hotwatch};
-use num_cpus;
-use rand::prelude::*;
-use std::collections::VecDeque;
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::net::{Ipv4Addr, TcpListener};
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::{Child, Command};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc;
 use std::sync::Arc;
-use std::sync::Mutex;
-use std::thread;
-use std::time::Duration;
 use winit::VirtualKeyCode;
 use guid_create::GUID;
 
 // -----------------------------------------------------------------------------------------
 // Types
-type JobQueue = VecDeque<RayJob>;
-type Transmitter = std::sync::mpsc::Sender<RayJobResult>;
 type ImageBuffer = std::vec::Vec<[u8; 4]>;
 
 // -----------------------------------------------------------------------------------------
 // Config
 const _PROGRESS_UPDATE_INTERVAL: f64 = 1.0;
-const QUALITY_PRESETS_FILE: StringLiteral = "quality_presets.json";
-const MATERIALS_FILE: StringLiteral = "materials.json";
-const EPSILON: f32 = 0.001;
 const CAMERA_ROTATION_SPEED: f32 = 2.0;
 
 // -----------------------------------------------------------------------------------------
@@ -56,229 +55,57 @@ const CAMERA_LOOKAT: Vec3 = Vec3 {
     z: 0.0,
 };
 
-// -----------------------------------------------------------------------------------------
-// Config | Sky
-const SKY_COLOUR_BOTTOM: Vec3 = Vec3 {
-    x: 1.0,
-    y: 1.0,
-    z: 1.0,
-};
-const SKY_COLOUR_TOP: Vec3 = Vec3 {
-    x: 0.5,
-    y: 0.7,
-    z: 1.0,
-};
-
-// -----------------------------------------------------------------------------------------
-// Config | Scene
-const FLOOR_PLANE: Plane = Plane {
-    position: Vec3::ZERO,
-    normal: Vec3::UP,
-    diffuse: Vec3::ONE,
-};
-const SCENE_SPHERES: [Sphere; 3] = [
-    Sphere {
-        centre: Vec3 {
-            x: -6.5,
-            y: 3.0,
-            z: 0.0,
-        },
-        radius: 3.0,
-        material: "red",
-    },
-    Sphere {
-        centre: Vec3 {
-            x: 0.0,
-            y: 3.0,
-            z: 0.0,
-        },
-        radius: 3.0,
-        material: "mirror",
-    },
-    Sphere {
-        centre: Vec3 {
-            x: 6.5,
-            y: 3.0,
-            z: 0.0,
-        },
-        radius: 3.0,
-        material: "blue",
-    },
-];
-
 // -----------------------------------------------------------------------------------------
 
 fn main() {
-    run_interactive();
-}
-
-// -----------------------------------------------------------------------------------------
-
-//type SharedJob = std::sync::Arc<arrt::job::Job>;
-type SafeQueue = std::sync::Arc<std::sync::Mutex<std::collections::VecDeque<RayJob>>>;
-
-#[derive(Debug, Default, Copy, Clone)]
-pub struct RayJob {
-    pub pixel_index: usize,
-    pub sample_index: usize,
-    pub ray_index: usize,
-    pub bounce_index: u32,
-    pub ray: Ray,
-    pub max_bounces: u32,
-    pub movement_counter: u64,
-}
-
-#[derive(Debug, Default, Copy, Clone)]
-pub struct RayJobResult {
-    pub pixel_index: usize,
-    pub sample_index: usize,
-    pub ray_index: usize,
-    pub bounce_index: u32,
-    pub colour: Vec3,
-}
-
-// -----------------------------------------------------------------------------------------
-
-fn run_thread(
-    _thread_index: usize,
-    rng_seed: u64,
-    job_queue_arc: &SafeQueue,
-    job_arc: &Job,
-    tx: Transmitter,
-) {
-    let mut rng = SeedableRng::seed_from_u64(rng_seed);
-
-    loop {
-        let mut job_queue = job_queue_arc.lock().unwrap();
-        if job_queue.len() > 0 {
-            let ray_job = job_queue.pop_front().unwrap();
-            drop(job_queue); // release lock
-            let ray = &ray_job.ray;
-            let (colour, result) = sample_scene(ray, &job_arc);
-
-            // Transmit result
-            let job_result = RayJobResult {
-                pixel_index: ray_job.pixel_index,
-                sample_index: ray_job.sample_index,
-                ray_index: ray_job.ray_index,
-                bounce_index: ray_job.bounce_index,
-                colour,
-            };
-            // println!(
-            //     "[THREAD] send: pixel_index = {} ray_index = {}, colour = {}, {}, {}",
-            //     job_result.pixel_index,
-            //     job_result.ray_index,
-            //     job_result.colour.x,
-            //     job_result.colour.y,
-            //     job_result.colour.z,
-            // );
-            tx.send(job_result).unwrap();
-
-            // Schedule bounce job?
-            if result.hit && ((ray_job.bounce_index + 1) <= ray_job.max_bounces) {
-                // Calculate reflected point
-                let material = job_arc.materials.get(result.material_name);
-                let refelcted_point = if material.name == "mirror" {
-                    result.position + Vec3::reflect(ray.direction, result.normal)
-                } else {
-                    result.position
-                        + result.normal
-                        + (Vec3::random_point_in_unit_sphere(&mut rng) * 0.99)
-                };
-
-                // Calculate reflected ray
-                let reflected_ray_origin = result.position + (result.normal * EPSILON);
-                let refelcted_ray_direction = Vec3::normalize(refelcted_point - result.position);
-                let reflected_ray = Ray::new(reflected_ray_origin, refelcted_ray_direction);
-
-                // Setup bounce job
-                let mut bounce_job = ray_job;
-                bounce_job.ray = reflected_ray;
-                bounce_job.bounce_index = ray_job.bounce_index + 1;
-
-                // Lock queue and schedule bounce job
-                let mut job_queue = job_queue_arc.lock().unwrap();
-                job_queue.push_back(bounce_job);
-            }
-        } else {
-            // TODO: wait on event here which is raised when camera moves!
-            // (*job_queue).push_back(2);// schedule next bounce
-            drop(job_queue); // release lock
-                             //println!("Thread {} starved", thread_index);
-            thread::sleep(Duration::from_millis(100));
-        }
-    }
-}
-
-// -----------------------------------------------------------------------------------------
-
-fn schedule_work(
-    image_width: u32,
-    image_height: u32,
-    samples_per_pixel: usize,
-    max_bounces: u32,
-    camera: &Camera,
-    job_queue: &mut JobQueue,
-    movement_counter: u64,
-) {
-    job_queue.clear();
-    for sample_index in 0..samples_per_pixel {
-        for pixel_y in 0..image_height {
-            for pixel_x in 0..image_width {
-                let pixel_index = (((pixel_y * image_width) + pixel_x) as usize) * samples_per_pixel;
-                    let ray_index = pixel_index + sample_index;
-                    let ray = camera.cached_rays[ray_index as usize];
-                    let ray_job = RayJob {
-                        pixel_index,
-                        sample_index,
-                        ray_index,
-                        ray,
-                        bounce_index: 0,
-                        movement_counter,
-                        max_bounces,
-                    };
-                    job_queue.push_back(ray_job);
-            }
-        }
+    let args = command_line::parse();
+    let (subcommand, subcommand_args) = args.subcommand();
+    let args = subcommand_args.unwrap_or(&args);
+
+    // Global options are propagated to the subcommand, so everything is read from its args
+    let config = Config::load(args);
+    match subcommand {
+        "render" => run_render(args, &config),
+        "compare" => run_compare(args, &config),
+        "worker" => run_worker(args, &config),
+        "serve" => run_serve(args, &config),
+        "info" => run_info(&config),
+        _ => run_interactive(args, &config),
     }
 }
 
 // -----------------------------------------------------------------------------------------
 
-fn run_interactive() {
-    // Parse command line args
-    let args = command_line::parse();
-
+fn run_interactive(args: &clap::ArgMatches, config: &Config) {
     // Load quality presets
-    let quality_presets = QualityPresetBank::load_from_file(QUALITY_PRESETS_FILE);
-    let quality_preset_name = args.value_of("quality").unwrap_or("default");
-    let quality = quality_presets.get(quality_preset_name);
+    let quality_presets = QualityPresetBank::load_from_file(&config.quality_presets_file);
+    let quality =
+        load_quality(&quality_presets, config).unwrap_or_else(|error| panic!("{}", error));
 
     // Load materials
-    let materials = MaterialBank::load_from_file(MATERIALS_FILE);
-
-    // Setup rng
-    let rng_seed: u64 = args.occurrences_of("seed");
-    let mut rng = SeedableRng::seed_from_u64(rng_seed);
+    let materials = MaterialBank::load_from_file(&config.materials_file);
+
+    // Load scene (and resolve the materials it references), errors are shown in the viewer
+    let mut load_errors = BTreeMap::new();
+    let scene_file = &config.scene_file;
+    let mut scene = Scene::try_load_from_file(scene_file, &materials).unwrap_or_else(|error| {
+        println!("{}", error);
+        load_errors.insert(scene_file.clone(), error);
+        Scene::new(scene_file, Vec::new())
+    });
 
     // Setup camera
     let mut camera = Camera::new(CAMERA_POSITION, CAMERA_LOOKAT, CAMERA_FOV);
 
-    // Cache camera rays
-    camera.update_cached_rays(
-        quality.image_width,
-        quality.image_height,
-        quality.samples_per_pixel,
-        &mut rng,
-    );
-
-    // Setup job
-    let debug_normals = args.is_present("debug-normals");
-    let debug_heatmap = args.is_present("debug-heatmap");
-    let (image_width, image_height) = (quality.image_width, quality.image_height);
-    let samples_per_pixel = quality.samples_per_pixel;
-    let max_bounces = quality.max_bounces;
-    let job = Job::new(quality, materials, debug_normals, debug_heatmap);
+    // Setup renderer
+    let (mut image_width, mut image_height) = (quality.image_width, quality.image_height);
+    let mut renderer = Renderer::new(quality, materials, camera.clone());
+    renderer.seed = config.seed;
+    renderer.thread_count = config.thread_count;
+    renderer.debug_normals = args.is_present("debug-normals");
+    renderer.debug_heatmap = args.is_present("debug-heatmap");
+    renderer.debug_samples = args.is_present("debug-samples");
+    renderer.spectral = args.is_present("spectral");
     let mut can_take_screenshot = true;
 
     // Setup image buffer
@@ -297,65 +124,28 @@ fn run_interactive() {
     let mut watcher = Hotwatch::new().expect("File watcher failed to initialize!");
     let reload_materials_flag = Arc::new(AtomicBool::new(false));
     let reload_quality_flag = Arc::new(AtomicBool::new(false));
-    watch_file(&mut watcher, MATERIALS_FILE, &reload_materials_flag);
-    watch_file(&mut watcher, QUALITY_PRESETS_FILE, &reload_quality_flag);
-
-    // Setup result store
-    let total_ray_job_count = (total_pixel_count as usize) * samples_per_pixel;
-    let mut result_store = vec![Vec3::BLACK; total_ray_job_count];
-
-    // Setup result queue
-    let (tx, rx) = mpsc::channel::<RayJobResult>();
-
-    // Schedule work
-    let mut movement_counter = 0;
-    let total_ray_job_count = (total_pixel_count as usize) * samples_per_pixel;
-    let mut job_queue = JobQueue::with_capacity(total_ray_job_count as usize);
-    schedule_work(
-        image_width,
-        image_height,
-        samples_per_pixel,
-        max_bounces,
-        &camera,
-        &mut job_queue,
-        movement_counter
-    );
-
-    // Threading
-    let job_arc = Arc::new(job);
-    let job_queue_arc = Arc::new(Mutex::new(job_queue));
-    let thread_count = num_cpus::get();
-    for thread_index in 0..thread_count {
-        let job_arc = job_arc.clone();
-        let job_queue_arc = job_queue_arc.clone();
-        let tx = mpsc::Sender::clone(&tx);
-        thread::spawn(move || {
-            run_thread(thread_index, rng_seed, &job_queue_arc, &job_arc, tx);
-        });
+    let reload_scene_flag = Arc::new(AtomicBool::new(false));
+    let mut watched_files = HashSet::new();
+    let mut materials_files = vec![PathBuf::from(&config.materials_file)];
+    materials_files.extend_from_slice(renderer.materials.images());
+    let quality_files = vec![PathBuf::from(&config.quality_presets_file)];
+    let mut scene_files = vec![PathBuf::from(scene_file)];
+    scene_files.extend(scene.files());
+    for (files, flag) in &[
+        (materials_files, &reload_materials_flag),
+        (quality_files, &reload_quality_flag),
+        (scene_files, &reload_scene_flag),
+    ] {
+        watch_files(&mut watcher, &mut watched_files, files, flag);
     }
 
+    // Start rendering (restarted whenever the camera moves or data is reloaded)
+    let mut render = renderer.render_async(&scene);
+
     // Pump message loop
-    let job = job_arc.clone();
     let (mut draw_time_acc_s, mut present_time_acc_s) = (0.0, 0.0);
     let (mut frame_count, mut total_frame_count, mut output_iteration) = (0, 0, 0);
-    let _reload_materials_flag_consume = Arc::clone(&reload_materials_flag);
-    let _reload_quality_flag_consume = Arc::clone(&reload_quality_flag);
     window_handle.glutin_handle_basic_input(move |window, input| {
-        // Live update materials?
-        // if reload_materials_flag_consume.load(Ordering::Relaxed) {
-        //     reload_materials_flag_consume.swap(false, Ordering::Relaxed);
-        //     println!("Reloading materials");
-        //     job.materials = MaterialBank::load_from_file(MATERIALS_FILE);
-        // }
-
-        // // Live update quality?
-        // if reload_quality_flag_consume.load(Ordering::Relaxed) {
-        //     reload_quality_flag_consume.swap(false, Ordering::Relaxed);
-        //     println!("Reloading quality presets");
-        //     let quality_presets = QualityPresetBank::load_from_file(QUALITY_PRESETS_FILE);
-        //     job.quality = quality_presets.get(&job.quality.name);
-        // }
-
         // Quit
         if input.key_is_down(VirtualKeyCode::Escape) {
             return false;
@@ -365,7 +155,9 @@ fn run_interactive() {
         if can_take_screenshot && input.key_is_down(VirtualKeyCode::O) {
             let guid = GUID::rand().to_string();
             let filename = format!("gallery/screenshot_{}.bmp", guid);
-            save_image(&image_buffer, image_width, image_height, &filename);
+            if let Err(error) = render.preview().save(&filename) {
+                println!("{}", error);
+            }
             can_take_screenshot = false;
         }
         else if !can_take_screenshot
@@ -413,69 +205,103 @@ fn run_interactive() {
              camera.up = Vec3::cross(camera.forward, camera.right);
              update_camera = true;
          }
+
+        // Live update materials? (scene is re-bound, as ids belong to the new bank)
+        let mut reload = false;
+        if reload_materials_flag.swap(false, Ordering::Relaxed) {
+            println!("Reloading materials");
+            match MaterialBank::try_load_from_file(&config.materials_file) {
+                Ok(materials) => {
+                    let images = materials.images();
+                    watch_files(
+                        &mut watcher,
+                        &mut watched_files,
+                        images,
+                        &reload_materials_flag,
+                    );
+                    load_errors.remove(&config.materials_file);
+                    scene.bind_materials(&materials);
+                    renderer.materials = Arc::new(materials);
+                    reload = true;
+                }
+                Err(error) => {
+                    println!("{}", error);
+                    load_errors.insert(config.materials_file.clone(), error);
+                }
+            }
+        }
+
+        // Live update quality?
+        if reload_quality_flag.swap(false, Ordering::Relaxed) {
+            println!("Reloading quality presets");
+            let quality = QualityPresetBank::try_load_from_file(&config.quality_presets_file)
+                .and_then(|quality_presets| load_quality(&quality_presets, config));
+            match quality {
+                Ok(quality) => {
+                    load_errors.remove(&config.quality_presets_file);
+                    renderer.quality = quality;
+                    reload = true;
+                }
+                Err(error) => {
+                    println!("{}", error);
+                    load_errors.insert(config.quality_presets_file.clone(), error);
+                }
+            }
+        }
+
+        // Live update scene? (only modified files are re-parsed and re-partitioned)
+        if reload_scene_flag.swap(false, Ordering::Relaxed) {
+            println!("Reloading scene");
+            match scene.reload(&renderer.materials) {
+                Ok(reloaded) => {
+                    watch_files(
+                        &mut watcher,
+                        &mut watched_files,
+                        &reloaded.files(),
+                        &reload_scene_flag,
+                    );
+                    load_errors.remove(scene_file);
+                    scene = reloaded;
+                    reload = true;
+                }
+                Err(error) => {
+                    println!("{}", error);
+                    load_errors.insert(scene_file.clone(), error);
+                }
+            }
+        }
+
+        // Restart with reloaded data
+        if reload {
+            let quality = &renderer.quality;
+            if (quality.image_width, quality.image_height) != (image_width, image_height) {
+                image_width = quality.image_width;
+                image_height = quality.image_height;
+                let pixel_count = (image_width * image_height) as usize;
+                image_buffer = vec![clear_colour; pixel_count];
+                window.resize_buffer(image_width, image_height);
+            }
+            update_camera = true;
+        }
  
-         // Update camera
+         // Update camera (the previous render is cancelled, its results are discarded)
          if update_camera {
-             camera.update_cached_rays(
-                 job.quality.image_width,
-                 job.quality.image_height,
-                 job.quality.samples_per_pixel,
-                 &mut rng,
-             );
-
-             // Clear results
-             for i in 0..result_store.len()
-             {
-                result_store[i] = Vec3::BLACK;
-             }
- 
-             // Lock and re-schedule work
-             movement_counter = movement_counter + 1;
-             let mut job_queue = job_queue_arc.lock().unwrap();
-             schedule_work(
-                 image_width,
-                 image_height,
-                 samples_per_pixel,
-                 max_bounces,
-                 &camera,
-                 &mut job_queue,
-                 movement_counter,
-             );
+             renderer.camera = camera.clone();
+             render.cancel();
+             render = renderer.render_async(&scene);
              return true;
          }
 
-        // Receive results from threads
-        let iter = rx.try_iter();
-        for result in iter.take((image_width * image_height) as usize) {
-            // println!(
-            //     "[MAIN] rcv: pixel_index = {} ray_index = {}, colour = {}, {}, {}",
-            //     result.pixel_index,
-            //     result.ray_index,
-            //     result.colour.x,
-            //     result.colour.y,
-            //     result.colour.z
-            // );
-            let first_result = result.bounce_index == 0;
-            if first_result {
-                result_store[result.ray_index] = result.colour;
-            } else {
-                result_store[result.ray_index] *= result.colour;
-            }
-        }
         // Redraw
         let timer_draw_begin = time::precise_time_s();
         let mut pixel = [0u8, 0u8, 0u8, 255u8];
+        let preview = render.preview();
         for pixel_y in 0..image_height {
             for pixel_x in 0..image_width {
-                let mut average = Vec3::BLACK;
-                let pixel_bucket_index =
-                    (((pixel_y * image_width) + pixel_x) as usize) * samples_per_pixel;
-                for sample_index in 0..samples_per_pixel {
-                    let ray_index = pixel_bucket_index + sample_index;
-                    average += result_store[ray_index];
-                }
-                average /= samples_per_pixel as f32;
-                Vec3::copy_to_pixel(average, &mut pixel);
+                // Preview is stored top row first, the image buffer bottom row first
+                let preview_index =
+                    (((image_height - 1 - pixel_y) * image_width) + pixel_x) as usize;
+                Vec3::copy_to_pixel(preview.pixels[preview_index], &mut pixel);
 
                 // Write pixel
                 let pixel_index = ((pixel_y * image_width) + pixel_x) as usize;
@@ -483,6 +309,13 @@ fn run_interactive() {
             }
         }
         // draw_scene(job, false);
+
+        // Show load errors over the last good render
+        if !load_errors.is_empty() {
+            let message: Vec<&str> = load_errors.values().map(|error| error.as_str()).collect();
+            let message = message.join("\n");
+            font::draw_message(&mut image_buffer, image_width, image_height, &message);
+        }
         let timer_draw_end = time::precise_time_s();
 
         // Present
@@ -520,143 +353,320 @@ fn run_interactive() {
 
 // -----------------------------------------------------------------------------------------
 
-pub fn save_image(image_buffer: &ImageBuffer, width: u32, height: u32, filename: &String) {
-    // Create bitmap
-    let mut output_bmp = bmp::Image::new(width, height);
-
-    // Copy image buffer to bitmap
-    for x in 0..width {
-        for y in 0..height {
-            let pixel_index = ((width * y) + x) as usize;
-            let pixel = bmp::Pixel {
-                r: image_buffer[pixel_index][0],
-                g: image_buffer[pixel_index][1],
-                b: image_buffer[pixel_index][2],
-            };
-            output_bmp.set_pixel(x, height - y - 1, pixel);
-        }
+fn run_render(args: &clap::ArgMatches, config: &Config) {
+    // Load quality, materials and scene
+    let quality_presets = QualityPresetBank::load_from_file(&config.quality_presets_file);
+    let quality =
+        load_quality(&quality_presets, config).unwrap_or_else(|error| panic!("{}", error));
+    let materials = MaterialBank::load_from_file(&config.materials_file);
+    let scene = Scene::try_load_from_file(&config.scene_file, &materials)
+        .unwrap_or_else(|error| panic!("{}", error));
+
+    // Setup renderer
+    let camera = Camera::new(CAMERA_POSITION, CAMERA_LOOKAT, CAMERA_FOV);
+    let mut renderer = Renderer::new(quality, materials, camera);
+    renderer.seed = config.seed;
+    renderer.thread_count = config.thread_count;
+    renderer.debug_normals = args.is_present("debug-normals");
+    renderer.debug_heatmap = args.is_present("debug-heatmap");
+    renderer.debug_samples = args.is_present("debug-samples");
+    renderer.spectral = args.is_present("spectral");
+    renderer.checkpoint_file = args.value_of("checkpoint").map(String::from);
+    if let Some(interval) = args.value_of("checkpoint-interval") {
+        renderer.checkpoint_interval_s =
+            quality::parse_duration(interval).unwrap_or_else(|error| panic!("{}", error));
+    }
+    renderer.on_checkpoint_error = Some(Arc::new(|error| println!("{}", error)));
+
+    // Distributed render?
+    let output_file = args.value_of("output-file").unwrap_or("output.png");
+    if args.is_present("listen") || args.is_present("local-workers") {
+        let image = run_coordinator(args, &renderer, &scene);
+        image
+            .save(output_file)
+            .unwrap_or_else(|error| panic!("{}", error));
+        println!("Saved '{}'", output_file);
+        return;
     }
 
-    // Save bitmap
-    output_bmp.save(filename).expect("Failed to save image");
+    // Render until a stopping criteria is met, reporting progress after each pass
+    let mut last_progress = None;
+    let on_pass = |progress: &Progress, _: &Framebuffer| {
+        let converged = (progress.converged_pixel_count * 100) as f64 / progress.pixel_count as f64;
+        println!(
+            "{}/{}|{:.2}s|{:.1}%",
+            progress.samples_per_pixel,
+            progress.total_samples_per_pixel,
+            progress.time_s,
+            converged
+        );
+        last_progress = Some(*progress);
+        true
+    };
+    let image = match args.value_of("checkpoint") {
+        Some(checkpoint_file) if args.is_present("resume") => renderer
+            .resume_progressive(&scene, checkpoint_file, on_pass)
+            .unwrap_or_else(|error| panic!("{}", error)),
+        _ => renderer.render_progressive(&scene, on_pass),
+    };
+
+    // Save
+    image
+        .save(output_file)
+        .unwrap_or_else(|error| panic!("{}", error));
+    if let Some(progress) = last_progress {
+        println!(
+            "Saved '{}' ({} spp, {:.2}s)",
+            output_file, progress.samples_per_pixel, progress.time_s
+        );
+    }
 }
 
 // -----------------------------------------------------------------------------------------
 
-fn sample_scene(ray: &Ray, job: &Job) -> (Vec3, RayHitResult) {
-    let mut result = RayHitResult::MAX_HIT;
-
-    // Test against spheres
-    let sphere_result = sample_scene_spheres(ray);
-    if sphere_result.hit {
-        result = sphere_result;
+fn run_coordinator(args: &clap::ArgMatches, renderer: &Renderer, scene: &Scene) -> Framebuffer {
+    // Listen (local workers only need a loopback port)
+    let address = args.value_of("listen").unwrap_or("127.0.0.1:0");
+    let listener = TcpListener::bind(address)
+        .unwrap_or_else(|error| panic!("ERROR: Could not listen on '{}' ({})", address, error));
+    let mut local_address = listener.local_addr().unwrap();
+    println!("Waiting for workers on {}", local_address);
+
+    // Start local workers
+    if local_address.ip().is_unspecified() {
+        local_address.set_ip(Ipv4Addr::LOCALHOST.into());
     }
-
-    // Test against planes
-    let plane_result = sample_scene_planes(ray);
-    if plane_result.hit && (plane_result.distance < result.distance) {
-        result = plane_result;
-    }
-
-    // Test against sdf
-    // let sdf_result = _sample_scene_sdf(ray);
-    // if sdf_result.hit && sdf_result.distance < result.distance {
-    //     result = sdf_result;
-    // }
-
-    // Use background?
-    let colour: Vec3;
-    if job.debug_normals {
-        colour = Vec3::new(
-            (result.normal.x + 1.0) * 0.5,
-            (result.normal.y + 1.0) * 0.5,
-            (result.normal.z + 1.0) * 0.5,
-        );
-    } else {
-        if !result.hit {
-            colour = sample_background(ray);
-        } else {
-            // Grab material
-            let material = job.materials.get(result.material_name);
-
-            // Shade pixel (diffuse)
-            let reflected = 1.0 - material.absorbed;
-            colour = material.diffuse * reflected;
+    let local_worker_count = args.value_of("local-workers").map_or(0, |count| {
+        count
+            .parse()
+            .unwrap_or_else(|_| panic!("ERROR: Invalid local worker count: '{}'", count))
+    });
+    let executable = env::current_exe().unwrap();
+    let mut local_workers: Vec<Child> = (0..local_worker_count)
+        .map(|_| {
+            Command::new(&executable)
+                .args(["worker", "--connect", &local_address.to_string()])
+                .spawn()
+                .unwrap_or_else(|error| panic!("ERROR: Could not start worker ({})", error))
+        })
+        .collect();
+
+    // Render, reporting progress after each tile
+    let timer_begin = time::precise_time_s();
+    let image = distributed::coordinate(renderer, scene, listener, |event| match event {
+        distributed::Event::TileCompleted {
+            completed,
+            tile_count,
+        } => {
+            let time_s = time::precise_time_s() - timer_begin;
+            println!("{}/{} tiles|{:.2}s", completed, tile_count, time_s);
+        }
+        distributed::Event::WorkerDropped { address, error } => {
+            println!("Worker {} dropped ({})", address, error);
+        }
+        distributed::Event::AcceptFailed(error) => {
+            println!("Could not accept worker ({})", error);
         }
+    })
+    .unwrap_or_else(|error| panic!("{}", error));
+    for worker in &mut local_workers {
+        let _ = worker.wait();
     }
+    image
+}
+
+// -----------------------------------------------------------------------------------------
 
-    // Return info
-    (colour, result)
+fn run_worker(args: &clap::ArgMatches, config: &Config) {
+    let address = args.value_of("connect").unwrap();
+    distributed::work(address, config.thread_count).unwrap_or_else(|error| panic!("{}", error));
 }
 
 // -----------------------------------------------------------------------------------------
 
-fn sample_scene_spheres(ray: &Ray) -> RayHitResult {
-    // Process all spheres
-    let mut closest_result = RayHitResult::MAX_HIT;
-    let mut closest_sphere_centre = Vec3::ZERO;
-    for sphere in &SCENE_SPHERES {
-        let result = arrt::intersect::ray_sphere(&ray, &sphere);
-        if result.hit && (result.distance < closest_result.distance) {
-            closest_result = result;
-            closest_sphere_centre = sphere.centre;
-        }
+fn run_serve(args: &clap::ArgMatches, config: &Config) {
+    // Jobs leaving out their materials, preset, camera or seed get the same as a render, and
+    // named presets take the same overrides (an unknown preset is reported now, not per job)
+    let quality_presets = QualityPresetBank::load_from_file(&config.quality_presets_file);
+    load_quality(&quality_presets, config).unwrap_or_else(|error| panic!("{}", error));
+    let defaults = service::Defaults {
+        quality_presets,
+        quality: config.quality.clone(),
+        materials_file: config.materials_file.clone(),
+        seed: config.seed,
+        overrides: config.overrides.clone(),
+        camera: Camera::new(CAMERA_POSITION, CAMERA_LOOKAT, CAMERA_FOV),
+        thread_count: config.thread_count,
+    };
+    let address = args.value_of("listen").unwrap();
+    let listener = TcpListener::bind(address)
+        .unwrap_or_else(|error| panic!("ERROR: Could not listen on '{}' ({})", address, error));
+    println!("Serving on http://{}", listener.local_addr().unwrap());
+    service::serve(listener, defaults).unwrap_or_else(|error| panic!("{}", error));
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn run_info(config: &Config) {
+    // Everything a render with the same options would use (load errors are shown, not fatal)
+    println!("arrt {}", env!("CARGO_PKG_VERSION"));
+    match &config.file {
+        Some(file) => println!("Config:          {}", file.display()),
+        None => match config::user_config_file() {
+            Some(file) => println!("Config:          none ({} doesn't exist)", file.display()),
+            None => println!("Config:          none"),
+        },
     }
+    println!("Threads:         {}", config.thread_count);
+    println!("Seed:            {}", config.seed);
 
-    // Calculate normal (if valid hit)
-    if closest_result.hit {
-        closest_result.normal = Vec3::normalize(closest_result.position - closest_sphere_centre);
-        closest_result
-    } else {
-        RayHitResult::NO_HIT
+    // Data files
+    match QualityPresetBank::try_load_from_file(&config.quality_presets_file) {
+        Ok(quality_presets) => {
+            println!(
+                "Quality presets: {} ({})",
+                config.quality_presets_file,
+                quality_presets.names().join(", ")
+            );
+            match load_quality(&quality_presets, config) {
+                Ok(quality) => println!(
+                    "Quality:         {}",
+                    serde_json::to_string(&quality).unwrap()
+                ),
+                Err(error) => println!("{}", error),
+            }
+        }
+        Err(error) => println!("{}", error),
+    }
+    match MaterialBank::try_load_from_file(&config.materials_file) {
+        Ok(materials) => {
+            println!(
+                "Materials:       {} ({} materials, {} images)",
+                config.materials_file,
+                materials.names().len(),
+                materials.images().len()
+            );
+            match Scene::try_load_from_file(&config.scene_file, &materials) {
+                Ok(scene) => println!(
+                    "Scene:           {} ({} files, {} primitives)",
+                    config.scene_file,
+                    scene.files().len(),
+                    scene.primitive_count()
+                ),
+                Err(error) => println!("{}", error),
+            }
+        }
+        Err(error) => println!("{}", error),
     }
 }
 
 // -----------------------------------------------------------------------------------------
 
-fn sample_scene_planes(ray: &Ray) -> RayHitResult {
-    arrt::intersect::ray_plane(&ray, &FLOOR_PLANE)
-}
+fn run_compare(args: &clap::ArgMatches, config: &Config) {
+    // Load reference
+    let reference_file = args.value_of("reference").unwrap();
+    let reference =
+        Framebuffer::load_from_file(reference_file).unwrap_or_else(|error| panic!("{}", error));
 
-// -----------------------------------------------------------------------------------------
+    // Measure convergence of the scene towards the reference?
+    if let Some(csv_file) = args.value_of("convergence") {
+        run_convergence(args, config, &reference, csv_file);
+        return;
+    }
 
-fn _sample_scene_sdf(ray: &Ray) -> RayHitResult {
-    let sphere_origin = Vec3::new(0.0, 9.5, 0.0);
-    let sphere_radius = 3.0;
-    let sphere_material = "green";
-
-    let mut ray_pos = ray.origin;
-    const MAX_STEPS: u32 = 50;
-    for _x in 0..MAX_STEPS {
-        let sphere_dist = _sdf_sphere(sphere_origin, sphere_radius, ray_pos);
-        if sphere_dist < EPSILON {
-            let normal = Vec3::normalize(ray_pos - sphere_origin);
-            let dist = Vec3::length(ray_pos - ray.origin);
-            return RayHitResult::new(true, dist, ray_pos, normal, sphere_material);
-        }
-        ray_pos += ray.direction * sphere_dist;
+    // Compare image
+    let image_file = args.value_of("image").unwrap();
+    let image = Framebuffer::load_from_file(image_file).unwrap_or_else(|error| panic!("{}", error));
+    let metrics = Metrics::compare(&reference, &image).unwrap_or_else(|error| panic!("{}", error));
+    println!("MSE:    {:.6}", metrics.mse);
+    println!("relMSE: {:.6}", metrics.rel_mse);
+    println!("PSNR:   {:.2} dB", metrics.psnr);
+    println!("SSIM:   {:.4}", metrics.ssim);
+
+    // Write error map?
+    if let Some(error_map_file) = args.value_of("error-map") {
+        metrics::error_map(&reference, &image)
+            .and_then(|error_map| error_map.save(error_map_file))
+            .unwrap_or_else(|error| panic!("{}", error));
     }
-    RayHitResult::NO_HIT
 }
 
 // -----------------------------------------------------------------------------------------
 
-fn _sdf_sphere(origin: Vec3, radius: f32, sample: Vec3) -> f32 {
-    // https://iquilezles.org/www/articles/distfunctions/distfunctions.htm
-    let to_origin = origin - sample;
-    Vec3::length(to_origin) - radius
+fn run_convergence(
+    args: &clap::ArgMatches,
+    config: &Config,
+    reference: &Framebuffer,
+    csv_file: &str,
+) {
+    // Load quality, materials and scene
+    let quality_presets = QualityPresetBank::load_from_file(&config.quality_presets_file);
+    let quality =
+        load_quality(&quality_presets, config).unwrap_or_else(|error| panic!("{}", error));
+    let materials = MaterialBank::load_from_file(&config.materials_file);
+    let scene = Scene::try_load_from_file(&config.scene_file, &materials)
+        .unwrap_or_else(|error| panic!("{}", error));
+    if (quality.image_width, quality.image_height) != (reference.width, reference.height) {
+        panic!(
+            "ERROR: Quality preset '{}' is {}x{}, reference is {}x{}",
+            quality.name,
+            quality.image_width,
+            quality.image_height,
+            reference.width,
+            reference.height
+        );
+    }
+
+    // Setup renderer
+    let camera = Camera::new(CAMERA_POSITION, CAMERA_LOOKAT, CAMERA_FOV);
+    let mut renderer = Renderer::new(quality, materials, camera);
+    renderer.seed = config.seed;
+    renderer.thread_count = config.thread_count;
+    renderer.spectral = args.is_present("spectral");
+
+    // Measure error after each sample per pixel
+    let mut csv = String::from("samples_per_pixel,time_s,mse,rel_mse,psnr,ssim\n");
+    let image = renderer.render_progressive(&scene, |progress, image| {
+        let metrics =
+            Metrics::compare(reference, image).unwrap_or_else(|error| panic!("{}", error));
+        let spp = progress.samples_per_pixel;
+        let time_s = progress.time_s;
+        println!(
+            "{}|{:.2}s|{:.6}|{:.6}|{:.2}|{:.4}",
+            spp, time_s, metrics.mse, metrics.rel_mse, metrics.psnr, metrics.ssim
+        );
+        csv += &format!(
+            "{},{:.3},{},{},{},{}\n",
+            spp, time_s, metrics.mse, metrics.rel_mse, metrics.psnr, metrics.ssim
+        );
+        true
+    });
+    fs::write(csv_file, csv)
+        .unwrap_or_else(|error| panic!("ERROR: Could not write '{}' ({})", csv_file, error));
+
+    // Save final render?
+    if let Some(output_file) = args.value_of("output-file") {
+        image
+            .save(output_file)
+            .unwrap_or_else(|error| panic!("{}", error));
+    }
 }
 
 // -----------------------------------------------------------------------------------------
 
-fn sample_background(ray: &Ray) -> Vec3 {
-    let t = (ray.direction.y + 1.0) * 0.5;
-    Vec3::lerp(SKY_COLOUR_BOTTOM, SKY_COLOUR_TOP, t)
+fn load_quality(
+    quality_presets: &QualityPresetBank,
+    config: &Config,
+) -> Result<QualityPreset, String> {
+    // Named preset, with fields overridden by the config file, --override and quality options
+    quality_presets
+        .try_get(&config.quality)?
+        .with_overrides(&config.overrides)
 }
 
 // -----------------------------------------------------------------------------------------
 
-fn watch_file(watcher: &mut hotwatch::Hotwatch, file: &str, flag: &Arc<AtomicBool>) {
+fn watch_file(watcher: &mut hotwatch::Hotwatch, file: &Path, flag: &Arc<AtomicBool>) {
     let flag_shared = Arc::clone(&flag);
     watcher
         .watch(file, move |event: Event| {
@@ -668,3 +678,19 @@ fn watch_file(watcher: &mut hotwatch::Hotwatch, file: &str, flag: &Arc<AtomicBoo
 }
 
 // -----------------------------------------------------------------------------------------
+
+fn watch_files(
+    watcher: &mut hotwatch::Hotwatch,
+    watched_files: &mut HashSet<PathBuf>,
+    files: &[PathBuf],
+    flag: &Arc<AtomicBool>,
+) {
+    // Files referenced by reloaded data are picked up as they appear
+    for file in files {
+        if file.exists() && watched_files.insert(file.clone()) {
+            watch_file(watcher, file, flag);
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------------------
@@ -0,0 +1,64 @@
+// -----------------------------------------------------------------------------------------
+// Checkpoints, resumed renders must match uninterrupted ones
+// -----------------------------------------------------------------------------------------
+
+use super::bits;
+use crate::arrt::checkpoint::Checkpoint;
+use crate::{QualityPreset, Renderer, Scene};
+use std::env;
+use std::fs;
+use std::process;
+
+// -----------------------------------------------------------------------------------------
+
+const SAMPLES_PER_PIXEL: usize = 16;
+const STOP_AFTER_PASSES: usize = 2;
+
+// -----------------------------------------------------------------------------------------
+
+fn load() -> (Renderer, Scene) {
+    let quality = QualityPreset {
+        name: String::from("checkpoint"),
+        image_width: 64,
+        image_height: 48,
+        samples_per_pixel: SAMPLES_PER_PIXEL,
+        max_bounces: 4,
+        time_limit_s: None,
+        noise_threshold: None,
+        adaptive: false,
+    };
+    super::load_renderer("primitives.json", quality)
+}
+
+// -----------------------------------------------------------------------------------------
+// Resume
+#[test]
+fn resume_after_time_limit() {
+    // Stop after a few passes, then resume with the time limit already spent, so the next pass
+    // is scheduled but stopped before any of it is traced
+    let (renderer, scene) = load();
+    let file = env::temp_dir().join(format!("arrt_resume_{}.checkpoint", process::id()));
+    let file = file.to_str().unwrap();
+    let mut stopped_renderer = renderer.clone();
+    stopped_renderer.checkpoint_file = Some(String::from(file));
+    let mut pass_count = 0;
+    stopped_renderer.render_progressive(&scene, |_, _| {
+        pass_count += 1;
+        pass_count < STOP_AFTER_PASSES
+    });
+    let checkpoint = Checkpoint::load_from_file(file).unwrap_or_else(|error| panic!("{}", error));
+    stopped_renderer.quality.time_limit_s = Some(checkpoint.time_s);
+    let stopped = stopped_renderer.resume_progressive(&scene, file, |_, _| true);
+    let checkpoint = Checkpoint::load_from_file(file).unwrap_or_else(|error| panic!("{}", error));
+
+    // Resume without a limit
+    let resumed = renderer.resume_progressive(&scene, file, |_, _| true);
+    fs::remove_file(file).unwrap();
+    stopped.unwrap_or_else(|error| panic!("{}", error));
+    let resumed = resumed.unwrap_or_else(|error| panic!("{}", error));
+    assert_eq!(checkpoint.pass_index, STOP_AFTER_PASSES);
+    assert!(
+        bits(&renderer.render(&scene)) == bits(&resumed),
+        "A resumed render differs from an uninterrupted one"
+    );
+}
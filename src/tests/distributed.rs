@@ -0,0 +1,108 @@
+// -----------------------------------------------------------------------------------------
+// Distributed renders with localhost workers, which must match local renders
+// -----------------------------------------------------------------------------------------
+
+use super::bits;
+use crate::arrt::distributed;
+use crate::arrt::distributed::Event;
+use crate::{Framebuffer, QualityPreset, Renderer, Scene};
+use std::io::Read;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+// -----------------------------------------------------------------------------------------
+
+const WORKER_COUNT: usize = 3;
+
+// -----------------------------------------------------------------------------------------
+
+fn load() -> (Renderer, Scene) {
+    // Large enough for several tiles
+    let quality = QualityPreset {
+        name: String::from("distributed"),
+        image_width: 300,
+        image_height: 200,
+        samples_per_pixel: 2,
+        max_bounces: 2,
+        time_limit_s: None,
+        noise_threshold: None,
+        adaptive: false,
+    };
+    super::load_renderer("primitives.json", quality)
+}
+
+fn start_workers(address: &str) -> Vec<thread::JoinHandle<Result<(), String>>> {
+    (0..WORKER_COUNT)
+        .map(|_| {
+            let address = String::from(address);
+            thread::spawn(move || distributed::work(&address, 1))
+        })
+        .collect()
+}
+
+fn drop_after_tile(address: &str) {
+    // Reads length prefixed frames until a tile is handed out, then disconnects
+    let mut stream = TcpStream::connect(address).unwrap();
+    loop {
+        let mut length = [0u8; 8];
+        stream.read_exact(&mut length).unwrap();
+        let mut frame = vec![0u8; u64::from_le_bytes(length) as usize];
+        stream.read_exact(&mut frame).unwrap();
+        if frame.starts_with(b"{\"Tile\"") {
+            return;
+        }
+    }
+}
+
+fn coordinate(renderer: &Renderer, scene: &Scene, listener: TcpListener) -> (Framebuffer, usize) {
+    let mut dropped_count = 0;
+    let image = distributed::coordinate(renderer, scene, listener, |event| {
+        if let Event::WorkerDropped { .. } = event {
+            dropped_count += 1;
+        }
+    })
+    .unwrap_or_else(|error| panic!("{}", error));
+    (image, dropped_count)
+}
+
+// -----------------------------------------------------------------------------------------
+// Workers
+#[test]
+fn distributed_matches_local() {
+    let (renderer, scene) = load();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let workers = start_workers(&address);
+    let (image, dropped_count) = coordinate(&renderer, &scene, listener);
+    for worker in workers {
+        worker.join().unwrap().unwrap();
+    }
+    assert_eq!(dropped_count, 0);
+    assert!(
+        bits(&renderer.render(&scene)) == bits(&image),
+        "A distributed render differs from a local one"
+    );
+}
+
+#[test]
+fn dropped_worker_tile_is_reassigned() {
+    // The only worker takes a tile and disconnects, the others start once it has gone
+    let (renderer, scene) = load();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let workers = thread::spawn(move || {
+        drop_after_tile(&address);
+        start_workers(&address)
+    });
+    let (image, dropped_count) = coordinate(&renderer, &scene, listener);
+    for worker in workers.join().unwrap() {
+        worker.join().unwrap().unwrap();
+    }
+    assert_eq!(dropped_count, 1);
+    assert!(
+        bits(&renderer.render(&scene)) == bits(&image),
+        "A distributed render with a dropped worker differs from a local one"
+    );
+}
+
+// -----------------------------------------------------------------------------------------
@@ -0,0 +1,303 @@
+// -----------------------------------------------------------------------------------------
+// Golden images, small scenes rendered headlessly and compared against stored references
+// (set ARRT_BLESS=1 to write new references after an intended change)
+// -----------------------------------------------------------------------------------------
+
+use crate::arrt::vector::Vec3;
+use crate::{Camera, Framebuffer, QualityPreset, Renderer};
+use image::RgbImage;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// -----------------------------------------------------------------------------------------
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+const SAMPLES_PER_PIXEL: usize = 16;
+const MAX_BOUNCES: u32 = 4;
+const SEED: u64 = 1;
+
+// Root mean square error over all channels (0 to 1) allowed before a render counts as changed
+const TOLERANCE: f32 = 0.02;
+
+// -----------------------------------------------------------------------------------------
+// GoldenTest Type
+struct GoldenTest {
+    name: &'static str,
+    scene: &'static str,
+    camera: Camera,
+    width: u32,
+    height: u32,
+    debug_normals: bool,
+    spectral: bool,
+    thread_count: Option<usize>,
+}
+
+// -----------------------------------------------------------------------------------------
+// GoldenTest Constructor
+impl GoldenTest {
+    fn new(name: &'static str, scene: &'static str) -> GoldenTest {
+        GoldenTest {
+            name,
+            scene,
+            camera: Camera::new(Vec3::new(0.0, 6.0, -20.0), Vec3::new(0.0, 3.0, 0.0), 60.0),
+            width: WIDTH,
+            height: HEIGHT,
+            debug_normals: false,
+            spectral: false,
+            thread_count: None,
+        }
+    }
+
+    fn camera(mut self, position: Vec3, lookat: Vec3, fov: f32) -> GoldenTest {
+        self.camera = Camera::new(position, lookat, fov);
+        self
+    }
+
+    fn resolution(mut self, width: u32, height: u32) -> GoldenTest {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    fn debug_normals(mut self) -> GoldenTest {
+        self.debug_normals = true;
+        self
+    }
+
+    fn spectral(mut self) -> GoldenTest {
+        self.spectral = true;
+        self
+    }
+
+    fn threads(mut self, thread_count: usize) -> GoldenTest {
+        self.thread_count = Some(thread_count);
+        self
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// GoldenTest Members
+impl GoldenTest {
+    fn check(self) {
+        let image = self.render();
+        let reference_file = super::golden_directory()
+            .join("reference")
+            .join(format!("{}.png", self.name));
+        if env::var_os("ARRT_BLESS").is_some() {
+            image.save(&reference_file).unwrap();
+            return;
+        }
+
+        // Compare against the stored reference
+        let reference = match image::open(&reference_file) {
+            Ok(reference) => reference.to_rgb8(),
+            Err(error) => panic!(
+                "Missing reference '{}' ({}), run with ARRT_BLESS=1 to create it",
+                reference_file.display(),
+                error
+            ),
+        };
+        assert_eq!(
+            reference.dimensions(),
+            image.dimensions(),
+            "Reference '{}' has a different resolution",
+            reference_file.display()
+        );
+        let error = rmse(&reference, &image);
+        if error > TOLERANCE {
+            // Keep the render and a difference image (scaled up so small changes are visible)
+            let output = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/golden");
+            fs::create_dir_all(&output).unwrap();
+            let actual_file = output.join(format!("{}.png", self.name));
+            let diff_file = output.join(format!("{}.diff.png", self.name));
+            image.save(&actual_file).unwrap();
+            difference(&reference, &image).save(&diff_file).unwrap();
+            panic!(
+                "'{}' differs from its reference (rmse {:.4} > {}), see '{}' and '{}'",
+                self.name,
+                error,
+                TOLERANCE,
+                actual_file.display(),
+                diff_file.display()
+            );
+        }
+    }
+
+    fn render(&self) -> RgbImage {
+        self.render_framebuffer().to_rgb_image()
+    }
+
+    fn render_framebuffer(&self) -> Framebuffer {
+        let (materials, scene) = super::load_scene(self.scene);
+
+        // Render (results don't depend on the thread count)
+        let quality = QualityPreset {
+            name: String::from(self.name),
+            image_width: self.width,
+            image_height: self.height,
+            samples_per_pixel: SAMPLES_PER_PIXEL,
+            max_bounces: MAX_BOUNCES,
+            time_limit_s: None,
+            noise_threshold: None,
+            adaptive: false,
+        };
+        let mut renderer = Renderer::new(quality, materials, self.camera.clone());
+        renderer.seed = SEED;
+        renderer.debug_normals = self.debug_normals;
+        renderer.spectral = self.spectral;
+        if let Some(thread_count) = self.thread_count {
+            renderer.thread_count = thread_count;
+        }
+        renderer.render(&scene)
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn rmse(a: &RgbImage, b: &RgbImage) -> f32 {
+    let mut sum = 0.0;
+    for (pixel_a, pixel_b) in a.pixels().zip(b.pixels()) {
+        for channel in 0..3 {
+            let delta = (f32::from(pixel_a[channel]) - f32::from(pixel_b[channel])) / 255.0;
+            sum += delta * delta;
+        }
+    }
+    (sum / (a.len() as f32)).sqrt()
+}
+
+// -----------------------------------------------------------------------------------------
+
+fn difference(a: &RgbImage, b: &RgbImage) -> RgbImage {
+    let mut image = RgbImage::new(a.width(), a.height());
+    for ((pixel_a, pixel_b), pixel) in a.pixels().zip(b.pixels()).zip(image.pixels_mut()) {
+        for channel in 0..3 {
+            let delta = (i32::from(pixel_a[channel]) - i32::from(pixel_b[channel])).abs();
+            pixel[channel] = (delta * 4).min(255) as u8;
+        }
+    }
+    image
+}
+
+// -----------------------------------------------------------------------------------------
+// Intersection edge cases
+#[test]
+fn primitives() {
+    GoldenTest::new("primitives", "primitives.json").check();
+}
+
+#[test]
+fn camera_inside_sphere() {
+    let (position, lookat) = (Vec3::ZERO, Vec3::new(0.0, 0.0, 1.0));
+    GoldenTest::new("camera_inside_sphere", "inside_sphere.json")
+        .camera(position, lookat, 90.0)
+        .check();
+}
+
+#[test]
+fn plane_at_grazing_angle() {
+    let (position, lookat) = (Vec3::new(0.0, 0.05, 0.0), Vec3::new(0.0, 0.05, 1.0));
+    GoldenTest::new("plane_at_grazing_angle", "plane_grazing.json")
+        .camera(position, lookat, 60.0)
+        .check();
+}
+
+// -----------------------------------------------------------------------------------------
+// Camera projections
+#[test]
+fn camera_wide_fov() {
+    let (position, lookat) = (Vec3::new(0.0, 6.0, -12.0), Vec3::new(0.0, 3.0, 0.0));
+    GoldenTest::new("camera_wide_fov", "primitives.json")
+        .camera(position, lookat, 120.0)
+        .check();
+}
+
+#[test]
+fn camera_narrow_fov() {
+    let (position, lookat) = (Vec3::new(0.0, 6.0, -40.0), Vec3::new(1.0, 2.0, 0.0));
+    GoldenTest::new("camera_narrow_fov", "primitives.json")
+        .camera(position, lookat, 15.0)
+        .check();
+}
+
+#[test]
+fn camera_portrait() {
+    GoldenTest::new("camera_portrait", "primitives.json")
+        .resolution(32, 64)
+        .check();
+}
+
+#[test]
+fn camera_off_axis() {
+    let (position, lookat) = (Vec3::new(-15.0, 12.0, -12.0), Vec3::new(2.0, 0.0, 2.0));
+    GoldenTest::new("camera_off_axis", "primitives.json")
+        .camera(position, lookat, 60.0)
+        .check();
+}
+
+// -----------------------------------------------------------------------------------------
+// Materials
+#[test]
+fn materials_metal_and_specular() {
+    GoldenTest::new("materials_metal_and_specular", "metals.json").check();
+}
+
+#[test]
+fn materials_principled() {
+    GoldenTest::new("materials_principled", "principled.json").check();
+}
+
+#[test]
+fn materials_procedural_textures() {
+    GoldenTest::new("materials_procedural_textures", "textures.json").check();
+}
+
+#[test]
+fn materials_subsurface_and_emission() {
+    GoldenTest::new("materials_subsurface_and_emission", "subsurface.json").check();
+}
+
+#[test]
+fn materials_dispersion_spectral() {
+    GoldenTest::new("materials_dispersion_spectral", "dispersion.json")
+        .spectral()
+        .check();
+}
+
+#[test]
+fn participating_media() {
+    GoldenTest::new("participating_media", "media.json").check();
+}
+
+#[test]
+fn fog_with_empty_regions() {
+    // Sky rays cross fog which is empty along their direction, tracking must still end
+    GoldenTest::new("fog_with_empty_regions", "fog.json").check();
+}
+
+#[test]
+fn debug_normals() {
+    GoldenTest::new("debug_normals", "primitives.json")
+        .debug_normals()
+        .check();
+}
+
+// -----------------------------------------------------------------------------------------
+// Thread count
+#[test]
+fn thread_count_does_not_change_results() {
+    // Every pixel has its own sample streams, so splitting the work differently changes nothing
+    let render = |thread_count| {
+        GoldenTest::new("thread_count", "primitives.json")
+            .threads(thread_count)
+            .render_framebuffer()
+    };
+    let (single, multiple) = (render(1), render(4));
+    assert!(
+        super::bits(&single) == super::bits(&multiple),
+        "Rendering with 1 and 4 threads gave different results"
+    );
+}
+
+// -----------------------------------------------------------------------------------------
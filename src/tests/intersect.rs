@@ -0,0 +1,231 @@
+// -----------------------------------------------------------------------------------------
+// Sphere, plane, box, distance field and CSG intersection edge cases
+// -----------------------------------------------------------------------------------------
+
+use crate::arrt::csg::{CsgNode, CsgObject};
+use crate::arrt::geometry::Cuboid;
+use crate::arrt::geometry::MaterialRef;
+use crate::arrt::geometry::Plane;
+use crate::arrt::geometry::Sphere;
+use crate::arrt::intersect;
+use crate::arrt::ray::Ray;
+use crate::arrt::sdf::{SdfNode, SdfObject};
+use crate::arrt::vector::Vec3;
+
+// -----------------------------------------------------------------------------------------
+
+fn unit_sphere() -> Sphere {
+    Sphere {
+        centre: Vec3::ZERO,
+        radius: 1.0,
+        material: MaterialRef::from(String::from("default")),
+    }
+}
+
+fn ground_plane() -> Plane {
+    Plane {
+        position: Vec3::ZERO,
+        normal: Vec3::UP,
+        material: MaterialRef::from(String::from("default")),
+    }
+}
+
+fn flat_box() -> Cuboid {
+    Cuboid {
+        min: Vec3::new(-1.0, 0.0, -1.0),
+        max: Vec3::new(1.0, 0.0, 1.0),
+        material: MaterialRef::from(String::from("default")),
+    }
+}
+
+fn sdf_sphere() -> SdfObject {
+    SdfObject {
+        shape: SdfNode::Sphere {
+            centre: Vec3::ZERO,
+            radius: 1.0,
+        },
+        material: MaterialRef::from(String::from("default")),
+    }
+}
+
+fn half_sphere() -> CsgObject {
+    // Sphere of radius 2, the half behind z = 0 carved away by a box
+    let sphere = Sphere {
+        centre: Vec3::ZERO,
+        radius: 2.0,
+        material: MaterialRef::from(String::from("default")),
+    };
+    let cuboid = Cuboid {
+        min: Vec3::new(-3.0, -3.0, -3.0),
+        max: Vec3::new(3.0, 3.0, 0.0),
+        material: MaterialRef::from(String::from("default")),
+    };
+    CsgObject {
+        shape: CsgNode::Difference {
+            a: Box::new(CsgNode::Sphere(sphere)),
+            b: Box::new(CsgNode::Box(cuboid)),
+        },
+    }
+}
+
+fn assert_near(actual: f32, expected: f32) {
+    assert!(
+        (actual - expected).abs() < 1e-4,
+        "expected {}, got {}",
+        expected,
+        actual
+    );
+}
+
+// -----------------------------------------------------------------------------------------
+// Sphere
+#[test]
+fn sphere_hit_from_outside() {
+    let ray = Ray::new(Vec3::new(0.0, 0.0, -5.0), Vec3::FORWARD);
+    let result = intersect::ray_sphere(&ray, &unit_sphere());
+    assert!(result.hit);
+    assert_near(result.distance, 4.0);
+    assert_near(result.normal.z, -1.0);
+}
+
+#[test]
+fn sphere_hit_from_inside_uses_exit() {
+    let ray = Ray::new(Vec3::ZERO, Vec3::FORWARD);
+    let result = intersect::ray_sphere(&ray, &unit_sphere());
+    assert!(result.hit);
+    assert_near(result.distance, 1.0);
+}
+
+#[test]
+fn sphere_behind_ray_is_missed() {
+    let ray = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::FORWARD);
+    assert!(!intersect::ray_sphere(&ray, &unit_sphere()).hit);
+}
+
+#[test]
+fn sphere_tangent_ray_hits_once() {
+    let ray = Ray::new(Vec3::new(0.0, 1.0, -5.0), Vec3::FORWARD);
+    let result = intersect::ray_sphere(&ray, &unit_sphere());
+    assert!(result.hit);
+    assert_near(result.distance, 5.0);
+}
+
+#[test]
+fn sphere_near_miss() {
+    let ray = Ray::new(Vec3::new(0.0, 1.001, -5.0), Vec3::FORWARD);
+    assert!(!intersect::ray_sphere(&ray, &unit_sphere()).hit);
+}
+
+#[test]
+fn sphere_hit_at_surface_ignores_origin() {
+    // Bounce rays start on the surface, they must not hit it again at distance zero
+    let ray = Ray::new(Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 0.0, -1.0));
+    assert!(!intersect::ray_sphere(&ray, &unit_sphere()).hit);
+}
+
+// -----------------------------------------------------------------------------------------
+// Plane
+#[test]
+fn plane_hit_from_above() {
+    let ray = Ray::new(Vec3::new(0.0, 2.0, 0.0), -Vec3::UP);
+    let result = intersect::ray_plane(&ray, &ground_plane());
+    assert!(result.hit);
+    assert_near(result.distance, 2.0);
+}
+
+#[test]
+fn plane_hit_from_below() {
+    let ray = Ray::new(Vec3::new(0.0, -2.0, 0.0), Vec3::UP);
+    let result = intersect::ray_plane(&ray, &ground_plane());
+    assert!(result.hit);
+    assert_near(result.distance, 2.0);
+}
+
+#[test]
+fn plane_parallel_ray_is_missed() {
+    let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::FORWARD);
+    assert!(!intersect::ray_plane(&ray, &ground_plane()).hit);
+}
+
+#[test]
+fn plane_pointing_away_is_missed() {
+    let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::UP);
+    assert!(!intersect::ray_plane(&ray, &ground_plane()).hit);
+}
+
+#[test]
+fn plane_grazing_ray_hits_far_away() {
+    let direction = Vec3::normalize(Vec3::new(0.0, -0.01, 1.0));
+    let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), direction);
+    let result = intersect::ray_plane(&ray, &ground_plane());
+    assert!(result.hit);
+    assert_near(result.position.y, 0.0);
+    assert!(result.position.z > 99.0);
+}
+
+// -----------------------------------------------------------------------------------------
+// Box
+#[test]
+fn flat_box_hit_from_above() {
+    let ray = Ray::new(Vec3::new(0.5, 2.0, 0.0), -Vec3::UP);
+    let result = intersect::ray_box(&ray, &flat_box());
+    assert!(result.hit);
+    assert_near(result.distance, 2.0);
+    assert_near(result.normal.y, 1.0);
+    assert!(!result.uv.x.is_nan() && !result.uv.y.is_nan());
+}
+
+#[test]
+fn flat_box_hit_from_below() {
+    let ray = Ray::new(Vec3::new(0.0, -2.0, 0.5), Vec3::UP);
+    let result = intersect::ray_box(&ray, &flat_box());
+    assert!(result.hit);
+    assert_near(result.normal.y, -1.0);
+}
+
+// -----------------------------------------------------------------------------------------
+// Distance field
+#[test]
+fn sdf_hit_from_outside() {
+    let ray = Ray::new(Vec3::new(0.0, 0.0, -5.0), Vec3::FORWARD);
+    let result = intersect::ray_sdf(&ray, &sdf_sphere());
+    assert!(result.hit);
+    assert_near(result.distance, 4.0);
+}
+
+#[test]
+fn sdf_hit_from_inside_uses_exit() {
+    // Refracted rays start inside the shape, they must reach the far side
+    let ray = Ray::new(Vec3::ZERO, Vec3::FORWARD);
+    let result = intersect::ray_sdf(&ray, &sdf_sphere());
+    assert!(result.hit);
+    assert_near(result.distance, 1.0);
+    assert_near(result.position.z, 1.0);
+}
+
+// -----------------------------------------------------------------------------------------
+// CSG
+#[test]
+fn csg_difference_carved_face_faces_ray() {
+    // The carved face is the box's far side, both normals must be turned towards the ray
+    let ray = Ray::new(Vec3::new(0.0, 0.0, -5.0), Vec3::FORWARD);
+    let result = intersect::ray_csg(&ray, &half_sphere());
+    assert!(result.hit);
+    assert_near(result.distance, 5.0);
+    assert!(Vec3::dot(result.normal, ray.direction) < 0.0);
+    assert!(Vec3::dot(result.shading_normal, ray.direction) < 0.0);
+}
+
+#[test]
+fn csg_difference_carved_face_keeps_handedness() {
+    // Flipping the normal alone would mirror the tangent frame
+    let ray = Ray::new(Vec3::new(0.5, 0.5, -5.0), Vec3::FORWARD);
+    let result = intersect::ray_csg(&ray, &half_sphere());
+    let (tangent, bitangent) = result.tangent_frame();
+    assert_near(
+        Vec3::dot(Vec3::cross(tangent, bitangent), result.shading_normal),
+        1.0,
+    );
+}
+
+// -----------------------------------------------------------------------------------------
@@ -0,0 +1,81 @@
+// -----------------------------------------------------------------------------------------
+// Image comparison metrics and error maps
+// -----------------------------------------------------------------------------------------
+
+use crate::arrt::metrics;
+use crate::arrt::metrics::Metrics;
+use crate::arrt::vector::Vec3;
+use crate::Framebuffer;
+
+// -----------------------------------------------------------------------------------------
+
+fn constant(width: u32, height: u32, value: f32) -> Framebuffer {
+    let pixels = vec![Vec3::ONE * value; (width * height) as usize];
+    Framebuffer::new(width, height, pixels)
+}
+
+fn gradient(width: u32, height: u32) -> Framebuffer {
+    let pixels = (0..width * height)
+        .map(|index| Vec3::ONE * ((index as f32) / ((width * height) as f32)))
+        .collect();
+    Framebuffer::new(width, height, pixels)
+}
+
+fn assert_near(actual: f32, expected: f32) {
+    assert!(
+        (actual - expected).abs() < 1e-4,
+        "expected {}, got {}",
+        expected,
+        actual
+    );
+}
+
+// -----------------------------------------------------------------------------------------
+// Metrics
+#[test]
+fn identical_images() {
+    let image = gradient(16, 12);
+    let metrics = Metrics::compare(&image, &image).unwrap();
+    assert_near(metrics.mse, 0.0);
+    assert_near(metrics.rel_mse, 0.0);
+    assert!(metrics.psnr.is_infinite() && metrics.psnr > 0.0);
+    assert_near(metrics.ssim, 1.0);
+}
+
+#[test]
+fn constant_offset() {
+    let metrics = Metrics::compare(&constant(16, 12, 0.25), &constant(16, 12, 0.35)).unwrap();
+    assert_near(metrics.mse, 0.01);
+    assert_near(metrics.rel_mse, 0.01 / ((0.25 * 0.25) + 0.01));
+    assert_near(metrics.psnr, 20.0);
+    assert!(metrics.ssim < 1.0);
+}
+
+#[test]
+fn mismatched_sizes() {
+    let (reference, image) = (constant(16, 12, 0.5), constant(12, 16, 0.5));
+    assert!(Metrics::compare(&reference, &image).is_err());
+    assert!(metrics::error_map(&reference, &image).is_err());
+}
+
+// -----------------------------------------------------------------------------------------
+// Error map
+#[test]
+fn error_map_colours() {
+    // No error is the first colour (dark blue), large errors clamp to the last (red)
+    let reference = constant(4, 4, 0.0);
+    let same = metrics::error_map(&reference, &reference).unwrap();
+    let different = metrics::error_map(&reference, &constant(4, 4, 10.0)).unwrap();
+    for pixel in &same.pixels {
+        assert_near(pixel.x, 0.0);
+        assert_near(pixel.y, 0.0);
+        assert_near(pixel.z, 0.5);
+    }
+    for pixel in &different.pixels {
+        assert_near(pixel.x, 1.0);
+        assert_near(pixel.y, 0.0);
+        assert_near(pixel.z, 0.0);
+    }
+}
+
+// -----------------------------------------------------------------------------------------
@@ -0,0 +1,55 @@
+// -----------------------------------------------------------------------------------------
+// Regression tests (golden images, intersection edge cases, image metrics, checkpoints,
+// distributed renders and the render service)
+// -----------------------------------------------------------------------------------------
+
+use crate::arrt::vector::Vec3;
+use crate::{Camera, Framebuffer, MaterialBank, QualityPreset, Renderer, Scene};
+use std::path::PathBuf;
+
+// -----------------------------------------------------------------------------------------
+
+mod checkpoint;
+mod distributed;
+mod golden;
+mod intersect;
+mod metrics;
+mod service;
+
+// -----------------------------------------------------------------------------------------
+
+fn golden_directory() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn load_scene(scene: &str) -> (MaterialBank, Scene) {
+    // A golden image scene, with the materials they share
+    let directory = golden_directory();
+    let materials_file = directory.join("materials.json");
+    let materials = MaterialBank::load_from_file(materials_file.to_str().unwrap());
+    let scene_file = directory.join("scenes").join(scene);
+    let scene = Scene::try_load_from_file(scene_file.to_str().unwrap(), &materials)
+        .unwrap_or_else(|error| panic!("{}", error));
+    (materials, scene)
+}
+
+fn load_renderer(scene: &str, quality: QualityPreset) -> (Renderer, Scene) {
+    // Seeded and on one thread, with the golden images' camera
+    let (materials, scene) = load_scene(scene);
+    let camera = Camera::new(Vec3::new(0.0, 6.0, -20.0), Vec3::new(0.0, 3.0, 0.0), 60.0);
+    let mut renderer = Renderer::new(quality, materials, camera);
+    renderer.seed = 1;
+    renderer.thread_count = 1;
+    (renderer, scene)
+}
+
+fn bits(framebuffer: &Framebuffer) -> Vec<[u32; 3]> {
+    // Exact values, for renders which must match bit for bit
+    framebuffer
+        .pixels
+        .iter()
+        .map(|pixel| [pixel.x.to_bits(), pixel.y.to_bits(), pixel.z.to_bits()])
+        .collect()
+}
+
+// -----------------------------------------------------------------------------------------
@@ -0,0 +1,161 @@
+// -----------------------------------------------------------------------------------------
+// Render service, jobs sent over a localhost connection
+// -----------------------------------------------------------------------------------------
+
+use crate::arrt::quality::QualityPresetBank;
+use crate::arrt::service;
+use crate::arrt::service::Service;
+use crate::arrt::vector::Vec3;
+use crate::{Camera, MaterialBank, QualityPreset, Renderer, Scene};
+use serde_json::{json, Value};
+use std::env;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// -----------------------------------------------------------------------------------------
+
+const JOB_TIMEOUT_S: u64 = 60;
+
+// -----------------------------------------------------------------------------------------
+
+fn start_service(directory: &Path) -> (Arc<Service>, String) {
+    // Serves until the test process exits
+    let presets_file = directory.join("quality_presets.json");
+    let materials_file = directory.join("tests/golden/materials.json");
+    let defaults = service::Defaults {
+        quality_presets: QualityPresetBank::load_from_file(presets_file.to_str().unwrap()),
+        quality: String::from("default"),
+        materials_file: String::from(materials_file.to_str().unwrap()),
+        seed: 0,
+        overrides: serde_json::Map::new(),
+        camera: camera(),
+        thread_count: 1,
+    };
+    let service = Arc::new(Service::new(defaults));
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let server = service.clone();
+    thread::spawn(move || server.serve(listener));
+    (service, address)
+}
+
+fn camera() -> Camera {
+    Camera::new(Vec3::new(0.0, 6.0, -20.0), Vec3::new(0.0, 3.0, 0.0), 60.0)
+}
+
+fn quality() -> QualityPreset {
+    QualityPreset {
+        name: String::from("service"),
+        image_width: 16,
+        image_height: 12,
+        samples_per_pixel: 1,
+        max_bounces: 1,
+        time_limit_s: None,
+        noise_threshold: None,
+        adaptive: false,
+    }
+}
+
+fn request(address: &str, method: &str, path: &str, body: &str) -> Value {
+    // One request per connection, the body of the response is JSON
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        address,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    serde_json::from_str(body).unwrap_or_else(|error| panic!("{} in '{}'", error, body))
+}
+
+fn wait_for_job(address: &str, id: usize) -> Value {
+    let begin = Instant::now();
+    loop {
+        let job = request(address, "GET", &format!("/jobs/{}", id), "");
+        if job["status"] != "queued" && job["status"] != "rendering" {
+            return job;
+        }
+        assert!(
+            begin.elapsed() < Duration::from_secs(JOB_TIMEOUT_S),
+            "The job didn't finish"
+        );
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+// -----------------------------------------------------------------------------------------
+// Failures
+#[test]
+fn panicking_render_fails_job() {
+    // The checkpoint can't be saved to a missing directory, the callback then panics on the
+    // render thread once the render stops
+    let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let (service, address) = start_service(&directory);
+    let materials_file = directory.join("tests/golden/materials.json");
+    let materials = MaterialBank::load_from_file(materials_file.to_str().unwrap());
+    let scene = Scene::try_load_from_str("test", r#"{"primitives": []}"#, &materials)
+        .unwrap_or_else(|error| panic!("{}", error));
+    let mut renderer = Renderer::new(quality(), materials, camera());
+    renderer.thread_count = 1;
+    let file = env::temp_dir()
+        .join(format!("arrt_missing_{}", process::id()))
+        .join("render.checkpoint");
+    renderer.checkpoint_file = Some(String::from(file.to_str().unwrap()));
+    renderer.on_checkpoint_error = Some(Arc::new(|error| panic!("{}", error)));
+    let (id, _) = service.queue((renderer, scene), Vec::new());
+    let failed = wait_for_job(&address, id);
+    assert_eq!(failed["status"], "failed", "{}", failed);
+    assert!(
+        failed["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("ERROR: Could not save checkpoint"),
+        "{}",
+        failed
+    );
+
+    // The next job still renders
+    let job = json!({"scene": {"primitives": []}, "quality": quality()});
+    let queued = request(&address, "POST", "/jobs", &job.to_string());
+    let finished = wait_for_job(&address, queued["id"].as_u64().unwrap() as usize);
+    assert_eq!(finished["status"], "finished", "{}", finished);
+}
+
+// -----------------------------------------------------------------------------------------
+// Paths
+#[test]
+fn paths_outside_working_directory_are_refused() {
+    let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let (_, address) = start_service(&directory);
+    let jobs = [
+        json!({"scene": {"include": ["../scene.json"]}}),
+        json!({"scene": {"include": ["scenes/../../scene.json"]}}),
+        json!({"scene": {"include": ["/etc/hostname"]}}),
+        json!({
+            "scene": {"primitives": []},
+            "materials": {"wood": {"diffuse": {"image": "/etc/hostname"}}}
+        }),
+    ];
+    for job in &jobs {
+        let refused = request(&address, "POST", "/jobs", &job.to_string());
+        let error = refused["error"].as_str().unwrap_or_default();
+        assert!(
+            error.contains("must be relative"),
+            "{} gave {}",
+            job,
+            refused
+        );
+    }
+}